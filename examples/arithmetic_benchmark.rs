@@ -0,0 +1,45 @@
+// Times a loop that does arithmetic on a few variables, so most of its time goes to evaluating
+// binary expressions.
+//
+// cargo run --release --example arithmetic_benchmark
+use std::time::{Duration, Instant};
+
+use jm::{
+    interpreter::{evaluate_program, generate_environment},
+    parser,
+};
+
+const SOURCE_CODE: &str = "
+func run(n) {
+    let total = 0
+    let i = 0
+    while i < n {
+        total = total + i * 3 - i % 7
+        i = i + 1
+    }
+    return total
+}
+run(300000)
+";
+
+const RUNS: u32 = 5;
+
+// The fastest of a few runs, which is the one least disturbed by anything else on the machine.
+fn time(run: impl Fn()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let program = parser::parse(SOURCE_CODE).expect("the benchmark parses");
+    let elapsed = time(|| {
+        evaluate_program(program.clone(), &generate_environment()).unwrap();
+    });
+    println!("300000 iterations: {:?}", elapsed);
+}
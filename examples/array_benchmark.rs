@@ -0,0 +1,47 @@
+// Times a loop that writes every element of a large array and then reads them all back.
+//
+// cargo run --release --example array_benchmark
+use std::time::{Duration, Instant};
+
+use jm::{
+    interpreter::{evaluate_program, generate_environment},
+    parser,
+};
+
+const SOURCE_CODE: &str = "
+func run(n) {
+    let b = [0]
+    while len(b) < n { b = b + b }
+    let i = 0
+    while i < n {
+        b[i] = i
+        i++
+    }
+    let total = 0
+    for x in b { total += x }
+    return total
+}
+run(20000)
+";
+
+const RUNS: u32 = 5;
+
+// The fastest of a few runs, which is the one least disturbed by anything else on the machine.
+fn time(run: impl Fn()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let program = parser::parse(SOURCE_CODE).expect("the benchmark parses");
+    let elapsed = time(|| {
+        evaluate_program(program.clone(), &generate_environment()).unwrap();
+    });
+    println!("20000 array writes and reads: {:?}", elapsed);
+}
@@ -0,0 +1,103 @@
+// Runs JM from Rust: a value and a few native functions are handed to the script as globals, the
+// script defines a function of its own, and its result is read back. A second interpreter is watched
+// by a step hook.
+//
+// cargo run --example embed
+use std::{cell::Cell, rc::Rc};
+
+use jm::{
+    error::{Error, ErrorType},
+    interpreter::{Interpreter, RuntimeValue, Step},
+};
+
+fn host_version(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if !arguments.is_empty() {
+        return Err(Error::new(
+            ErrorType::TypeError,
+            "host_version() takes no arguments".to_string(),
+            0,
+            0,
+        ));
+    }
+    Ok(RuntimeValue::String(env!("CARGO_PKG_VERSION").into()))
+}
+
+fn double(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    match arguments.as_slice() {
+        [RuntimeValue::Integer(i)] => Ok(RuntimeValue::Integer(i * 2)),
+        [RuntimeValue::Float(f)] => Ok(RuntimeValue::Float(f * 2.0)),
+        _ => Err(Error::new(
+            ErrorType::TypeError,
+            "double() expects a single number".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+fn run(interpreter: &mut Interpreter, source_code: &str) {
+    match interpreter.eval_str(source_code) {
+        Ok(value) => println!("{}", value),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error.render(source_code));
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+    interpreter.max_call_depth = 100;
+
+    interpreter.set_global("greeting", RuntimeValue::String("hello from rust".into()));
+    interpreter.register_native("host_version", host_version);
+    interpreter.register_native("double", double);
+
+    // Natives are closures, so they can keep state of the host program between calls.
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    interpreter.register_native("tick", move |_| {
+        counter.set(counter.get() + 1);
+        Ok(RuntimeValue::Integer(counter.get()))
+    });
+
+    run(&mut interpreter, "func shout(s) { upper(s) + \"!!\" }");
+    run(&mut interpreter, "shout(greeting)");
+    run(&mut interpreter, "\"running on jm \" + host_version()");
+    run(&mut interpreter, "double(21) + double(0.25)");
+    run(&mut interpreter, "double(\"twice\")");
+    run(&mut interpreter, "tick()\ntick()\ntick()");
+    println!("the script called tick() {} times", calls.get());
+    run(
+        &mut interpreter,
+        "func forever(n) { forever(n + 1) }\nforever(0)",
+    );
+
+    let answer = interpreter.eval_str("let answer = 6 * 7").unwrap();
+    assert_eq!(
+        interpreter
+            .get_global("answer")
+            .map(|value| value.to_string()),
+        Some(answer.to_string())
+    );
+
+    // A step hook sees every statement before it runs, here to count them and to stop the program
+    // once `total` grows too large.
+    let mut debugged = Interpreter::new();
+    let steps = Rc::new(Cell::new(0));
+    let counter = steps.clone();
+    debugged.on_step(move |_, environment| {
+        counter.set(counter.get() + 1);
+        match environment.get("total") {
+            Some(RuntimeValue::Integer(total)) if total > 100 => Step::Stop,
+            _ => Step::Continue,
+        }
+    });
+    run(
+        &mut debugged,
+        "let total = 0\nfor i in range(0, 3) { total += i }\ntotal",
+    );
+    println!("the step hook ran {} times", steps.get());
+    run(&mut debugged, "while true { total += 50 }");
+}
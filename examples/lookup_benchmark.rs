@@ -0,0 +1,66 @@
+// Times a tight loop that reads variables declared a few scopes out, once with every name searched
+// for scope by scope and once with each name resolved to its variable before the program runs.
+//
+// cargo run --release --example lookup_benchmark
+use std::time::{Duration, Instant};
+
+use jm::{
+    interpreter::{evaluate, evaluate_program, generate_environment},
+    parser,
+};
+
+const SOURCE_CODE: &str = "
+func run(n) {
+    let a = 1
+    let b = 2
+    let c = 3
+    let d = 4
+    let total = 0
+    let i = 0
+    while i < n {
+        if true {
+            if true {
+                if true {
+                    total = total + a + b + c + d + a * b + c * d + i
+                }
+            }
+        }
+        i = i + 1
+    }
+    return total
+}
+run(300000)
+";
+
+const RUNS: u32 = 5;
+
+// The fastest of a few runs, which is the one least disturbed by anything else on the machine.
+fn time(run: impl Fn()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let program = parser::parse(SOURCE_CODE).expect("the benchmark parses");
+
+    // `evaluate` runs the program as it was parsed, while `evaluate_program` resolves it first.
+    let searched = time(|| {
+        evaluate(&program, &generate_environment()).unwrap();
+    });
+    let resolved = time(|| {
+        evaluate_program(program.clone(), &generate_environment()).unwrap();
+    });
+
+    println!("searched: {:?}", searched);
+    println!("resolved: {:?}", resolved);
+    println!(
+        "speedup:  {:.2}x",
+        searched.as_secs_f64() / resolved.as_secs_f64()
+    );
+}
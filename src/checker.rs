@@ -0,0 +1,538 @@
+use crate::{
+    error::{Error, ErrorType},
+    interpreter::{apply_binary_operator, LazyIterable, NativeFn, RuntimeValue},
+    native_functions::NATIVE_CONSTANTS,
+    parser::{BinaryOperator, Node, Parameter, StringPart},
+};
+use std::collections::HashMap;
+
+// Finds the type errors a program is certain to run into, without running it. Only the types of
+// literals, of variables declared with a type and of constants are known, and everything else is
+// left alone, so a program that passes can still fail with a `TypeError` when it runs.
+pub fn check(program: &Node) -> Vec<Error> {
+    let globals = NATIVE_CONSTANTS
+        .iter()
+        .map(|(name, value)| (name.to_string(), Some(value.type_name())))
+        .collect();
+    let mut checker = Checker {
+        scopes: vec![Scope {
+            names: globals,
+            imported: false,
+        }],
+        errors: Vec::new(),
+    };
+
+    // The top level runs in the global scope, as it does in `evaluate_program`.
+    match program {
+        Node::Scope { body } => checker.check_statements(body),
+        node => checker.check_node(node),
+    }
+    checker.errors
+}
+
+struct Scope {
+    // The type of each name declared in the scope, or `None` when it can change while running.
+    names: HashMap<String, Option<&'static str>>,
+    // Whether the scope imports a file, whose names are not known until it runs.
+    imported: bool,
+}
+
+struct Checker {
+    scopes: Vec<Scope>,
+    errors: Vec<Error>,
+}
+
+impl Checker {
+    fn push_scope(&mut self, names: impl IntoIterator<Item = String>) {
+        self.scopes.push(Scope {
+            names: names.into_iter().map(|name| (name, None)).collect(),
+            imported: false,
+        });
+    }
+
+    fn declare(&mut self, name: &str, type_name: Option<&'static str>) {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.names.insert(name.to_string(), type_name);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&'static str> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(type_name) = scope.names.get(name) {
+                return *type_name;
+            }
+            if scope.imported {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn error(&mut self, message: String, line: u128, column: u128) {
+        self.errors
+            .push(Error::new(ErrorType::TypeError, message, line, column));
+    }
+
+    // Every name a block declares is known to be there before any of its statements are checked, so
+    // a function that runs later does not mistake it for a name of the same type further out.
+    fn check_statements(&mut self, statements: &[Node]) {
+        for statement in statements {
+            match statement {
+                Node::VariableDeclaration { name, .. } | Node::ConstantDeclaration { name, .. } => {
+                    for name in declared_names(name) {
+                        self.declare(&name, None);
+                    }
+                }
+                Node::FunctionDeclaration { name, .. } => self.declare(name, None),
+                _ => (),
+            }
+        }
+        for statement in statements {
+            self.check_node(statement);
+        }
+    }
+
+    fn check_block(&mut self, node: &Node, names: impl IntoIterator<Item = String>) {
+        self.push_scope(names);
+        match node {
+            Node::Scope { body } => self.check_statements(body),
+            node => self.check_node(node),
+        }
+        self.scopes.pop();
+    }
+
+    fn check_function(&mut self, parameters: &[Parameter], body: &Node) {
+        self.push_scope(parameters.iter().map(|parameter| parameter.name.clone()));
+        for parameter in parameters {
+            if let Some(default) = &parameter.default {
+                self.check_node(default);
+            }
+        }
+        self.check_block(body, Vec::new());
+        self.scopes.pop();
+    }
+
+    fn check_node(&mut self, node: &Node) {
+        match node {
+            Node::StringLiteral(_)
+            | Node::CharLiteral(_)
+            | Node::FloatLiteral(_)
+            | Node::IntegerLiteral(_)
+            | Node::BooleanLiteral(_)
+            | Node::NullLiteral
+            | Node::Identifier { .. }
+            | Node::Break { .. }
+            | Node::Continue { .. } => (),
+            Node::InterpolatedString { parts } => {
+                for part in parts {
+                    if let StringPart::Expression(expression) = part {
+                        self.check_node(expression);
+                    }
+                }
+            }
+            Node::ArrayLiteral(elements) => {
+                for element in elements {
+                    self.check_node(element);
+                }
+            }
+            Node::DictionaryLiteral(entries) => {
+                for (_, value) in entries {
+                    self.check_node(value);
+                }
+            }
+            Node::BinaryExpression {
+                left,
+                operand,
+                right,
+                line,
+                column,
+            } => {
+                self.check_node(left);
+                self.check_node(right);
+                if matches!(operand, BinaryOperator::And | BinaryOperator::Or) {
+                    return;
+                }
+                let (Some(l), Some(r)) = (self.infer(left), self.infer(right)) else {
+                    return;
+                };
+                if let Some(Err(error)) = binary_result(*operand, l, r) {
+                    if matches!(error.error_type(), ErrorType::TypeError) {
+                        self.error(
+                            format!("Cannot apply '{}' to {} and {}", operand, l, r),
+                            *line,
+                            *column,
+                        );
+                    }
+                }
+            }
+            Node::UnaryExpression {
+                operator,
+                operand,
+                line,
+                column,
+            } => {
+                self.check_node(operand);
+                if let Some(type_name) = self.infer(operand) {
+                    if unary_type(*operator, type_name).is_none() {
+                        self.error(
+                            format!("Cannot apply '{}' to {}", operator, type_name),
+                            *line,
+                            *column,
+                        );
+                    }
+                }
+            }
+            Node::UpdateExpression { target, .. } => self.check_node(target),
+            Node::IndexExpression {
+                collection, index, ..
+            } => {
+                self.check_node(collection);
+                self.check_node(index);
+            }
+            Node::SliceExpression {
+                collection,
+                start,
+                end,
+                ..
+            } => {
+                self.check_node(collection);
+                for bound in [start, end].into_iter().flatten() {
+                    self.check_node(bound);
+                }
+            }
+            Node::TernaryExpression {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.check_node(condition);
+                self.check_node(then);
+                self.check_node(otherwise);
+            }
+            Node::AssignmentExpression {
+                name,
+                value,
+                line,
+                column,
+            } => {
+                self.check_node(name);
+                self.check_node(value);
+                // A variable declared with a type keeps it for every value assigned to it later.
+                if let Node::Identifier { name, .. } = name.as_ref() {
+                    if let (Some(declared), Some(found)) = (self.lookup(name), self.infer(value)) {
+                        if declared != found {
+                            self.error(
+                                format!("Expected {} for '{}', got {}", declared, name, found),
+                                *line,
+                                *column,
+                            );
+                        }
+                    }
+                }
+            }
+            Node::VariableDeclaration {
+                name,
+                type_name,
+                value,
+                line,
+                column,
+            }
+            | Node::ConstantDeclaration {
+                name,
+                type_name,
+                value,
+                line,
+                column,
+            } => {
+                self.check_node(value);
+                let declared = type_name.as_deref().and_then(known_type);
+                let found = self.infer(value);
+                if let (Some(declared), Some(found), Node::Identifier { name, .. }) =
+                    (declared, found, name.as_ref())
+                {
+                    if declared != found {
+                        self.error(
+                            format!("Expected {} for '{}', got {}", declared, name, found),
+                            *line,
+                            *column,
+                        );
+                    }
+                }
+
+                // A constant keeps the type of the value it is declared with.
+                let constant = matches!(node, Node::ConstantDeclaration { .. });
+                let type_name = match name.as_ref() {
+                    Node::Identifier { .. } if constant => declared.or(found),
+                    Node::Identifier { .. } => declared,
+                    _ => None,
+                };
+                for name in declared_names(name) {
+                    self.declare(&name, type_name);
+                }
+            }
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Node::WhileLoop {
+                condition,
+                body: then_branch,
+                else_branch,
+                ..
+            } => {
+                self.check_node(condition);
+                self.check_node(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_node(else_branch);
+                }
+            }
+            Node::DoWhileLoop {
+                body, condition, ..
+            } => {
+                self.check_node(body);
+                self.check_node(condition);
+            }
+            Node::ForLoop {
+                variable,
+                iterable,
+                body,
+                else_branch,
+                ..
+            } => {
+                self.check_node(iterable);
+                self.check_block(body, declared_names(variable));
+                if let Some(else_branch) = else_branch {
+                    self.check_node(else_branch);
+                }
+            }
+            Node::FunctionDeclaration {
+                parameters, body, ..
+            }
+            | Node::FunctionExpression {
+                parameters, body, ..
+            } => self.check_function(parameters, body),
+            Node::CallExpression {
+                callee,
+                arguments,
+                line,
+                column,
+            } => {
+                self.check_node(callee);
+                for argument in arguments {
+                    self.check_node(argument);
+                }
+                if let Some(type_name) = self.infer(callee) {
+                    if type_name != "function" {
+                        self.error(
+                            format!("Cannot call {}, it is not a function", type_name),
+                            *line,
+                            *column,
+                        );
+                    }
+                }
+            }
+            Node::NamedArgument { value, .. } => self.check_node(value),
+            Node::ReturnStatement { value, .. } => {
+                if let Some(value) = value {
+                    self.check_node(value);
+                }
+            }
+            Node::Match { subject, arms, .. } => {
+                self.check_node(subject);
+                for (_, body) in arms {
+                    self.check_node(body);
+                }
+            }
+            Node::ThrowStatement { value, .. } => self.check_node(value),
+            Node::ImportStatement {
+                alias: Some(alias), ..
+            } => self.declare(alias, None),
+            Node::ImportStatement { alias: None, .. } => {
+                self.scopes.last_mut().unwrap().imported = true
+            }
+            Node::MemberAccess { object, .. } => self.check_node(object),
+            Node::TryCatch {
+                body,
+                error_name,
+                handler,
+                ..
+            } => {
+                self.check_node(body);
+                self.check_block(handler, [error_name.clone()]);
+            }
+            Node::Scope { .. } => self.check_block(node, Vec::new()),
+        }
+    }
+
+    // The type an expression is certain to have, as `type` would name it.
+    fn infer(&self, node: &Node) -> Option<&'static str> {
+        match node {
+            Node::StringLiteral(_) | Node::InterpolatedString { .. } => Some("string"),
+            Node::CharLiteral(_) => Some("char"),
+            Node::FloatLiteral(_) => Some("float"),
+            Node::IntegerLiteral(_) => Some("integer"),
+            Node::BooleanLiteral(_) => Some("boolean"),
+            Node::NullLiteral => Some("null"),
+            Node::ArrayLiteral(_) => Some("array"),
+            Node::DictionaryLiteral(_) => Some("dictionary"),
+            Node::FunctionExpression { .. } => Some("function"),
+            Node::Identifier { name, .. } => self.lookup(name),
+            Node::BinaryExpression {
+                operand: BinaryOperator::And | BinaryOperator::Or,
+                ..
+            } => Some("boolean"),
+            Node::BinaryExpression {
+                left,
+                operand,
+                right,
+                ..
+            } => binary_result(*operand, self.infer(left)?, self.infer(right)?)?.ok(),
+            Node::UnaryExpression {
+                operator, operand, ..
+            } => unary_type(*operator, self.infer(operand)?),
+            Node::TernaryExpression {
+                then, otherwise, ..
+            } => {
+                let type_name = self.infer(then)?;
+                (self.infer(otherwise)? == type_name).then_some(type_name)
+            }
+            Node::AssignmentExpression { value, .. } => self.infer(value),
+            _ => None,
+        }
+    }
+}
+
+fn declared_names(target: &Node) -> Vec<String> {
+    match target {
+        Node::Identifier { name, .. } => vec![name.clone()],
+        Node::ArrayLiteral(targets) => targets.iter().flat_map(declared_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn known_type(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "any" => None,
+        type_name => sample(type_name).map(|value| value.type_name()),
+    }
+}
+
+// What `left operand right` gives for values of the two types: the type of the result, or the error
+// the interpreter raises. The operator is applied to a value of each type, so the answer is the
+// interpreter's own.
+fn binary_result(
+    operand: BinaryOperator,
+    left: &str,
+    right: &str,
+) -> Option<Result<&'static str, Error>> {
+    let result = apply_binary_operator(operand, &sample(left)?, &sample(right)?);
+    Some(result.map(|value| value.type_name()))
+}
+
+fn unary_type(operator: char, type_name: &str) -> Option<&'static str> {
+    match (operator, type_name) {
+        ('-', "integer") | ('~', "integer") => Some("integer"),
+        ('-', "float") => Some("float"),
+        ('!', "boolean") => Some("boolean"),
+        _ => None,
+    }
+}
+
+// A value of the given type to try operators on.
+fn sample(type_name: &str) -> Option<RuntimeValue> {
+    Some(match type_name {
+        "null" => RuntimeValue::Null,
+        "integer" => RuntimeValue::Integer(1),
+        "float" => RuntimeValue::Float(1.0),
+        "string" => RuntimeValue::String("a".into()),
+        "char" => RuntimeValue::Char('a'),
+        "boolean" => RuntimeValue::Boolean(true),
+        "array" => RuntimeValue::Array(Vec::new().into()),
+        "range" => RuntimeValue::Iterable(LazyIterable::new(0, || Box::new(std::iter::empty()))),
+        "dictionary" => RuntimeValue::Dictionary(HashMap::new().into()),
+        "function" => RuntimeValue::NativeFunction {
+            name: "sample".to_string(),
+            function: NativeFn::new(|_| Ok(RuntimeValue::Null)),
+        },
+        "module" => RuntimeValue::Module {
+            name: "sample".to_string(),
+            members: HashMap::new().into(),
+        },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn errors(source_code: &str) -> Vec<String> {
+        check(&parser::parse(source_code).unwrap())
+            .iter()
+            .map(Error::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn flags_operators_applied_to_the_wrong_types() {
+        assert_eq!(
+            errors("let x = \"a\" - 1"),
+            ["TypeError: Cannot apply '-' to string and integer in line 1 column 13"]
+        );
+        assert_eq!(
+            errors("let x = -\"a\""),
+            ["TypeError: Cannot apply '-' to string in line 1 column 9"]
+        );
+    }
+
+    #[test]
+    fn flags_calls_to_values_that_are_not_functions() {
+        assert_eq!(
+            errors("const n = 5\nn()"),
+            ["TypeError: Cannot call integer, it is not a function in line 2 column 2"]
+        );
+        assert_eq!(
+            errors("\"text\"(1)"),
+            ["TypeError: Cannot call string, it is not a function in line 1 column 7"]
+        );
+    }
+
+    #[test]
+    fn flags_values_of_the_wrong_type_for_a_typed_variable() {
+        assert_eq!(
+            errors("let x: integer = \"a\""),
+            ["TypeError: Expected integer for 'x', got string in line 1 column 1"]
+        );
+        assert_eq!(
+            errors("let x: integer = 1\nx = \"a\""),
+            ["TypeError: Expected integer for 'x', got string in line 2 column 3"]
+        );
+        assert_eq!(
+            errors("let x: integer = 1\nfunc f() { x += 0.5 }"),
+            ["TypeError: Expected integer for 'x', got float in line 2 column 14"]
+        );
+    }
+
+    #[test]
+    fn passes_clean_code() {
+        let source_code = "
+            let count: integer = 0
+            let name = \"jm\"
+            func greet(who) { return \"hello \" + who }
+            for i in range(0, 3) { count += i }
+            count = count * 2
+            name = 5
+            println(greet(name), count - 1, [1] + [2])
+        ";
+        assert!(errors(source_code).is_empty());
+    }
+
+    #[test]
+    fn leaves_values_it_cannot_know_alone() {
+        assert!(errors("func f(x) { return x - 1 }\nf(\"a\")").is_empty());
+        assert!(errors("let x = 1\nx = \"a\"\nx()").is_empty());
+    }
+}
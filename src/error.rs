@@ -1,9 +1,18 @@
+use crate::interpreter::RuntimeValue;
+use std::rc::Rc;
+
 #[derive(Debug)]
 pub enum ErrorType {
     Error,
     SyntaxError,
     NameError,
     TypeError,
+    KeyError,
+    ZeroDivisionError,
+    OverflowError,
+    RecursionError,
+    AssertionError,
+    ImportError,
 }
 
 pub struct Error {
@@ -11,6 +20,12 @@ pub struct Error {
     message: String,
     line: u128,
     column: u128,
+    // Function calls the error passed through while unwinding, innermost first.
+    trace: Vec<(String, u128)>,
+    // The value given to `throw`, if the error was raised by the program itself.
+    thrown: Option<Box<RuntimeValue>>,
+    // The path and source of the imported file the error was raised in, if it was raised in one.
+    file: Option<Box<(String, Rc<str>)>>,
 }
 
 impl Error {
@@ -20,17 +35,153 @@ impl Error {
             message,
             line,
             column,
+            trace: Vec::new(),
+            thrown: None,
+            file: None,
+        }
+    }
+
+    pub fn thrown(value: RuntimeValue) -> Self {
+        let mut error = Error::new(ErrorType::Error, value.to_string(), 0, 0);
+        error.thrown = Some(Box::new(value));
+        error
+    }
+
+    // The value a catch block sees: what was thrown, or the message of a built-in error.
+    pub fn value(&self) -> RuntimeValue {
+        match &self.thrown {
+            Some(value) => (**value).clone(),
+            None => RuntimeValue::String(self.message.clone().into()),
+        }
+    }
+
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error_type
+    }
+
+    pub fn position(&self) -> (u128, u128) {
+        (self.line, self.column)
+    }
+
+    pub fn with_frame(mut self, function: &str, line: u128) -> Self {
+        self.trace.push((function.to_string(), line));
+        self
+    }
+
+    // Runs of the same frame, as left behind by deep recursion, are shown once with a count, and so are
+    // runs of the same few frames, as left behind by functions that call each other. Functions called
+    // back by a built-in have no call site of their own, so their frames have no line.
+    fn render_trace(&self) -> String {
+        let frames = self
+            .trace
+            .iter()
+            .rev()
+            .map(|(function, line)| match line {
+                0 => format!("in {}", function),
+                _ => format!("in {} (line {})", function, line),
+            })
+            .collect::<Vec<String>>();
+
+        let mut rendered = Vec::new();
+        let mut start = 0;
+        while start < frames.len() {
+            let (length, count) = repeated_cycle(&frames[start..]);
+            let cycle = frames[start..start + length].join(" -> ");
+            rendered.push(match (length, count) {
+                (_, 1) => cycle,
+                (1, _) => format!("{} x{}", cycle, count),
+                _ => format!("({}) repeated {} times", cycle, count),
+            });
+            start += length * count;
+        }
+        rendered.join(" -> ")
+    }
+
+    // Fills in the position of an error that was raised without one.
+    pub fn with_position(mut self, line: u128, column: u128) -> Self {
+        if self.line == 0 && self.column == 0 {
+            self.line = line;
+            self.column = column;
+        }
+        self
+    }
+
+    // Errors from a file imported by another imported file keep the innermost file.
+    pub fn in_file(mut self, path: &str, source_code: &str) -> Self {
+        if self.file.is_none() {
+            self.file = Some(Box::new((path.to_string(), source_code.into())));
         }
+        self
+    }
+
+    // Renders the error followed by the offending source line with a caret under the column. The
+    // line is taken from the imported file the error was raised in, if there is one.
+    pub fn render(&self, source_code: &str) -> String {
+        let source_code = match self.file.as_deref() {
+            Some((_, source_code)) => source_code,
+            None => source_code,
+        };
+        let source_line = match (self.line as usize).checked_sub(1) {
+            Some(index) => source_code.lines().nth(index),
+            None => None,
+        };
+        let source_line = match source_line {
+            Some(source_line) => source_line,
+            None => return self.to_string(),
+        };
+
+        let number = self.line.to_string();
+        let padding = source_line
+            .chars()
+            .take((self.column as usize).saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        format!(
+            "{}\n{} | {}\n{} | {}^",
+            self,
+            number,
+            source_line,
+            " ".repeat(number.len()),
+            padding
+        )
     }
 }
 
+// The longest cycle of frames checked for, which is enough for a handful of functions calling each
+// other in turn.
+const MAX_CYCLE_LENGTH: usize = 8;
+
+// The number of frames in the cycle that repeats most often at the start of `frames`, and how many
+// times it repeats. Of cycles covering the same frames, the shortest wins.
+fn repeated_cycle(frames: &[String]) -> (usize, usize) {
+    let mut best = (1, 1);
+    for length in 1..=MAX_CYCLE_LENGTH.min(frames.len()) {
+        let cycle = &frames[..length];
+        let count = frames
+            .chunks_exact(length)
+            .take_while(|chunk| *chunk == cycle)
+            .count();
+        if count > 1 && length * count > best.0 * best.1 {
+            best = (length, count);
+        }
+    }
+    best
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{:?}: {} in line {} column {}",
             self.error_type, self.message, self.line, self.column
-        )
+        )?;
+        if let Some((path, _)) = self.file.as_deref() {
+            write!(f, " of '{}'", path)?;
+        }
+        if !self.trace.is_empty() {
+            write!(f, "\n{}", self.render_trace())?;
+        }
+        Ok(())
     }
 }
 
@@ -38,8 +189,46 @@ impl std::fmt::Debug for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Error {{ error_type: {:?}, message: {}, line: {}, column: {} }}",
-            self.error_type, self.message, self.line, self.column
+            "Error {{ error_type: {:?}, message: {}, line: {}, column: {}, trace: [{}] }}",
+            self.error_type,
+            self.message,
+            self.line,
+            self.column,
+            self.render_trace()
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::run;
+
+    #[test]
+    fn errors_display_their_type_message_and_position() {
+        let error = Error::new(ErrorType::TypeError, "Bad value".to_string(), 3, 7);
+        assert_eq!(error.to_string(), "TypeError: Bad value in line 3 column 7");
+        assert_eq!(error.with_position(1, 1).position(), (3, 7));
+    }
+
+    #[test]
+    fn undefined_variables_are_reported_where_they_are_used() {
+        assert_eq!(
+            run("let a = 1\nlet b = a +   c"),
+            Err("NameError: 'c' is undefined in line 2 column 15".to_string())
+        );
+        assert_eq!(
+            run("func f() {\n    return missing\n}\nf()"),
+            Err("NameError: 'missing' is undefined in line 2 column 12\nin f (line 4)".to_string())
+        );
+    }
+
+    #[test]
+    fn rendered_errors_point_at_the_column() {
+        let error = Error::new(ErrorType::NameError, "'c' is undefined".to_string(), 2, 5);
+        assert_eq!(
+            error.render("let a = 1\n\tx + c"),
+            "NameError: 'c' is undefined in line 2 column 5\n2 | \tx + c\n  | \t   ^"
+        );
+    }
+}
@@ -1,281 +1,3538 @@
 use crate::{
     error::{Error, ErrorType},
-
-    parser::Node,
+    native_functions,
+    parser::{self, BinaryOperator, Node, Parameter, Pattern, StringPart},
+    resolver, unparser,
+};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
-use std::collections::HashMap;
 
+// Strings, collections and function bodies are shared behind `Rc`, so copying a value out of a
+// variable is cheap. Collections are copied on write when one of the sharers changes them.
 #[derive(Debug, Clone)]
 pub enum RuntimeValue {
     Null,
     Integer(i128),
     Float(f64),
-    String(String),
+    String(Rc<str>),
+    Char(char),
     Boolean(bool),
-    Array(Vec<RuntimeValue>),
-    Iterable(Vec<Node>),
+    Array(Rc<Vec<RuntimeValue>>),
+    Dictionary(Rc<HashMap<String, RuntimeValue>>),
+    Iterable(LazyIterable),
     Function {
-        args: Vec<RuntimeValue>,
-        body: Vec<Node>,
+        name: String,
+        parameters: Rc<Vec<Parameter>>,
+        body: Rc<Vec<Node>>,
+        closure: Rc<RefCell<Environment>>,
+    },
+    NativeFunction {
+        name: String,
+        function: NativeFn,
+    },
+    // The top-level names of a file imported with `import "path" as name`.
+    Module {
+        name: String,
+        members: Rc<HashMap<String, RuntimeValue>>,
     },
 }
 
-pub fn generate_environment() -> HashMap<String, RuntimeValue> {
-    let environment = HashMap::new();
+// A function implemented in Rust. It is a closure rather than a plain `fn` so that host programs
+// can expose functions that capture their own state.
+#[derive(Clone)]
+pub struct NativeFn(Rc<dyn Fn(Vec<RuntimeValue>) -> Result<RuntimeValue, Error>>);
+
+impl NativeFn {
+    pub fn new(
+        function: impl Fn(Vec<RuntimeValue>) -> Result<RuntimeValue, Error> + 'static,
+    ) -> Self {
+        NativeFn(Rc::new(function))
+    }
+
+    pub fn call(&self, arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+        (self.0)(arguments)
+    }
+}
+
+impl std::fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFn")
+    }
+}
+
+// A sequence whose elements are produced one at a time as a loop asks for them, so `range` does not
+// need memory for every number in it. Each loop starts a fresh iterator, which lets the same
+// iterable be looped over more than once.
+#[derive(Clone)]
+pub struct LazyIterable {
+    length: u128,
+    iterator: Rc<dyn Fn() -> Box<dyn Iterator<Item = RuntimeValue>>>,
+}
+
+impl LazyIterable {
+    pub fn new(
+        length: u128,
+        iterator: impl Fn() -> Box<dyn Iterator<Item = RuntimeValue>> + 'static,
+    ) -> Self {
+        LazyIterable {
+            length,
+            iterator: Rc::new(iterator),
+        }
+    }
+
+    pub fn len(&self) -> u128 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = RuntimeValue>> {
+        (self.iterator)()
+    }
+}
+
+impl std::fmt::Debug for LazyIterable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LazyIterable({})", self.length)
+    }
+}
+
+impl RuntimeValue {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            RuntimeValue::Null => "null",
+            RuntimeValue::Integer(_) => "integer",
+            RuntimeValue::Float(_) => "float",
+            RuntimeValue::String(_) => "string",
+            RuntimeValue::Char(_) => "char",
+            RuntimeValue::Boolean(_) => "boolean",
+            RuntimeValue::Array(_) => "array",
+            RuntimeValue::Iterable(_) => "range",
+            RuntimeValue::Dictionary(_) => "dictionary",
+            RuntimeValue::Function { .. } | RuntimeValue::NativeFunction { .. } => "function",
+            RuntimeValue::Module { .. } => "module",
+        }
+    }
+
+    // Strings and characters inside arrays and dictionaries are quoted so that `["a, b"]` and
+    // `["a", "b"]` print differently.
+    pub(crate) fn to_nested_string(&self) -> String {
+        match self {
+            RuntimeValue::String(s) => format!("{:?}", s),
+            RuntimeValue::Char(c) => format!("{:?}", c),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeValue::Null => write!(f, "null"),
+            RuntimeValue::Integer(i) => write!(f, "{}", i),
+            RuntimeValue::Float(n) => write!(f, "{:?}", n),
+            RuntimeValue::String(s) => write!(f, "{}", s),
+            RuntimeValue::Char(c) => write!(f, "{}", c),
+            RuntimeValue::Boolean(b) => write!(f, "{}", b),
+            RuntimeValue::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(RuntimeValue::to_nested_string)
+                    .collect::<Vec<String>>();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            RuntimeValue::Dictionary(entries) => {
+                let mut keys = entries.keys().collect::<Vec<&String>>();
+                keys.sort();
+                let entries = keys
+                    .into_iter()
+                    .map(|key| format!("{:?}: {}", key, entries[key].to_nested_string()))
+                    .collect::<Vec<String>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            RuntimeValue::Iterable(_) => write!(f, "<iterable>"),
+            RuntimeValue::Function { name, .. } => write!(f, "<function {}>", name),
+            RuntimeValue::NativeFunction { name, .. } => write!(f, "<native function {}>", name),
+            RuntimeValue::Module { name, .. } => write!(f, "<module {}>", name),
+        }
+    }
+}
+
+pub struct Environment {
+    // The value of each variable, in the order the variables were declared. The resolver numbers
+    // variables the same way, so a resolved identifier can read its variable by position.
+    slots: Vec<RuntimeValue>,
+    // The slot of each variable.
+    names: HashMap<String, usize>,
+    constants: HashSet<String>,
+    // The declared type of each variable that was given one.
+    types: HashMap<String, String>,
+    parent: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new(parent: Option<Rc<RefCell<Environment>>>) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            slots: Vec::new(),
+            names: HashMap::new(),
+            constants: HashSet::new(),
+            types: HashMap::new(),
+            parent,
+        }))
+    }
+}
+
+impl Environment {
+    // Looks a name up here and then in the enclosing scopes, for tools that inspect a running program.
+    pub fn get(&self, name: &str) -> Option<RuntimeValue> {
+        match (self.value(name), &self.parent) {
+            (Some(value), _) => Some(value.clone()),
+            (None, Some(parent)) => parent.borrow().get(name),
+            (None, None) => None,
+        }
+    }
+
+    fn value(&self, name: &str) -> Option<&RuntimeValue> {
+        self.names.get(name).map(|&slot| &self.slots[slot])
+    }
+
+    // Declares `name`, or replaces its value if it is already declared.
+    fn set(&mut self, name: &str, value: RuntimeValue) {
+        match self.names.get(name) {
+            Some(&slot) => self.slots[slot] = value,
+            None => {
+                self.names.insert(name.to_string(), self.slots.len());
+                self.slots.push(value);
+            }
+        }
+    }
+
+    // The variables in the order they were declared.
+    fn variables(&self) -> Vec<(&String, &RuntimeValue)> {
+        let mut variables = self
+            .names
+            .iter()
+            .map(|(name, &slot)| (name, &self.slots[slot]))
+            .collect::<Vec<(&String, &RuntimeValue)>>();
+        variables.sort_by_key(|(name, _)| self.names[*name]);
+        variables
+    }
+}
+
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Functions capture the environment they are declared in, which in turn
+        // holds the function, so only the names are printed to avoid recursing.
+        let mut names = self.names.keys().collect::<Vec<&String>>();
+        names.sort();
+        write!(f, "Environment {{ names: {:?} }}", names)
+    }
+}
+
+pub fn generate_environment() -> Rc<RefCell<Environment>> {
+    let environment = Environment::new(None);
 
-    // environment.insert(
-    //     "print".to_string(),
-    //     RuntimeValue::NativeFunction {
-    //         args: vec![],
-    //         function_call: std::sync::Arc::new(|x| nf_print(x)),
-    //     },
-    // );
+    for (name, function) in native_functions::NATIVE_FUNCTIONS {
+        define_native(&environment, name, NativeFn::new(function));
+    }
+    for (name, value) in native_functions::NATIVE_CONSTANTS {
+        let mut global = environment.borrow_mut();
+        global.set(name, value);
+        global.constants.insert(name.to_string());
+    }
 
     environment
 }
 
-fn declare(
-    hashmap: &mut HashMap<String, RuntimeValue>,
-    name: String,
-    value: RuntimeValue,
-) -> Result<(), Error> {
-    if hashmap.contains_key(&name) {
-        Err(Error::new(
+// Built-in functions and the ones registered by a host program are defined the same way, so a host
+// can also replace a built-in.
+fn define_native(env: &Rc<RefCell<Environment>>, name: &str, function: NativeFn) {
+    let mut environment = env.borrow_mut();
+    environment.constants.remove(name);
+    environment.set(
+        name,
+        RuntimeValue::NativeFunction {
+            name: name.to_string(),
+            function,
+        },
+    );
+}
+
+fn declare(env: &Rc<RefCell<Environment>>, name: String, value: RuntimeValue) -> Result<(), Error> {
+    let mut environment = env.borrow_mut();
+    let environment = &mut *environment;
+    match environment.names.entry(name) {
+        Entry::Occupied(entry) => Err(Error::new(
             ErrorType::NameError,
-            format!("Variable '{}' already declared", name),
+            format!("Variable '{}' already declared in this scope", entry.key()),
             0,
             0,
-        ))
-    } else {
-        hashmap.insert(name, value);
-        Ok(())
+        )),
+        Entry::Vacant(entry) => {
+            entry.insert(environment.slots.len());
+            environment.slots.push(value);
+            Ok(())
+        }
     }
 }
 
-fn assign(
-    hashmap: &mut HashMap<String, RuntimeValue>,
+fn declare_constant(
+    env: &Rc<RefCell<Environment>>,
     name: String,
     value: RuntimeValue,
 ) -> Result<(), Error> {
-    if hashmap.contains_key(&name) {
-        hashmap.insert(name, value);
-        Ok(())
-    } else {
-        Err(Error::new(
+    declare(env, name.clone(), value)?;
+    env.borrow_mut().constants.insert(name);
+    Ok(())
+}
+
+// `assign` and `lookup` walk outwards through the parent scopes while `declare`
+// only touches the innermost one. A function call's scope has the function's
+// captured environment as its parent, so captured variables are found and
+// updated in place, and the change is visible to later calls.
+fn assign(
+    env: &Rc<RefCell<Environment>>,
+    name: &str,
+    slot: Option<usize>,
+    value: RuntimeValue,
+) -> Result<(), Error> {
+    let (scope, slot) = assignable(env, name, slot)?;
+    let mut scope = scope.borrow_mut();
+    if let Some(type_name) = scope.types.get(name) {
+        check_type(name, type_name, &value)?;
+    }
+    scope.slots[slot] = value;
+    Ok(())
+}
+
+// The scope that holds the variable `name` and its slot there, unless it is a constant.
+fn assignable(
+    env: &Rc<RefCell<Environment>>,
+    name: &str,
+    slot: Option<usize>,
+) -> Result<(Rc<RefCell<Environment>>, usize), Error> {
+    let (scope, slot) = find_variable(env, name, slot).ok_or_else(|| undefined(name))?;
+    if scope.borrow().constants.contains(name) {
+        return Err(Error::new(
             ErrorType::NameError,
-            format!("'{}' is undefined", name),
+            format!("Cannot assign to constant '{}'", name),
             0,
             0,
-        ))
+        ));
     }
+    Ok((scope, slot))
 }
 
-fn lookup(hashmap: &mut HashMap<String, RuntimeValue>, name: String) -> Option<RuntimeValue> {
-    hashmap.get(&name).cloned()
+fn lookup(env: &Rc<RefCell<Environment>>, name: &str) -> Option<RuntimeValue> {
+    let (scope, slot) = find_variable(env, name, None)?;
+    let value = scope.borrow().slots[slot].clone();
+    Some(value)
 }
 
-pub fn evaluate(
-    node: Node,
-    env: &mut HashMap<String, RuntimeValue>,
-) -> Result<RuntimeValue, Error> {
-    match node {
-        Node::Scope { body: statements } => {
-            let mut result = RuntimeValue::Null;
-            for statement in statements {
-                result = evaluate(statement, env)?;
-            }
+// The value in `slot` of the scope `depth` scopes out, if its variable has been declared.
+fn read_slot(env: &Environment, depth: usize, slot: usize) -> Option<RuntimeValue> {
+    match depth {
+        0 => env.slots.get(slot).cloned(),
+        _ => read_slot(&env.parent.as_ref()?.borrow(), depth - 1, slot),
+    }
+}
+
+// The scope that holds the variable `name` and its slot there. The slot the resolver found is used
+// once the declaration has run, which it has when the scope has that many variables, because
+// variables are numbered in the order they are declared. Otherwise the scopes are searched by name.
+fn find_variable(
+    env: &Rc<RefCell<Environment>>,
+    name: &str,
+    slot: Option<usize>,
+) -> Option<(Rc<RefCell<Environment>>, usize)> {
+    if let Some(slot) = slot {
+        if slot < env.borrow().slots.len() {
+            return Some((env.clone(), slot));
+        }
+    }
+
+    let mut scope = Some(env.clone());
+    while let Some(current) = scope {
+        let found = current.borrow().names.get(name).copied();
+        if let Some(slot) = found {
+            return Some((current, slot));
+        }
+        scope = current.borrow().parent.clone();
+    }
+
+    None
+}
+
+fn undefined(name: &str) -> Error {
+    Error::new(
+        ErrorType::NameError,
+        format!("'{}' is undefined", name),
+        0,
+        0,
+    )
+}
+
+// The scope `depth` scopes out, where the resolver found the name's declaration. Searching from
+// there finds the same value as searching from `env`, because the scopes in between never declare
+// the name, and it still finds a declaration the resolver could not see further out.
+fn resolved_scope(
+    env: &Rc<RefCell<Environment>>,
+    depth: Option<usize>,
+) -> Rc<RefCell<Environment>> {
+    let mut scope = env.clone();
+    for _ in 0..depth.unwrap_or(0) {
+        let parent = scope.borrow().parent.clone();
+        match parent {
+            Some(parent) => scope = parent,
+            None => break,
+        }
+    }
+    scope
+}
+
+pub enum Flow {
+    Normal(RuntimeValue),
+    Return(RuntimeValue),
+    Break {
+        line: u128,
+        column: u128,
+    },
+    Continue {
+        line: u128,
+        column: u128,
+    },
+    // `return f(...)` where `f` is written in JM. The call is left to the function returning, so that
+    // a function returning a call to itself can loop instead of nesting one call deeper each time.
+    TailCall {
+        callee: RuntimeValue,
+        positional: Vec<RuntimeValue>,
+        named: NamedArguments,
+        line: u128,
+        column: u128,
+    },
+}
+
+// A `break` or `continue` that reaches a function body or the top of the program was not inside a loop.
+// The parser already rejects these, so only a syntax tree built by hand gets this far.
+fn flow_value(flow: Flow) -> Result<RuntimeValue, Error> {
+    match flow {
+        Flow::Normal(value) | Flow::Return(value) => Ok(value),
+        Flow::TailCall {
+            callee,
+            positional,
+            named,
+            line,
+            column,
+        } => {
+            call(callee, positional, named, line).map_err(|error| error.with_position(line, column))
+        }
+        Flow::Break { line, column } => Err(Error::new(
+            ErrorType::SyntaxError,
+            "'break' outside of a loop".to_string(),
+            line,
+            column,
+        )),
+        Flow::Continue { line, column } => Err(Error::new(
+            ErrorType::SyntaxError,
+            "'continue' outside of a loop".to_string(),
+            line,
+            column,
+        )),
+    }
+}
+
+// How many `evaluate` and `execute` calls may be nested before evaluation stops with a
+// `RecursionError` instead of overflowing the native stack.
+pub const MAX_DEPTH: usize = 10_000;
+
+// How many user function calls may be nested before a call fails with a `RecursionError`, unless
+// changed with `set_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1_000;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static MAX_CALL_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_CALL_DEPTH) };
+    // The script being run followed by the files it is importing, innermost last.
+    static FILES: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+    static TRACE: Cell<bool> = const { Cell::new(false) };
+    static STEP_HOOK: RefCell<Option<StepHook>> = const { RefCell::new(None) };
+}
+
+// What a step hook tells the interpreter to do with the statement it is about to run. A debugger
+// can pause a program by not returning until it should go on.
+pub enum Step {
+    Continue,
+    Stop,
+}
+
+type StepHook = Rc<dyn Fn(&Node, &Environment) -> Step>;
+
+// Called before every statement of a scope, with the scope it runs in.
+fn step(statement: &Node, env: &Rc<RefCell<Environment>>) -> Result<(), Error> {
+    let Some(hook) = STEP_HOOK.with_borrow(Option::clone) else {
+        return Ok(());
+    };
+    match hook(statement, &env.borrow()) {
+        Step::Continue => Ok(()),
+        Step::Stop => {
+            let (line, column) = statement.position();
+            Err(Error::new(
+                ErrorType::Error,
+                "Execution stopped by the step hook".to_string(),
+                line,
+                column,
+            ))
+        }
+    }
+}
+
+pub fn set_max_call_depth(limit: usize) {
+    MAX_CALL_DEPTH.set(limit);
+}
+
+// While tracing, every node is printed to standard error as it is evaluated, followed by what it
+// produced, indented by how deeply evaluation is nested.
+pub fn set_trace(enabled: bool) {
+    TRACE.set(enabled);
+}
 
-            Ok(result)
+fn trace_node(node: &Node) {
+    let source = unparser::unparse_node(node);
+    let mut lines = source.lines();
+    let first = lines.next().unwrap_or_default();
+    let more = if lines.next().is_some() { " ..." } else { "" };
+    eprintln!("{}{}{}", trace_indent(), first, more);
+}
+
+fn trace_result(result: Result<String, &Error>) {
+    match result {
+        Ok(outcome) => eprintln!("{}=> {}", trace_indent(), outcome),
+        Err(error) => eprintln!(
+            "{}=> error {}",
+            trace_indent(),
+            error.value().to_nested_string()
+        ),
+    }
+}
+
+fn trace_indent() -> String {
+    "  ".repeat(DEPTH.get() - 1)
+}
+
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Result<DepthGuard, Error> {
+        let depth = DEPTH.get();
+        if depth >= MAX_DEPTH {
+            return Err(Error::new(
+                ErrorType::RecursionError,
+                "Maximum recursion depth exceeded".to_string(),
+                0,
+                0,
+            ));
+        }
+        DEPTH.set(depth + 1);
+        Ok(DepthGuard)
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.set(DEPTH.get() - 1);
+    }
+}
+
+struct FileGuard;
+
+impl FileGuard {
+    fn enter(path: PathBuf) -> FileGuard {
+        FILES.with_borrow_mut(|files| files.push(path));
+        FileGuard
+    }
+}
+
+impl Drop for FileGuard {
+    fn drop(&mut self) {
+        FILES.with_borrow_mut(|files| files.pop());
+    }
+}
+
+struct CallGuard;
+
+impl CallGuard {
+    fn enter() -> Result<CallGuard, Error> {
+        let depth = CALL_DEPTH.get();
+        let limit = MAX_CALL_DEPTH.get();
+        if depth >= limit {
+            return Err(Error::new(
+                ErrorType::RecursionError,
+                format!("Stack overflow: maximum call depth {} exceeded", limit),
+                0,
+                0,
+            ));
+        }
+        CALL_DEPTH.set(depth + 1);
+        Ok(CallGuard)
+    }
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        CALL_DEPTH.set(CALL_DEPTH.get() - 1);
+    }
+}
+
+// `Interpreter::eval_str` applies its own settings to the thread while it runs and puts back the
+// ones from before when it returns, so they do not carry over to anything evaluated afterwards.
+struct SettingsGuard {
+    max_call_depth: usize,
+    trace: bool,
+    step_hook: Option<StepHook>,
+}
+
+impl SettingsGuard {
+    fn enter(interpreter: &Interpreter) -> SettingsGuard {
+        SettingsGuard {
+            max_call_depth: MAX_CALL_DEPTH.replace(interpreter.max_call_depth),
+            trace: TRACE.replace(interpreter.trace),
+            step_hook: STEP_HOOK.replace(interpreter.step_hook.clone()),
         }
-        Node::IntegerLiteral(i) => Ok(RuntimeValue::Integer(i)),
-        Node::StringLiteral(s) => Ok(RuntimeValue::String(s)),
-        Node::FloatLiteral(f) => Ok(RuntimeValue::Float(f)),
-        Node::Identifier(name) => evaluate_identifier(name, env),
+    }
+}
+
+impl Drop for SettingsGuard {
+    fn drop(&mut self) {
+        MAX_CALL_DEPTH.set(self.max_call_depth);
+        TRACE.set(self.trace);
+        STEP_HOOK.set(self.step_hook.take());
+    }
+}
+
+// Errors raised without a position take the position of the innermost node they pass through.
+pub fn evaluate(node: &Node, env: &Rc<RefCell<Environment>>) -> Result<RuntimeValue, Error> {
+    let (line, column) = node.position();
+    let _guard = DepthGuard::enter().map_err(|error| error.with_position(line, column))?;
+    if !TRACE.get() {
+        return evaluate_node(node, env).map_err(|error| error.with_position(line, column));
+    }
+
+    trace_node(node);
+    let result = evaluate_node(node, env).map_err(|error| error.with_position(line, column));
+    trace_result(result.as_ref().map(RuntimeValue::to_nested_string));
+    result
+}
+
+fn evaluate_node(node: &Node, env: &Rc<RefCell<Environment>>) -> Result<RuntimeValue, Error> {
+    match node {
+        Node::Scope { .. }
+        | Node::IfStatement { .. }
+        | Node::WhileLoop { .. }
+        | Node::DoWhileLoop { .. }
+        | Node::ForLoop { .. }
+        | Node::ReturnStatement { .. }
+        | Node::Break { .. }
+        | Node::Continue { .. }
+        | Node::Match { .. }
+        | Node::ThrowStatement { .. }
+        | Node::TryCatch { .. } => flow_value(execute(node, env)?),
+        Node::ImportStatement { path, alias, .. } => evaluate_import(path, alias.clone(), env),
+        Node::MemberAccess { object, member, .. } => evaluate_member_access(object, member, env),
+        Node::IntegerLiteral(i) => Ok(RuntimeValue::Integer(*i)),
+        Node::StringLiteral(s) => Ok(RuntimeValue::String(s.as_str().into())),
+        Node::CharLiteral(c) => Ok(RuntimeValue::Char(*c)),
+        Node::InterpolatedString { parts } => evaluate_interpolated_string(parts, env),
+        Node::FloatLiteral(f) => Ok(RuntimeValue::Float(*f)),
+        Node::BooleanLiteral(b) => Ok(RuntimeValue::Boolean(*b)),
+        Node::NullLiteral => Ok(RuntimeValue::Null),
+        Node::ArrayLiteral(elements) => Ok(RuntimeValue::Array(
+            elements
+                .iter()
+                .map(|element| evaluate(element, env))
+                .collect::<Result<Vec<RuntimeValue>, Error>>()?
+                .into(),
+        )),
+        Node::DictionaryLiteral(entries) => Ok(RuntimeValue::Dictionary(
+            entries
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), evaluate(value, env)?)))
+                .collect::<Result<HashMap<String, RuntimeValue>, Error>>()?
+                .into(),
+        )),
+        Node::Identifier {
+            name, depth, slot, ..
+        } => evaluate_identifier(name, *depth, *slot, env),
         Node::BinaryExpression {
             left,
             operand,
             right,
-        } => evaluate_binary_expression(*left, operand, *right, env),
-        Node::AssignmentExpression { name, value } => {
-            evaluate_assignment_expression(*name, *value, env)
+            ..
+        } => evaluate_binary_expression(left, *operand, right, env),
+        Node::UnaryExpression {
+            operator, operand, ..
+        } => evaluate_unary_expression(*operator, operand, env),
+        Node::UpdateExpression {
+            target,
+            operator,
+            prefix,
+            ..
+        } => evaluate_update_expression(target, operator, *prefix, env),
+        Node::IndexExpression {
+            collection, index, ..
+        } => evaluate_index_expression(collection, index, env),
+        Node::SliceExpression {
+            collection,
+            start,
+            end,
+            ..
+        } => evaluate_slice_expression(collection, start.as_deref(), end.as_deref(), env),
+        Node::TernaryExpression {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => evaluate_ternary_expression(condition, then, otherwise, env),
+        Node::AssignmentExpression { name, value, .. } => {
+            evaluate_assignment_expression(name, value, env)
         }
-        Node::VariableDeclaration { name, value } => {
-            evaluate_variable_declaration(*name, *value, env)
+        Node::VariableDeclaration {
+            name,
+            type_name,
+            value,
+            ..
+        } => evaluate_declaration(name, type_name.as_deref(), value, false, env),
+        Node::ConstantDeclaration {
+            name,
+            type_name,
+            value,
+            ..
+        } => evaluate_declaration(name, type_name.as_deref(), value, true, env),
+        Node::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+            ..
+        } => evaluate_function_declaration(name, parameters, body, env),
+        Node::FunctionExpression {
+            parameters, body, ..
+        } => Ok(make_function(
+            "anonymous".to_string(),
+            parameters,
+            body,
+            env,
+        )),
+        Node::CallExpression {
+            callee,
+            arguments,
+            line,
+            ..
+        } => evaluate_call_expression(callee, arguments, *line, env),
+        Node::NamedArgument { .. } => {
+            unreachable!("named arguments are bound by evaluate_call_expression")
         }
     }
 }
 
-fn evaluate_identifier(
-    name: String,
-    env: &mut HashMap<String, RuntimeValue>,
+// Runs the top level of a program directly in `env` instead of a child scope,
+// so declarations persist between REPL lines.
+pub fn evaluate_program(
+    program: Node,
+    env: &Rc<RefCell<Environment>>,
 ) -> Result<RuntimeValue, Error> {
-    let result = lookup(env, name.clone());
-    match result {
-        Some(value) => Ok(value.clone()),
-        None => Err(Error::new(
-            ErrorType::NameError,
-            format!("'{}' is undefined", name),
-            0,
-            0,
-        )),
+    let mut program = program;
+    resolver::resolve(&mut program);
+    match &program {
+        Node::Scope { body: statements } => flow_value(execute_statements(statements, env)?),
+        node => evaluate(node, env),
     }
 }
 
-fn evaluate_variable_declaration(
-    name: Node,
-    value: Node,
-    env: &mut HashMap<String, RuntimeValue>,
+// Runs a program that was read from `path`, so that the files it imports are found next to it.
+pub fn evaluate_script(
+    program: Node,
+    path: &Path,
+    env: &Rc<RefCell<Environment>>,
 ) -> Result<RuntimeValue, Error> {
-    if let Node::Identifier(name) = name {
-        let value = evaluate(value, env)?;
-        let res = declare(env, name, value.clone());
-        match res {
-            Err(e) => Err(e),
-            Ok(_) => Ok(value),
-        }
-    } else {
-        Err(Error::new(
-            ErrorType::Error,
-            format!("Expected a string value"),
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let _guard = FileGuard::enter(path);
+    evaluate_program(program, env)
+}
+
+// The imported file runs in a scope of its own on top of the global one, and what it declares at
+// its top level is then declared where the import is as well. Paths are relative to the directory
+// of the importing file, or to the working directory when the program did not come from a file.
+// With an alias, the names are gathered into a module declared under the alias instead.
+fn evaluate_import(
+    path: &str,
+    alias: Option<String>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let resolved = FILES.with_borrow(|files| match files.last().and_then(|file| file.parent()) {
+        Some(directory) => directory.join(path),
+        None => PathBuf::from(path),
+    });
+    let source_code = fs::read_to_string(&resolved).map_err(|error| {
+        Error::new(
+            ErrorType::ImportError,
+            format!("Cannot read '{}': {}", path, error),
             0,
             0,
-        ))
+        )
+    })?;
+    let resolved = resolved.canonicalize().unwrap_or(resolved);
+    if FILES.with_borrow(|files| files.contains(&resolved)) {
+        return Err(Error::new(
+            ErrorType::ImportError,
+            format!("Circular import of '{}'", path),
+            0,
+            0,
+        ));
+    }
+
+    let program = parser::parse(&source_code).map_err(|errors| {
+        let error = errors.into_iter().next().unwrap();
+        error.in_file(path, &source_code)
+    })?;
+    let module_env = Environment::new(Some(global_environment(env)));
+    {
+        let _guard = FileGuard::enter(resolved);
+        evaluate_program(program, &module_env)
+            .map_err(|error| error.in_file(path, &source_code))?;
+    }
+
+    let module = module_env.borrow();
+    if let Some(alias) = alias {
+        let module = RuntimeValue::Module {
+            name: alias.clone(),
+            members: module
+                .variables()
+                .into_iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<HashMap<String, RuntimeValue>>()
+                .into(),
+        };
+        declare(env, alias, module)?;
+        return Ok(RuntimeValue::Null);
+    }
+    for (name, value) in module.variables() {
+        declare(env, name.clone(), value.clone())?;
+        if module.constants.contains(name) {
+            env.borrow_mut().constants.insert(name.clone());
+        }
+        if let Some(type_name) = module.types.get(name) {
+            env.borrow_mut()
+                .types
+                .insert(name.clone(), type_name.clone());
+        }
     }
+    Ok(RuntimeValue::Null)
 }
 
-fn evaluate_assignment_expression(
-    name: Node,
-    value: Node,
-    env: &mut HashMap<String, RuntimeValue>,
-) -> Result<RuntimeValue, Error> {
-    if let Node::Identifier(name) = name {
-        let value = evaluate(value, env)?;
-        let res = assign(env, name, value.clone());
-        match res {
-            Err(e) => Err(e),
-            Ok(_) => Ok(value),
+fn global_environment(env: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+    match &env.borrow().parent {
+        Some(parent) => global_environment(parent),
+        None => env.clone(),
+    }
+}
+
+// A session with its own global environment, for running JM from another Rust program. Globals
+// declared by one call to `eval_str` stay defined for the next, like lines typed into the REPL.
+pub struct Interpreter {
+    environment: Rc<RefCell<Environment>>,
+    pub max_call_depth: usize,
+    pub trace: bool,
+    step_hook: Option<StepHook>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            environment: generate_environment(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            trace: false,
+            step_hook: None,
         }
-    } else {
-        Err(Error::new(
-            ErrorType::Error,
-            format!("Expected a string value, found '{:?}'", name),
+    }
+
+    // Returns every syntax error when the source does not parse, or the single error that stopped
+    // it otherwise.
+    pub fn eval_str(&mut self, source_code: &str) -> Result<RuntimeValue, Vec<Error>> {
+        let ast = parser::parse(source_code)?;
+        let _guard = SettingsGuard::enter(self);
+        evaluate_program(ast, &self.environment).map_err(|error| vec![error])
+    }
+
+    // Defines `name`, or replaces it if it is already defined, even as a constant.
+    pub fn set_global(&mut self, name: &str, value: RuntimeValue) {
+        let mut environment = self.environment.borrow_mut();
+        environment.constants.remove(name);
+        environment.set(name, value);
+    }
+
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        function: impl Fn(Vec<RuntimeValue>) -> Result<RuntimeValue, Error> + 'static,
+    ) {
+        define_native(&self.environment, name, NativeFn::new(function));
+    }
+
+    // Registers a hook that is called before each statement runs, replacing any earlier one. It
+    // returns `Step::Stop` to end the program with an error.
+    pub fn on_step(&mut self, hook: impl Fn(&Node, &Environment) -> Step + 'static) {
+        self.step_hook = Some(Rc::new(hook));
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<RuntimeValue> {
+        self.environment.borrow().value(name).cloned()
+    }
+
+    pub fn environment(&self) -> &Rc<RefCell<Environment>> {
+        &self.environment
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+fn execute_statements(statements: &[Node], env: &Rc<RefCell<Environment>>) -> Result<Flow, Error> {
+    let mut result = RuntimeValue::Null;
+    for statement in statements {
+        step(statement, env)?;
+        match execute(statement, env)? {
+            Flow::Normal(value) => result = value,
+            flow => return Ok(flow),
+        }
+    }
+
+    Ok(Flow::Normal(result))
+}
+
+fn execute(node: &Node, env: &Rc<RefCell<Environment>>) -> Result<Flow, Error> {
+    let (line, column) = node.position();
+    let _guard = DepthGuard::enter().map_err(|error| error.with_position(line, column))?;
+    if !TRACE.get() {
+        return execute_node(node, env).map_err(|error| error.with_position(line, column));
+    }
+
+    trace_node(node);
+    let result = execute_node(node, env).map_err(|error| error.with_position(line, column));
+    trace_result(result.as_ref().map(|flow| match flow {
+        Flow::Normal(value) => value.to_nested_string(),
+        Flow::Return(value) => format!("return {}", value.to_nested_string()),
+        Flow::Break { .. } => "break".to_string(),
+        Flow::Continue { .. } => "continue".to_string(),
+        Flow::TailCall { .. } => "tail call".to_string(),
+    }));
+    result
+}
+
+fn execute_node(node: &Node, env: &Rc<RefCell<Environment>>) -> Result<Flow, Error> {
+    match node {
+        Node::Scope { body: statements } => {
+            execute_statements(statements, &Environment::new(Some(env.clone())))
+        }
+        Node::IfStatement {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => evaluate_if_statement(condition, then_branch, else_branch.as_deref(), env),
+        Node::WhileLoop {
+            condition,
+            body,
+            else_branch,
+            ..
+        } => evaluate_while_loop(condition, body, else_branch.as_deref(), env),
+        Node::DoWhileLoop {
+            body, condition, ..
+        } => evaluate_do_while_loop(body, condition, env),
+        Node::ForLoop {
+            variable,
+            iterable,
+            body,
+            else_branch,
+            ..
+        } => evaluate_for_loop(variable, iterable, body, else_branch.as_deref(), env),
+        Node::TryCatch {
+            body,
+            error_name,
+            handler,
+            ..
+        } => evaluate_try_catch(body, error_name, handler, env),
+        Node::ThrowStatement { value, .. } => Err(Error::thrown(evaluate(value, env)?)),
+        Node::Match { subject, arms, .. } => evaluate_match(subject, arms, env),
+        Node::Break { line, column } => Ok(Flow::Break {
+            line: *line,
+            column: *column,
+        }),
+        Node::Continue { line, column } => Ok(Flow::Continue {
+            line: *line,
+            column: *column,
+        }),
+        Node::ReturnStatement {
+            value: Some(value), ..
+        } if matches!(**value, Node::CallExpression { .. }) => evaluate_tail_call(value, env),
+        Node::ReturnStatement { value, .. } => {
+            let value = match value {
+                Some(value) => evaluate(value, env)?,
+                None => RuntimeValue::Null,
+            };
+            Ok(Flow::Return(value))
+        }
+        // `execute` has already entered the node, so expressions skip straight to evaluating it.
+        node => evaluate_node(node, env).map(Flow::Normal),
+    }
+}
+
+// The handler runs in its own scope with `error_name` bound to the thrown value, or to the message
+// of an error raised by the interpreter.
+fn evaluate_try_catch(
+    body: &Node,
+    error_name: &str,
+    handler: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    // A call returned from inside the body is made here, so that its errors are caught as well.
+    let result = execute(body, env).and_then(|flow| match flow {
+        Flow::TailCall { .. } => flow_value(flow).map(Flow::Return),
+        flow => Ok(flow),
+    });
+    match result {
+        Ok(flow) => Ok(flow),
+        Err(error) => {
+            let handler_env = Environment::new(Some(env.clone()));
+            declare(&handler_env, error_name.to_string(), error.value())?;
+            execute(handler, &handler_env)
+        }
+    }
+}
+
+fn evaluate_condition(condition: &Node, env: &Rc<RefCell<Environment>>) -> Result<bool, Error> {
+    expect_boolean(evaluate(condition, env)?, "condition")
+}
+
+// Values are never treated as true or false on their own, so `if count` is an error rather than a
+// test for zero. The message points at `bool`, which makes that test explicit.
+fn expect_boolean(value: RuntimeValue, context: &str) -> Result<bool, Error> {
+    match value {
+        RuntimeValue::Boolean(b) => Ok(b),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Expected a boolean {}, found '{}'; use bool() to convert it",
+                context,
+                value.to_nested_string()
+            ),
             0,
             0,
-        ))
+        )),
     }
 }
 
-fn evaluate_binary_expression(
-    left: Node,
-    operand: char,
-    right: Node,
-    environment: &mut HashMap<String, RuntimeValue>,
+// Only the branch that is picked gets evaluated.
+fn evaluate_ternary_expression(
+    condition: &Node,
+    then: &Node,
+    otherwise: &Node,
+    env: &Rc<RefCell<Environment>>,
 ) -> Result<RuntimeValue, Error> {
-    let left = evaluate(left, environment)?;
-    let right = evaluate(right, environment)?;
-
-    match operand {
-        '+' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Integer(l + r))
-            }
-            (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
-                Ok(RuntimeValue::Float(l as f64 + r))
-            }
-            (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l + r as f64))
+    if evaluate_condition(condition, env)? {
+        evaluate(then, env)
+    } else {
+        evaluate(otherwise, env)
+    }
+}
+
+// Patterns are compared with the same equality as `==`. When no arm matches the result is `null`.
+fn evaluate_match(
+    subject: &Node,
+    arms: &[(Pattern, Node)],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    let subject = evaluate(subject, env)?;
+    for (pattern, body) in arms {
+        if matches_pattern(&subject, pattern, env)? {
+            return execute(body, env);
+        }
+    }
+
+    Ok(Flow::Normal(RuntimeValue::Null))
+}
+
+// Range patterns only match integers.
+fn matches_pattern(
+    subject: &RuntimeValue,
+    pattern: &Pattern,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<bool, Error> {
+    match pattern {
+        Pattern::Wildcard => Ok(true),
+        Pattern::Literal(literal) => Ok(is_equal(subject, &evaluate(literal, env)?)),
+        Pattern::Range(start, end) => {
+            Ok(matches!(subject, RuntimeValue::Integer(i) if (start..=end).contains(&i)))
+        }
+        Pattern::Alternatives(alternatives) => {
+            for alternative in alternatives {
+                if matches_pattern(subject, alternative, env)? {
+                    return Ok(true);
+                }
             }
-            (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(RuntimeValue::Float(l + r)),
-            _ => Err(Error::new(
+            Ok(false)
+        }
+    }
+}
+
+fn evaluate_if_statement(
+    condition: &Node,
+    then_branch: &Node,
+    else_branch: Option<&Node>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    if evaluate_condition(condition, env)? {
+        execute(then_branch, env)
+    } else {
+        match else_branch {
+            Some(branch) => execute(branch, env),
+            None => Ok(Flow::Normal(RuntimeValue::Null)),
+        }
+    }
+}
+
+fn evaluate_while_loop(
+    condition: &Node,
+    body: &Node,
+    else_branch: Option<&Node>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    let mut result = RuntimeValue::Null;
+
+    while evaluate_condition(condition, env)? {
+        match execute(body, env)? {
+            Flow::Normal(value) => result = value,
+            Flow::Break { .. } => return Ok(Flow::Normal(result)),
+            Flow::Continue { .. } => continue,
+            flow => return Ok(flow),
+        }
+    }
+
+    finish_loop(result, else_branch, env)
+}
+
+// A loop that was not broken out of runs its else branch, whose value then becomes the loop's.
+fn finish_loop(
+    result: RuntimeValue,
+    else_branch: Option<&Node>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    match else_branch {
+        Some(else_branch) => execute(else_branch, env),
+        None => Ok(Flow::Normal(result)),
+    }
+}
+
+// The body runs once before the condition is first checked, and `continue` goes on to the check.
+fn evaluate_do_while_loop(
+    body: &Node,
+    condition: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    let mut result = RuntimeValue::Null;
+
+    loop {
+        match execute(body, env)? {
+            Flow::Normal(value) => result = value,
+            Flow::Break { .. } => break,
+            Flow::Continue { .. } => (),
+            flow => return Ok(flow),
+        }
+        if !evaluate_condition(condition, env)? {
+            break;
+        }
+    }
+
+    Ok(Flow::Normal(result))
+}
+
+fn evaluate_for_loop(
+    variable: &Node,
+    iterable: &Node,
+    body: &Node,
+    else_branch: Option<&Node>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    let name = match variable {
+        Node::Identifier { name, .. } => name,
+        _ => {
+            return Err(Error::new(
+                ErrorType::Error,
+                "Expected a loop variable".to_string(),
+                0,
+                0,
+            ))
+        }
+    };
+
+    let elements: Box<dyn Iterator<Item = RuntimeValue>> = match evaluate(iterable, env)? {
+        RuntimeValue::Array(elements) => {
+            Box::new((0..elements.len()).map(move |i| elements[i].clone()))
+        }
+        RuntimeValue::String(s) => Box::new(
+            s.chars()
+                .map(RuntimeValue::Char)
+                .collect::<Vec<RuntimeValue>>()
+                .into_iter(),
+        ),
+        RuntimeValue::Iterable(iterable) => iterable.iter(),
+        value => {
+            return Err(Error::new(
                 ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+                format!("'{:?}' is not iterable", value),
                 0,
                 0,
-            )),
-        },
-        '-' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Integer(l - r))
+            ))
+        }
+    };
+
+    // The loop variable lives in its own scope, so it shadows any outer binding
+    // of the same name for the duration of the loop and disappears afterwards. It is declared
+    // once, and each element replaces its value.
+    let loop_env = Environment::new(Some(env.clone()));
+    declare(&loop_env, name.clone(), RuntimeValue::Null)?;
+    let mut result = RuntimeValue::Null;
+
+    for element in elements {
+        loop_env.borrow_mut().slots[0] = element;
+        match execute(body, &loop_env)? {
+            Flow::Normal(value) => result = value,
+            Flow::Break { .. } => return Ok(Flow::Normal(result)),
+            Flow::Continue { .. } => continue,
+            flow => return Ok(flow),
+        }
+    }
+
+    finish_loop(result, else_branch, env)
+}
+
+fn evaluate_function_declaration(
+    name: &str,
+    parameters: &[Parameter],
+    body: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let function = make_function(name.to_string(), parameters, body, env);
+    declare(env, name.to_string(), function.clone())?;
+    Ok(function)
+}
+
+fn make_function(
+    name: String,
+    parameters: &[Parameter],
+    body: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> RuntimeValue {
+    let body = match body {
+        Node::Scope { body } => body.clone(),
+        node => vec![node.clone()],
+    };
+
+    RuntimeValue::Function {
+        name,
+        parameters: parameters.to_vec().into(),
+        body: body.into(),
+        closure: env.clone(),
+    }
+}
+
+fn evaluate_call_expression(
+    callee: &Node,
+    arguments: &[Node],
+    line: u128,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let callee = evaluate(callee, env)?;
+    let (positional, named) = evaluate_arguments(arguments, env)?;
+    call(callee, positional, named, line)
+}
+
+// Each named argument with the position of its name.
+type NamedArguments = Vec<(String, RuntimeValue, u128, u128)>;
+
+fn evaluate_arguments(
+    arguments: &[Node],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(Vec<RuntimeValue>, NamedArguments), Error> {
+    let mut positional = Vec::new();
+    let mut named = Vec::new();
+    for argument in arguments {
+        match argument {
+            Node::NamedArgument {
+                name,
+                value,
+                line,
+                column,
+            } => named.push((name.clone(), evaluate(value, env)?, *line, *column)),
+            argument => positional.push(evaluate(argument, env)?),
+        }
+    }
+    Ok((positional, named))
+}
+
+// Functions written in JM are called by the function returning, and any other function right away.
+fn evaluate_tail_call(
+    call_expression: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Flow, Error> {
+    let Node::CallExpression {
+        callee,
+        arguments,
+        line,
+        column,
+    } = call_expression
+    else {
+        unreachable!("only calls are returned as tail calls")
+    };
+
+    let callee = evaluate(callee, env)?;
+    let (positional, named) = evaluate_arguments(arguments, env)?;
+    match callee {
+        RuntimeValue::Function { .. } => Ok(Flow::TailCall {
+            callee,
+            positional,
+            named,
+            line: *line,
+            column: *column,
+        }),
+        callee => call(callee, positional, named, *line)
+            .map(Flow::Return)
+            .map_err(|error| error.with_position(*line, *column)),
+    }
+}
+
+// Calls a function with arguments that have already been evaluated, so that built-in functions and
+// host programs can call back into functions written in JM.
+pub fn call_function(
+    function: RuntimeValue,
+    arguments: Vec<RuntimeValue>,
+) -> Result<RuntimeValue, Error> {
+    call(function, arguments, Vec::new(), 0)
+}
+
+fn call(
+    callee: RuntimeValue,
+    positional: Vec<RuntimeValue>,
+    named: Vec<(String, RuntimeValue, u128, u128)>,
+    line: u128,
+) -> Result<RuntimeValue, Error> {
+    match callee {
+        RuntimeValue::Function {
+            name,
+            parameters,
+            body,
+            closure,
+        } => {
+            let mut arguments = bind_arguments(&name, &parameters, positional, named)?;
+            let _guard = CallGuard::enter()?;
+
+            // A call the function returns to itself runs the body again with the new arguments in
+            // place of a nested call, so tail recursion is not limited by the call depth.
+            loop {
+                // The body runs in a new scope on top of the environment the function
+                // was declared in, not the one it is called from. Defaults are evaluated
+                // in that scope as well, so they can use the parameters before them.
+                let function_env = Environment::new(Some(closure.clone()));
+                let declare_parameters = || {
+                    for (parameter, argument) in parameters.iter().zip(arguments) {
+                        let value = match (argument, &parameter.default) {
+                            (Some(argument), _) => argument,
+                            (None, Some(default)) => evaluate(default, &function_env)?,
+                            (None, None) => unreachable!("bind_arguments checks every parameter"),
+                        };
+                        declare(&function_env, parameter.name.clone(), value)?;
+                    }
+                    Ok(())
+                };
+
+                let flow = declare_parameters()
+                    .and_then(|_| execute_statements(&body, &function_env))
+                    .map_err(|error| error.with_frame(&name, line))?;
+                match flow {
+                    Flow::TailCall {
+                        callee:
+                            RuntimeValue::Function {
+                                body: next_body,
+                                closure: next_closure,
+                                ..
+                            },
+                        positional,
+                        named,
+                        line: tail_line,
+                        column,
+                    } if Rc::ptr_eq(&next_body, &body) && Rc::ptr_eq(&next_closure, &closure) => {
+                        arguments = bind_arguments(&name, &parameters, positional, named).map_err(
+                            |error| {
+                                error
+                                    .with_position(tail_line, column)
+                                    .with_frame(&name, line)
+                            },
+                        )?;
+                    }
+                    flow => return flow_value(flow).map_err(|error| error.with_frame(&name, line)),
+                }
             }
-            (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
-                Ok(RuntimeValue::Float(l as f64 - r))
+        }
+        RuntimeValue::NativeFunction { name, function } => {
+            if let Some((_, _, line, column)) = named.first() {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    format!("Function '{}' does not take named arguments", name),
+                    *line,
+                    *column,
+                ));
             }
-            (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l - r as f64))
+            function
+                .call(positional)
+                .map_err(|error| error.with_frame(&name, line))
+        }
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("'{:?}' is not a function", value),
+            0,
+            0,
+        )),
+    }
+}
+
+// Positional arguments fill the first parameters and named ones the rest, in any order. Each
+// parameter must be given at most once, and exactly once unless it has a default. Parameters left
+// to their default come back as `None`. A rest parameter is always the last one, and collects the
+// positional arguments left over into an array.
+fn bind_arguments(
+    function: &str,
+    parameters: &[Parameter],
+    mut positional: Vec<RuntimeValue>,
+    named: Vec<(String, RuntimeValue, u128, u128)>,
+) -> Result<Vec<Option<RuntimeValue>>, Error> {
+    let count = positional.len() + named.len();
+    let (fixed, rest) = match parameters.split_last() {
+        Some((last, fixed)) if last.rest => (fixed, Some(last)),
+        _ => (parameters, None),
+    };
+    let required = fixed
+        .iter()
+        .filter(|parameter| parameter.default.is_none())
+        .count();
+    let wrong_count = || {
+        let expected = if rest.is_some() {
+            format!("at least {}", required)
+        } else if required == fixed.len() {
+            fixed.len().to_string()
+        } else {
+            format!("{} to {}", required, fixed.len())
+        };
+        Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Function '{}' expected {} arguments, found {}",
+                function, expected, count
+            ),
+            0,
+            0,
+        )
+    };
+    if positional.len() > fixed.len() && rest.is_none() {
+        return Err(wrong_count());
+    }
+    let extra = positional.split_off(fixed.len().min(positional.len()));
+
+    let mut slots = positional.into_iter().map(Some).collect::<Vec<_>>();
+    slots.resize(fixed.len(), None);
+    for (name, value, line, column) in named {
+        let message = match fixed.iter().position(|parameter| parameter.name == name) {
+            Some(index) if slots[index].is_none() => {
+                slots[index] = Some(value);
+                continue;
             }
-            (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(RuntimeValue::Float(l - r)),
-            _ => Err(Error::new(
-                ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
-                0,
-                0,
-            )),
-        },
-        '*' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Integer(l * r))
+            Some(_) => format!("Argument '{}' was given more than once", name),
+            None if rest.is_some_and(|rest| rest.name == name) => {
+                format!("Rest parameter '{}' cannot be given by name", name)
+            }
+            None => format!("Function '{}' has no parameter named '{}'", function, name),
+        };
+        return Err(Error::new(ErrorType::TypeError, message, line, column));
+    }
+
+    let missing = slots
+        .iter()
+        .zip(fixed)
+        .any(|(slot, parameter)| slot.is_none() && parameter.default.is_none());
+    if missing {
+        return Err(wrong_count());
+    }
+    if rest.is_some() {
+        slots.push(Some(RuntimeValue::Array(extra.into())));
+    }
+    Ok(slots)
+}
+
+fn evaluate_interpolated_string(
+    parts: &[StringPart],
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            StringPart::Literal(text) => result.push_str(text),
+            StringPart::Expression(node) => result.push_str(&evaluate(node, env)?.to_string()),
+        }
+    }
+
+    Ok(RuntimeValue::String(result.into()))
+}
+
+// A resolved variable is read straight from its slot once its declaration has run, and any other
+// name is searched for.
+fn evaluate_identifier(
+    name: &str,
+    depth: Option<usize>,
+    slot: Option<usize>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    if let (Some(depth), Some(slot)) = (depth, slot) {
+        if let Some(value) = read_slot(&env.borrow(), depth, slot) {
+            return Ok(value);
+        }
+    }
+    lookup(&resolved_scope(env, depth), name).ok_or_else(|| undefined(name))
+}
+
+fn evaluate_declaration(
+    name: &Node,
+    type_name: Option<&str>,
+    value: &Node,
+    constant: bool,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let value = evaluate(value, env)?;
+    let typed = match (name, type_name) {
+        (Node::Identifier { name, .. }, Some(type_name)) => {
+            check_type(name, type_name, &value)?;
+            Some((name.clone(), type_name.to_string()))
+        }
+        _ => None,
+    };
+
+    declare_target(name, value.clone(), constant, env)?;
+    if let Some((name, type_name)) = typed {
+        env.borrow_mut().types.insert(name, type_name);
+    }
+    Ok(value)
+}
+
+fn check_type(name: &str, type_name: &str, value: &RuntimeValue) -> Result<(), Error> {
+    let found = value.type_name();
+    if type_name == "any" || type_name == found {
+        return Ok(());
+    }
+    Err(Error::new(
+        ErrorType::TypeError,
+        format!("Expected {} for '{}', got {}", type_name, name, found),
+        0,
+        0,
+    ))
+}
+
+// `let [a, b] = pair` declares every name in the array from the element in the same position.
+// Arrays of names can be nested to take apart nested arrays.
+fn declare_target(
+    target: &Node,
+    value: RuntimeValue,
+    constant: bool,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Error> {
+    match target {
+        Node::Identifier { name, .. } if constant => declare_constant(env, name.clone(), value),
+        Node::Identifier { name, .. } => declare(env, name.clone(), value),
+        Node::ArrayLiteral(targets) => {
+            for (target, element) in destructure(targets, value)? {
+                declare_target(target, element, constant, env)?;
+            }
+            Ok(())
+        }
+        _ => Err(Error::new(
+            ErrorType::Error,
+            "Expected a variable name".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+// Pairs each target with its element. The array must have exactly one element per target.
+fn destructure(targets: &[Node], value: RuntimeValue) -> Result<Vec<(&Node, RuntimeValue)>, Error> {
+    match value {
+        RuntimeValue::Array(elements) if elements.len() == targets.len() => {
+            Ok(targets.iter().zip(elements.iter().cloned()).collect())
+        }
+        RuntimeValue::Array(elements) => Err(Error::new(
+            ErrorType::Error,
+            format!(
+                "Cannot destructure an array of {} elements into {} targets",
+                elements.len(),
+                targets.len()
+            ),
+            0,
+            0,
+        )),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot destructure '{}', expected an array", value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn evaluate_assignment_expression(
+    name: &Node,
+    value: &Node,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let value = evaluate(value, env)?;
+    assign_to_target(name, value.clone(), env)?;
+    Ok(value)
+}
+
+// Arrays are values, so assigning to an element replaces the element inside the variable that
+// holds the array. The array is changed where it sits rather than read out and written back, so
+// `Rc::make_mut` only copies the elements when another value still shares them. This also handles
+// nested targets such as `grid[1][0] = 5`.
+fn assign_to_target(
+    target: &Node,
+    value: RuntimeValue,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Error> {
+    match target {
+        Node::Identifier {
+            name, depth, slot, ..
+        } => assign(&resolved_scope(env, *depth), name, *slot, value),
+        Node::IndexExpression { .. } | Node::MemberAccess { .. } => {
+            // The keys are collected from the outermost in, which evaluates the indices in the
+            // same order as before, and then reversed to lead from the variable to the element.
+            let mut keys = Vec::new();
+            let mut root = target;
+            loop {
+                match root {
+                    Node::IndexExpression {
+                        collection, index, ..
+                    } => {
+                        keys.push(Key::Index(evaluate(index, env)?));
+                        root = collection;
+                    }
+                    Node::MemberAccess { object, member, .. } => {
+                        keys.push(Key::Member(member.clone()));
+                        root = object;
+                    }
+                    _ => break,
+                }
             }
-            (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
-                Ok(RuntimeValue::Float(l as f64 * r))
+            keys.reverse();
+
+            match root {
+                Node::Identifier {
+                    name, depth, slot, ..
+                } => {
+                    let (scope, slot) = assignable(&resolved_scope(env, *depth), name, *slot)?;
+                    let mut scope = scope.borrow_mut();
+                    replace_element(&mut scope.slots[slot], &keys, value)
+                }
+                _ => Err(Error::new(
+                    ErrorType::Error,
+                    "Only variables and array elements can be assigned to".to_string(),
+                    0,
+                    0,
+                )),
             }
-            (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l * r as f64))
+        }
+        // The whole value is evaluated before any target is assigned, so `[a, b] = [b, a]` swaps.
+        Node::ArrayLiteral(targets) => {
+            for (target, element) in destructure(targets, value)? {
+                assign_to_target(target, element, env)?;
             }
-            (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(RuntimeValue::Float(l * r)),
-            _ => Err(Error::new(
+            Ok(())
+        }
+        _ => Err(Error::new(
+            ErrorType::Error,
+            "Only variables and array elements can be assigned to".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+// One step from a collection to one of its elements in an assignment target.
+enum Key {
+    Index(RuntimeValue),
+    Member(String),
+}
+
+fn replace_element(
+    collection: &mut RuntimeValue,
+    keys: &[Key],
+    value: RuntimeValue,
+) -> Result<(), Error> {
+    let Some((key, rest)) = keys.split_first() else {
+        *collection = value;
+        return Ok(());
+    };
+
+    let element = match (collection, key) {
+        (RuntimeValue::Array(elements), Key::Index(RuntimeValue::Integer(i))) => {
+            let position = resolve_index(*i, elements.len(), false)?;
+            &mut Rc::make_mut(elements)[position]
+        }
+        (RuntimeValue::Dictionary(entries), Key::Index(RuntimeValue::String(key))) => {
+            return replace_entry(Rc::make_mut(entries), key, rest, value);
+        }
+        (RuntimeValue::Dictionary(entries), Key::Member(member)) => {
+            return replace_entry(Rc::make_mut(entries), member, rest, value);
+        }
+        (collection, Key::Index(index)) => {
+            return Err(Error::new(
                 ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+                format!("Cannot index '{:?}' with '{:?}'", collection, index),
                 0,
                 0,
-            )),
-        },
-        '/' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l as f64 / r as f64))
-            }
-            (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
-                Ok(RuntimeValue::Float(l as f64 / r))
-            }
-            (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l / r as f64))
-            }
-            (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(RuntimeValue::Float(l / r)),
-            _ => Err(Error::new(
+            ))
+        }
+        (collection, Key::Member(member)) => {
+            return Err(Error::new(
                 ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+                format!(
+                    "Cannot assign to member '{}' of '{}', expected a dictionary",
+                    member,
+                    collection.to_nested_string()
+                ),
                 0,
                 0,
-            )),
-        },
-        '%' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Integer(l % r))
-            }
-            _ => Err(Error::new(
+            ))
+        }
+    };
+    replace_element(element, rest, value)
+}
+
+// The last key adds the entry if it is missing, while a key on the way to it must already exist.
+fn replace_entry(
+    entries: &mut HashMap<String, RuntimeValue>,
+    key: &str,
+    rest: &[Key],
+    value: RuntimeValue,
+) -> Result<(), Error> {
+    if rest.is_empty() {
+        entries.insert(key.to_string(), value);
+        return Ok(());
+    }
+    match entries.get_mut(key) {
+        Some(entry) => replace_element(entry, rest, value),
+        None => Err(missing_key(key)),
+    }
+}
+
+pub fn is_equal(left: &RuntimeValue, right: &RuntimeValue) -> bool {
+    match (left, right) {
+        (RuntimeValue::Null, RuntimeValue::Null) => true,
+        (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => l == r,
+        (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
+            compare_integer_to_float(*l, *r) == Some(Ordering::Equal)
+        }
+        (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
+            compare_integer_to_float(*r, *l) == Some(Ordering::Equal)
+        }
+        (RuntimeValue::Float(l), RuntimeValue::Float(r)) => l == r,
+        (RuntimeValue::String(l), RuntimeValue::String(r)) => l == r,
+        (RuntimeValue::Char(l), RuntimeValue::Char(r)) => l == r,
+        (RuntimeValue::Boolean(l), RuntimeValue::Boolean(r)) => l == r,
+        (RuntimeValue::Array(l), RuntimeValue::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| is_equal(l, r))
+        }
+        (RuntimeValue::Dictionary(l), RuntimeValue::Dictionary(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .all(|(key, l)| r.get(key).is_some_and(|r| is_equal(l, r)))
+        }
+        _ => false,
+    }
+}
+
+fn evaluate_unary_expression(
+    operator: char,
+    operand: &Node,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let operand = evaluate(operand, environment)?;
+
+    match (operator, operand) {
+        ('-', RuntimeValue::Integer(i)) => i
+            .checked_neg()
+            .map(RuntimeValue::Integer)
+            .ok_or_else(integer_overflow),
+        ('-', RuntimeValue::Float(f)) => Ok(RuntimeValue::Float(-f)),
+        ('!', RuntimeValue::Boolean(b)) => Ok(RuntimeValue::Boolean(!b)),
+        ('~', RuntimeValue::Integer(i)) => Ok(RuntimeValue::Integer(!i)),
+        ('-' | '~', RuntimeValue::Null) => Err(null_in_arithmetic()),
+        (operator, operand) => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot apply '{}' to '{:?}'", operator, operand),
+            0,
+            0,
+        )),
+    }
+}
+
+// `++x` and `--x` produce the new value, `x++` and `x--` the value from before the update.
+fn evaluate_update_expression(
+    target: &Node,
+    operator: &str,
+    prefix: bool,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let old = evaluate(target, env)?;
+    if !matches!(old, RuntimeValue::Integer(_) | RuntimeValue::Float(_)) {
+        return Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot apply '{}' to '{:?}'", operator, old),
+            0,
+            0,
+        ));
+    }
+
+    let operand = if operator == "++" {
+        BinaryOperator::Add
+    } else {
+        BinaryOperator::Sub
+    };
+    let new = apply_binary_operator(operand, &old, &RuntimeValue::Integer(1))?;
+    assign_to_target(target, new.clone(), env)?;
+
+    Ok(if prefix { new } else { old })
+}
+
+// A negative index counts from the end. Slice bounds may also point just past the last element.
+fn resolve_index(index: i128, length: usize, is_slice_bound: bool) -> Result<usize, Error> {
+    let position = if index < 0 {
+        length as i128 + index
+    } else {
+        index
+    };
+    let limit = if is_slice_bound {
+        length as i128 + 1
+    } else {
+        length as i128
+    };
+    if position < 0 || position >= limit {
+        return Err(Error::new(
+            ErrorType::Error,
+            format!("Index {} out of bounds for length {}", index, length),
+            0,
+            0,
+        ));
+    }
+    Ok(position as usize)
+}
+
+fn evaluate_index_expression(
+    collection: &Node,
+    index: &Node,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let collection = evaluate(collection, environment)?;
+    let index = evaluate(index, environment)?;
+
+    match (collection, index) {
+        (RuntimeValue::Array(elements), RuntimeValue::Integer(i)) => {
+            let position = resolve_index(i, elements.len(), false)?;
+            Ok(elements[position].clone())
+        }
+        (RuntimeValue::String(s), RuntimeValue::Integer(i)) => {
+            let position = resolve_index(i, s.chars().count(), false)?;
+            Ok(RuntimeValue::String(
+                s.chars().nth(position).unwrap().to_string().into(),
+            ))
+        }
+        (RuntimeValue::Dictionary(entries), RuntimeValue::String(key)) => {
+            entries.get(&*key).cloned().ok_or_else(|| missing_key(&key))
+        }
+        (collection, index) => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot index '{:?}' with '{:?}'", collection, index),
+            0,
+            0,
+        )),
+    }
+}
+
+fn missing_key(key: &str) -> Error {
+    Error::new(
+        ErrorType::KeyError,
+        format!("Key '{}' not found", key),
+        0,
+        0,
+    )
+}
+
+// `dictionary.key` is the same entry as `dictionary["key"]`.
+fn evaluate_member_access(
+    object: &Node,
+    member: &str,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    match evaluate(object, env)? {
+        RuntimeValue::Dictionary(entries) => entries
+            .get(member)
+            .cloned()
+            .ok_or_else(|| missing_key(member)),
+        RuntimeValue::Module { name, members } => members.get(member).cloned().ok_or_else(|| {
+            Error::new(
+                ErrorType::NameError,
+                format!("Module '{}' has no member '{}'", name, member),
+                0,
+                0,
+            )
+        }),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Cannot read member '{}' of '{}', expected a dictionary or a module",
+                member,
+                value.to_nested_string()
+            ),
+            0,
+            0,
+        )),
+    }
+}
+
+fn evaluate_slice_bound(
+    bound: Option<&Node>,
+    default: usize,
+    length: usize,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<usize, Error> {
+    match bound {
+        None => Ok(default),
+        Some(bound) => match evaluate(bound, environment)? {
+            RuntimeValue::Integer(i) => resolve_index(i, length, true),
+            value => Err(Error::new(
                 ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+                format!("Slice bounds must be integers, found '{:?}'", value),
                 0,
                 0,
             )),
         },
-        '^' => match (left.clone(), right.clone()) {
-            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Integer(l.pow(r.try_into().unwrap())))
-            }
-            (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => {
-                Ok(RuntimeValue::Float((l as f64).powf(r)))
-            }
-            (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
-                Ok(RuntimeValue::Float(l.powf(r as f64)))
-            }
-            (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(RuntimeValue::Float(l.powf(r))),
-            _ => Err(Error::new(
+    }
+}
+
+// Strings are sliced by unicode scalar values, the same unit `len` counts in.
+fn evaluate_slice_expression(
+    collection: &Node,
+    start: Option<&Node>,
+    end: Option<&Node>,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let collection = evaluate(collection, environment)?;
+    let length = match &collection {
+        RuntimeValue::Array(elements) => elements.len(),
+        RuntimeValue::String(s) => s.chars().count(),
+        collection => {
+            return Err(Error::new(
                 ErrorType::TypeError,
-                format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+                format!("Cannot slice '{:?}'", collection),
                 0,
                 0,
-            )),
+            ))
+        }
+    };
+    let start = evaluate_slice_bound(start, 0, length, environment)?;
+    let end = evaluate_slice_bound(end, length, length, environment)?;
+    if start > end {
+        return Err(Error::new(
+            ErrorType::Error,
+            format!("Slice start {} is after its end {}", start, end),
+            0,
+            0,
+        ));
+    }
+
+    match collection {
+        RuntimeValue::Array(elements) => {
+            Ok(RuntimeValue::Array(elements[start..end].to_vec().into()))
+        }
+        RuntimeValue::String(s) => Ok(RuntimeValue::String(
+            s.chars()
+                .skip(start)
+                .take(end - start)
+                .collect::<String>()
+                .into(),
+        )),
+        _ => unreachable!("only arrays and strings have a length"),
+    }
+}
+
+fn evaluate_logical_expression(
+    left: &Node,
+    operand: BinaryOperator,
+    right: &Node,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    let context = format!("for '{}'", operand);
+    let left = expect_boolean(evaluate(left, environment)?, &context)?;
+
+    match (operand, left) {
+        (BinaryOperator::And, false) => return Ok(RuntimeValue::Boolean(false)),
+        (BinaryOperator::Or, true) => return Ok(RuntimeValue::Boolean(true)),
+        _ => (),
+    }
+
+    expect_boolean(evaluate(right, environment)?, &context).map(RuntimeValue::Boolean)
+}
+
+// Integers and floats are compared by their exact values. The ordering is `None` when either side
+// is NaN. Characters compare by code point, and only with other characters.
+pub fn compare_numbers(
+    left: &RuntimeValue,
+    right: &RuntimeValue,
+) -> Result<Option<Ordering>, Error> {
+    match (left, right) {
+        (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => Ok(l.partial_cmp(r)),
+        (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => Ok(compare_integer_to_float(*l, *r)),
+        (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => {
+            Ok(compare_integer_to_float(*r, *l).map(Ordering::reverse))
+        }
+        (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Ok(l.partial_cmp(r)),
+        (RuntimeValue::Char(l), RuntimeValue::Char(r)) => Ok(l.partial_cmp(r)),
+        _ => Err(incompatible_types(left, right)),
+    }
+}
+
+// Converting the integer to a float would round integers above 2^53, so `2^53 + 1` would equal
+// `2.0^53`. The float is split into its integer part, which fits in an i128 whenever the two can be
+// equal, and its fraction instead.
+fn compare_integer_to_float(integer: i128, float: f64) -> Option<Ordering> {
+    const LIMIT: f64 = 170141183460469231731687303715884105728.0; // 2^127
+    if float.is_nan() {
+        None
+    } else if float >= LIMIT {
+        Some(Ordering::Less)
+    } else if float < -LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        let whole = float.trunc();
+        match integer.cmp(&(whole as i128)) {
+            Ordering::Equal => 0.0.partial_cmp(&(float - whole)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+// Null usually comes from a variable that was never given a proper value, which deserves a clearer
+// message than a dump of both operands.
+fn incompatible_operands(left: &RuntimeValue, right: &RuntimeValue) -> Error {
+    match (left, right) {
+        (RuntimeValue::Null, _) | (_, RuntimeValue::Null) => null_in_arithmetic(),
+        _ => incompatible_types(left, right),
+    }
+}
+
+fn null_in_arithmetic() -> Error {
+    Error::new(
+        ErrorType::TypeError,
+        "Cannot use null in arithmetic expression".to_string(),
+        0,
+        0,
+    )
+}
+
+fn incompatible_types(left: &RuntimeValue, right: &RuntimeValue) -> Error {
+    Error::new(
+        ErrorType::TypeError,
+        format!("Incompatible types: '{:?}' and '{:?}'", left, right),
+        0,
+        0,
+    )
+}
+
+pub fn integer_overflow() -> Error {
+    Error::new(
+        ErrorType::OverflowError,
+        "Integer overflow".to_string(),
+        0,
+        0,
+    )
+}
+
+fn is_zero(value: &RuntimeValue) -> bool {
+    match value {
+        RuntimeValue::Integer(i) => *i == 0,
+        RuntimeValue::Float(f) => *f == 0.0,
+        _ => false,
+    }
+}
+
+// Rounds towards negative infinity, so `-7 ~/ 2` is `-4` where truncating division would give `-3`.
+fn floor_divide(left: i128, right: i128) -> Option<i128> {
+    let quotient = left.checked_div(right)?;
+    if left % right != 0 && (left < 0) != (right < 0) {
+        Some(quotient - 1)
+    } else {
+        Some(quotient)
+    }
+}
+
+// A negative exponent gives a float, as `2 ^ -1` is `0.5`. `0 ^ 0` is `1`. Only 0, 1 and -1 can be
+// raised to an exponent too large for `u32` without overflowing, so those are worked out directly.
+fn integer_power(base: i128, exponent: i128) -> Result<RuntimeValue, Error> {
+    if exponent < 0 {
+        if base == 0 {
+            return Err(Error::new(
+                ErrorType::ZeroDivisionError,
+                "Zero cannot be raised to a negative power".to_string(),
+                0,
+                0,
+            ));
+        }
+        return Ok(RuntimeValue::Float((base as f64).powf(exponent as f64)));
+    }
+
+    match (base, u32::try_from(exponent)) {
+        (_, Ok(exponent)) => base
+            .checked_pow(exponent)
+            .map(RuntimeValue::Integer)
+            .ok_or_else(integer_overflow),
+        (0 | 1, Err(_)) => Ok(RuntimeValue::Integer(base)),
+        (-1, Err(_)) => Ok(RuntimeValue::Integer(if exponent % 2 == 0 {
+            1
+        } else {
+            -1
+        })),
+        (_, Err(_)) => Err(integer_overflow()),
+    }
+}
+
+// The operands of an arithmetic operator brought to a common type. Two integers stay integers, and
+// a float on either side turns both into floats.
+enum Numbers {
+    Integers(i128, i128),
+    Floats(f64, f64),
+}
+
+fn promote(left: &RuntimeValue, right: &RuntimeValue) -> Option<Numbers> {
+    match (left, right) {
+        (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => Some(Numbers::Integers(*l, *r)),
+        (RuntimeValue::Integer(l), RuntimeValue::Float(r)) => Some(Numbers::Floats(*l as f64, *r)),
+        (RuntimeValue::Float(l), RuntimeValue::Integer(r)) => Some(Numbers::Floats(*l, *r as f64)),
+        (RuntimeValue::Float(l), RuntimeValue::Float(r)) => Some(Numbers::Floats(*l, *r)),
+        _ => None,
+    }
+}
+
+// Every arithmetic operator is defined here once for integers and once for floats. Division by zero
+// has already been ruled out by the caller.
+fn evaluate_arithmetic(
+    operand: BinaryOperator,
+    left: &RuntimeValue,
+    right: &RuntimeValue,
+) -> Result<RuntimeValue, Error> {
+    match promote(left, right) {
+        Some(Numbers::Integers(l, r)) => match operand {
+            BinaryOperator::Add => l.checked_add(r),
+            BinaryOperator::Sub => l.checked_sub(r),
+            BinaryOperator::Mul => l.checked_mul(r),
+            BinaryOperator::Div => return Ok(RuntimeValue::Float(l as f64 / r as f64)),
+            BinaryOperator::FloorDiv => floor_divide(l, r),
+            // The remainder always fits, but `checked_rem` reports `MIN_INT % -1` as an overflow
+            // because the quotient does not.
+            BinaryOperator::Mod => Some(l.wrapping_rem(r)),
+            BinaryOperator::Pow => return integer_power(l, r),
+            _ => unreachable!("'{}' is not an arithmetic operator", operand),
+        }
+        .map(RuntimeValue::Integer)
+        .ok_or_else(integer_overflow),
+        Some(Numbers::Floats(l, r)) => Ok(RuntimeValue::Float(match operand {
+            BinaryOperator::Add => l + r,
+            BinaryOperator::Sub => l - r,
+            BinaryOperator::Mul => l * r,
+            BinaryOperator::Div => l / r,
+            BinaryOperator::FloorDiv => (l / r).floor(),
+            BinaryOperator::Mod => l % r,
+            BinaryOperator::Pow => l.powf(r),
+            _ => unreachable!("'{}' is not an arithmetic operator", operand),
+        })),
+        None => Err(incompatible_operands(left, right)),
+    }
+}
+
+fn evaluate_binary_expression(
+    left: &Node,
+    operand: BinaryOperator,
+    right: &Node,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<RuntimeValue, Error> {
+    if let BinaryOperator::And | BinaryOperator::Or = operand {
+        return evaluate_logical_expression(left, operand, right, environment);
+    }
+
+    let left = evaluate(left, environment)?;
+    let right = evaluate(right, environment)?;
+    apply_binary_operator(operand, &left, &right)
+}
+
+// Applies every binary operator except `&&` and `||`, which need their right side unevaluated.
+pub(crate) fn apply_binary_operator(
+    operand: BinaryOperator,
+    left: &RuntimeValue,
+    right: &RuntimeValue,
+) -> Result<RuntimeValue, Error> {
+    match operand {
+        BinaryOperator::Add => match (left, right) {
+            (RuntimeValue::String(l), RuntimeValue::String(r)) => {
+                Ok(RuntimeValue::String(format!("{}{}", l, r).into()))
+            }
+            (
+                RuntimeValue::String(l),
+                r @ (RuntimeValue::Integer(_) | RuntimeValue::Float(_) | RuntimeValue::Char(_)),
+            ) => Ok(RuntimeValue::String(format!("{}{}", l, r).into())),
+            (RuntimeValue::Char(l), RuntimeValue::String(r)) => {
+                Ok(RuntimeValue::String(format!("{}{}", l, r).into()))
+            }
+            (RuntimeValue::Array(l), RuntimeValue::Array(r)) => Ok(RuntimeValue::Array(
+                l.iter()
+                    .chain(r.iter())
+                    .cloned()
+                    .collect::<Vec<RuntimeValue>>()
+                    .into(),
+            )),
+            _ => evaluate_arithmetic(operand, left, right),
+        },
+        BinaryOperator::Div | BinaryOperator::FloorDiv | BinaryOperator::Mod if is_zero(right) => {
+            Err(Error::new(
+                ErrorType::ZeroDivisionError,
+                "Division by zero".to_string(),
+                0,
+                0,
+            ))
+        }
+        BinaryOperator::Sub
+        | BinaryOperator::Mul
+        | BinaryOperator::Div
+        | BinaryOperator::FloorDiv
+        | BinaryOperator::Mod
+        | BinaryOperator::Pow => evaluate_arithmetic(operand, left, right),
+        BinaryOperator::BitAnd => match (left, right) {
+            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
+                Ok(RuntimeValue::Integer(l & r))
+            }
+            _ => Err(incompatible_operands(left, right)),
+        },
+        BinaryOperator::BitOr => match (left, right) {
+            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
+                Ok(RuntimeValue::Integer(l | r))
+            }
+            _ => Err(incompatible_operands(left, right)),
+        },
+        // Bits shifted out are dropped, and `>>` keeps the sign, so `-8 >> 1` is `-4`.
+        BinaryOperator::Shl | BinaryOperator::Shr => match (left, right) {
+            (RuntimeValue::Integer(l), RuntimeValue::Integer(r)) => {
+                let amount = u32::try_from(*r)
+                    .ok()
+                    .filter(|amount| *amount < i128::BITS)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorType::Error,
+                            format!("Shift amount must be between 0 and 127, found {}", r),
+                            0,
+                            0,
+                        )
+                    })?;
+                match operand {
+                    BinaryOperator::Shl => Ok(RuntimeValue::Integer(l << amount)),
+                    _ => Ok(RuntimeValue::Integer(l >> amount)),
+                }
+            }
+            _ => Err(incompatible_operands(left, right)),
         },
-        _ => Ok(RuntimeValue::Null),
+        BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => {
+            let ordering = compare_numbers(left, right)?;
+
+            let result = match operand {
+                BinaryOperator::Lt => ordering == Some(Ordering::Less),
+                BinaryOperator::Gt => ordering == Some(Ordering::Greater),
+                BinaryOperator::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            };
+            Ok(RuntimeValue::Boolean(result))
+        }
+        BinaryOperator::And | BinaryOperator::Or => {
+            unreachable!("logical operators are evaluated by evaluate_logical_expression")
+        }
+        BinaryOperator::Eq => Ok(RuntimeValue::Boolean(is_equal(left, right))),
+        BinaryOperator::Neq => Ok(RuntimeValue::Boolean(!is_equal(left, right))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Runs a program on a thread with a stack as large as the one `jm` runs on. Values and errors
+    // are not `Send`, so the value of the last statement or the error comes back as text.
+    pub(crate) fn run(source_code: &str) -> Result<String, String> {
+        let source_code = source_code.to_string();
+        std::thread::Builder::new()
+            .stack_size(512 * 1024 * 1024)
+            .spawn(move || {
+                let program =
+                    parser::parse(&source_code).map_err(|errors| errors[0].to_string())?;
+                evaluate_program(program, &generate_environment())
+                    .map(|value| value.to_string())
+                    .map_err(|error| error.to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    pub(crate) fn assert_error(source_code: &str, expected: &str) {
+        let error = run(source_code).unwrap_err();
+        assert!(error.starts_with(expected), "{}", error);
+    }
+
+    #[test]
+    fn typed_declarations_accept_values_of_their_type() {
+        assert_eq!(run("let x: integer = 5; x"), Ok("5".to_string()));
+        assert_eq!(run("let x: any = 5; x = \"a\"; x"), Ok("a".to_string()));
+        assert_eq!(
+            run("let r: range = range(0, 3); len(r)"),
+            Ok("3".to_string())
+        );
+    }
+
+    #[test]
+    fn typed_declarations_reject_values_of_another_type() {
+        assert_error(
+            "let x: integer = \"a\"",
+            "TypeError: Expected integer for 'x', got string",
+        );
+        assert_error(
+            "let x: array = range(0, 3)",
+            "TypeError: Expected array for 'x', got range",
+        );
+    }
+
+    #[test]
+    fn typed_variables_reject_later_values_of_another_type() {
+        assert_error(
+            "let x: integer = 1\nx = \"a\"",
+            "TypeError: Expected integer for 'x', got string",
+        );
+        assert_error(
+            "let x: float = 1.0\nfunc f() { x = true }\nf()",
+            "TypeError: Expected float for 'x', got boolean",
+        );
+    }
+
+    #[test]
+    fn resolved_variables_read_their_own_slots() {
+        assert_eq!(
+            run("func f(a, b) { let c = a * 10\n if true { let d = b\n return c + d } }\nf(4, 2)"),
+            Ok("42".to_string())
+        );
+        assert_eq!(
+            run("func f() { let x = 1\n func g() { return x + y }\n let y = 2\n return g() }\nf()"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("func f() { let [a, b] = [1, 2]\n let c = 3\n return [a, b, c] }\nf()"),
+            Ok("[1, 2, 3]".to_string())
+        );
+    }
+
+    #[test]
+    fn variables_read_before_their_declaration_are_not_found() {
+        assert_error(
+            "func f() { func g() { return y }\n let z = g()\n let y = 1 }\nf()",
+            "NameError",
+        );
+    }
+
+    #[test]
+    fn assignments_write_the_resolved_slot() {
+        assert_eq!(
+            run("func f() { let a = 1\n let b = 2\n for i in range(0, 3) { b = b + i }\n return [a, b] }\nf()"),
+            Ok("[1, 5]".to_string())
+        );
+        assert_eq!(
+            run("func f() { let a = [1, [2]]\n a[1][0] = 3\n return a }\nf()"),
+            Ok("[1, [3]]".to_string())
+        );
+    }
+
+    #[test]
+    fn deep_expression_is_a_recursion_error() {
+        assert_error(
+            &vec!["1"; 3 * MAX_DEPTH].join(" + "),
+            "RecursionError: Maximum recursion depth exceeded",
+        );
+    }
+
+    #[test]
+    fn long_expression_within_the_limit_runs() {
+        assert_eq!(run(&vec!["1"; 1001].join(" + ")), Ok("1001".to_string()));
+    }
+
+    #[test]
+    fn negation_applies_to_numbers() {
+        assert_eq!(run("-3 + 4"), Ok("1".to_string()));
+        assert_eq!(run("-(2 * 3)"), Ok("-6".to_string()));
+        assert_eq!(run("- -5"), Ok("5".to_string()));
+        assert_eq!(run("-2.5"), Ok("-2.5".to_string()));
+        assert_error("-\"a\"", "TypeError");
+    }
+
+    #[test]
+    fn not_negates_booleans() {
+        assert_eq!(run("!!true"), Ok("true".to_string()));
+        assert_eq!(run("!(3 < 5)"), Ok("false".to_string()));
+        assert_eq!(
+            run("let a = 1\nlet b = 2\n!(a == b)"),
+            Ok("true".to_string())
+        );
+        assert_error("!1", "TypeError");
+    }
+
+    #[test]
+    fn plus_concatenates_strings() {
+        assert_eq!(run("\"\" + \"\""), Ok("".to_string()));
+        assert_eq!(run("\"a\" + \"\""), Ok("a".to_string()));
+        assert_eq!(run("\"a\" + \"b\" + \"c\""), Ok("abc".to_string()));
+        assert_eq!(run("1 + 2"), Ok("3".to_string()));
+        assert_eq!(run("1 + 2.5"), Ok("3.5".to_string()));
+        assert_eq!(run("\"n=\" + 5"), Ok("n=5".to_string()));
+        assert_eq!(run("\"x=\" + 1.5"), Ok("x=1.5".to_string()));
+    }
+
+    #[test]
+    fn operators_follow_their_precedence() {
+        assert_eq!(run("2 + 3 * 4"), Ok("14".to_string()));
+        assert_eq!(run("(2 + 3) * 4"), Ok("20".to_string()));
+        assert_eq!(run("2 ^ 3 ^ 2"), Ok("512".to_string()));
+        assert_eq!(run("10 - 4 - 3"), Ok("3".to_string()));
+        assert_eq!(run("24 / 4 / 2"), Ok("3.0".to_string()));
+        assert_eq!(run("2 * 3 ^ 2"), Ok("18".to_string()));
+        assert_eq!(run("-2 ^ 2"), Ok("4".to_string()));
+        assert_eq!(run("1 + 10 % 4 * 3"), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        for source_code in [
+            "5 / 0",
+            "5.0 / 0.0",
+            "5 / 0.0",
+            "5 % 0",
+            "5.5 % 0.0",
+            "5 ~/ 0",
+        ] {
+            assert_error(source_code, "ZeroDivisionError: Division by zero");
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic_past_the_limits_is_an_overflow() {
+        for source_code in [
+            "MAX_INT + 1",
+            "MIN_INT - 1",
+            "MAX_INT * 2",
+            "MIN_INT * -1",
+            "2 ^ 127",
+            "-MIN_INT",
+            "MIN_INT ~/ -1",
+            "let x = MAX_INT\nx++",
+            "let x = MIN_INT\nx -= 1",
+        ] {
+            assert_error(source_code, "OverflowError: Integer overflow");
+        }
+        assert_eq!(run("MAX_INT + 0"), run("2 ^ 126 - 1 + 2 ^ 126"));
+        assert_eq!(run("MIN_INT % -1"), Ok("0".to_string()));
+    }
+
+    #[test]
+    fn if_statements_run_the_branch_that_matches() {
+        let size = |x: i32| {
+            run(&format!(
+                "let x = {}\nif x > 10 {{ \"big\" }} else if x > 3 {{ \"medium\" }} else {{ \"small\" }}",
+                x
+            ))
+        };
+        assert_eq!(size(20), Ok("big".to_string()));
+        assert_eq!(size(5), Ok("medium".to_string()));
+        assert_eq!(size(1), Ok("small".to_string()));
+        assert_eq!(run("if false { 1 }"), Ok("null".to_string()));
+        assert_eq!(run("if true { 1 } else { 2 }"), Ok("1".to_string()));
+        assert_error("if 1 { 2 }", "TypeError");
+    }
+
+    #[test]
+    fn while_loops_run_until_the_condition_is_false() {
+        assert_eq!(
+            run("let count = 0\nwhile count < 10 { count = count + 1 }\ncount"),
+            Ok("10".to_string())
+        );
+        assert_eq!(run("while false { 1 }"), Ok("null".to_string()));
+        assert_error("while 1 { 2 }", "TypeError");
+    }
+
+    #[test]
+    fn for_loops_visit_every_element() {
+        assert_eq!(
+            run("let total = 0\nfor n in [1, 2, 3, 4] { total = total + n }\ntotal"),
+            Ok("10".to_string())
+        );
+        assert_error("for n in [1, 2] { n }\nn", "NameError");
+        assert_eq!(
+            run("let n = 5\nfor n in [1, 2] { n }\nn"),
+            Ok("5".to_string())
+        );
+        assert_error("for n in 5 { n }", "TypeError");
+    }
+
+    #[test]
+    fn return_leaves_loops_early() {
+        assert_eq!(
+            run("func find(values, wanted) {\n    for v in values {\n        if v == wanted { return v * 10 }\n    }\n    return -1\n}\nfind([1, 2, 3], 2)"),
+            Ok("20".to_string())
+        );
+        assert_eq!(
+            run("func f() {\n    let i = 0\n    while true {\n        i = i + 1\n        if i == 5 { return i }\n    }\n}\nf()"),
+            Ok("5".to_string())
+        );
+    }
+
+    #[test]
+    fn return_at_the_top_level_ends_the_program() {
+        assert_eq!(
+            run("let x = 1\nif true { return x }\nx = 2"),
+            Ok("1".to_string())
+        );
+        assert_eq!(run("return\nthrow \"unreachable\""), Ok("null".to_string()));
+    }
+
+    #[test]
+    fn closures_keep_the_environment_they_were_made_in() {
+        assert_eq!(
+            run("func make_counter() {\n    let count = 0\n    func next() {\n        count = count + 1\n        return count\n    }\n    return next\n}\nlet counter = make_counter()\n[counter(), counter(), counter()]"),
+            Ok("[1, 2, 3]".to_string())
+        );
+        assert_eq!(
+            run("func make_counter() {\n    let count = 0\n    return func() { count += 1; return count }\n}\nlet a = make_counter()\nlet b = make_counter()\na()\na()\n[a(), b()]"),
+            Ok("[3, 1]".to_string())
+        );
+    }
+
+    #[test]
+    fn inner_declarations_shadow_outer_ones_until_the_block_ends() {
+        assert_eq!(
+            run("let x = 1\nlet inner = null\nif true {\n    let x = 2\n    inner = x\n}\n[inner, x]"),
+            Ok("[2, 1]".to_string())
+        );
+        assert_error("let x = 1\nlet x = 2", "NameError");
+    }
+
+    #[test]
+    fn boolean_literals_are_booleans() {
+        assert_eq!(run("true"), Ok("true".to_string()));
+        assert_eq!(run("false"), Ok("false".to_string()));
+        assert_eq!(run("true == false"), Ok("false".to_string()));
+        assert!(run("let true = 1").unwrap_err().starts_with("SyntaxError"));
+    }
+
+    #[test]
+    fn separated_numbers_keep_their_value() {
+        assert_eq!(run("1_000 == 1000"), Ok("true".to_string()));
+        assert_eq!(run("1_000_000 + 0.000_5"), Ok("1000000.0005".to_string()));
+    }
+
+    #[test]
+    fn escaped_strings_hold_control_characters() {
+        assert_eq!(run(r#""a\"b\nc""#), Ok("a\"b\nc".to_string()));
+        assert_eq!(run(r#"len("\n\t")"#), Ok("2".to_string()));
+    }
+
+    #[test]
+    fn values_display_as_they_are_written() {
+        assert_eq!(RuntimeValue::Null.to_string(), "null");
+        assert_eq!(RuntimeValue::Integer(-42).to_string(), "-42");
+        assert_eq!(RuntimeValue::Float(2.0).to_string(), "2.0");
+        assert_eq!(RuntimeValue::Float(0.5).to_string(), "0.5");
+        assert_eq!(RuntimeValue::String("a b".into()).to_string(), "a b");
+        assert_eq!(RuntimeValue::Char('c').to_string(), "c");
+        assert_eq!(RuntimeValue::Boolean(true).to_string(), "true");
+        assert_eq!(
+            RuntimeValue::Array(
+                vec![
+                    RuntimeValue::Integer(1),
+                    RuntimeValue::String("a, b".into()),
+                    RuntimeValue::Char('c'),
+                    RuntimeValue::Array(Vec::new().into()),
+                ]
+                .into()
+            )
+            .to_string(),
+            "[1, \"a, b\", 'c', []]"
+        );
+        assert_eq!(
+            RuntimeValue::Dictionary(
+                HashMap::from([
+                    ("b".to_string(), RuntimeValue::String("x".into())),
+                    ("a".to_string(), RuntimeValue::Integer(1)),
+                ])
+                .into()
+            )
+            .to_string(),
+            "{\"a\": 1, \"b\": \"x\"}"
+        );
+        assert_eq!(run("range(0, 3)"), Ok("<iterable>".to_string()));
+        assert_eq!(run("func f() {}\nf"), Ok("<function f>".to_string()));
+        assert_eq!(run("len"), Ok("<native function len>".to_string()));
+    }
+
+    #[test]
+    fn array_literals_evaluate_their_elements() {
+        assert_eq!(run("[]"), Ok("[]".to_string()));
+        assert_eq!(run("[1, 2, 3]"), Ok("[1, 2, 3]".to_string()));
+        assert_eq!(run("[1, 2,]"), Ok("[1, 2]".to_string()));
+        assert_eq!(
+            run("[[1, 2], [3, 4], []]"),
+            Ok("[[1, 2], [3, 4], []]".to_string())
+        );
+        assert_eq!(
+            run("[1, 2.5, \"a\", 'b', true, null, [1 + 1]]"),
+            Ok("[1, 2.5, \"a\", 'b', true, null, [2]]".to_string())
+        );
+    }
+
+    #[test]
+    fn assigning_to_an_index_replaces_the_element() {
+        assert_eq!(
+            run("let a = [1, 2, 3]\na[1] = 10\na"),
+            Ok("[1, 10, 3]".to_string())
+        );
+        assert_eq!(
+            run("let a = [1, 2, 3]\na[-1] = 10\na"),
+            Ok("[1, 2, 10]".to_string())
+        );
+        assert_eq!(
+            run("let grid = [[1, 2], [3, 4]]\ngrid[1][0] = 5\ngrid"),
+            Ok("[[1, 2], [5, 4]]".to_string())
+        );
+        assert_eq!(
+            run("let a = [1, 2]\nlet b = a\nb[0] = 9\n[a, b]"),
+            Ok("[[1, 2], [9, 2]]".to_string())
+        );
+    }
+
+    #[test]
+    fn assigning_outside_an_array_is_an_error() {
+        assert_error(
+            "let a = [1, 2, 3]\na[3] = 10",
+            "Error: Index 3 out of bounds for length 3",
+        );
+        assert_error(
+            "let a = [1, 2, 3]\na[-4] = 10",
+            "Error: Index -4 out of bounds for length 3",
+        );
+    }
+
+    #[test]
+    fn dictionaries_map_keys_to_values() {
+        assert_eq!(run("{:}"), Ok("{}".to_string()));
+        assert_eq!(
+            run("let d = {\"b\": [1], \"a\": 1,}\nd"),
+            Ok("{\"a\": 1, \"b\": [1]}".to_string())
+        );
+        assert_eq!(run("let d = {\"a\": 1}\nd[\"a\"]"), Ok("1".to_string()));
+        assert_eq!(
+            run("let d = {\"a\": 1}\nd[\"b\"] = 2\nd[\"a\"] = 3\nd"),
+            Ok("{\"a\": 3, \"b\": 2}".to_string())
+        );
+        assert_eq!(run("{}"), Ok("null".to_string()));
+    }
+
+    #[test]
+    fn reading_a_missing_key_is_an_error() {
+        assert_error(
+            "let d = {\"a\": 1}\nd[\"b\"]",
+            "KeyError: Key 'b' not found",
+        );
+    }
+
+    #[test]
+    fn strings_are_indexed_and_sliced_by_character() {
+        assert_eq!(run("\"hello\"[0]"), Ok("h".to_string()));
+        assert_eq!(run("type(\"hello\"[0])"), Ok("string".to_string()));
+        assert_eq!(run("\"hello\"[1:3]"), Ok("el".to_string()));
+        assert_eq!(run("\"hello\"[:3]"), Ok("hel".to_string()));
+        assert_eq!(run("\"hello\"[2:]"), Ok("llo".to_string()));
+        assert_eq!(run("\"hello\"[-1]"), Ok("o".to_string()));
+        assert_eq!(run("\"héllo wörld\"[1]"), Ok("é".to_string()));
+        assert_eq!(run("\"héllo wörld\"[7:9]"), Ok("ör".to_string()));
+        assert_eq!(run("\"日本語\"[2]"), Ok("語".to_string()));
+        assert_eq!(run("[1, 2, 3, 4][1:3]"), Ok("[2, 3]".to_string()));
+    }
+
+    #[test]
+    fn indexing_outside_a_string_is_an_error() {
+        assert_error("\"héllo\"[5]", "Error: Index 5 out of bounds for length 5");
+        assert_error(
+            "\"héllo\"[1:7]",
+            "Error: Index 7 out of bounds for length 5",
+        );
+    }
+
+    #[test]
+    fn errors_in_nested_calls_list_every_call() {
+        assert_eq!(
+            run("func inner() { return 1 / 0 }\nfunc middle() { return inner() }\nfunc outer() { return middle() }\nouter()"),
+            Err("ZeroDivisionError: Division by zero in line 1 column 25\nin outer (line 4) -> in middle (line 3) -> in inner (line 2)".to_string())
+        );
+    }
+
+    #[test]
+    fn repeated_calls_are_folded_in_the_trace() {
+        assert_eq!(
+            run("func f(n) {\n    if n == 0 { return 1 / 0 }\n    return 1 + f(n - 1)\n}\nf(3)"),
+            Err("ZeroDivisionError: Division by zero in line 2 column 26\nin f (line 5) -> in f (line 3) x3".to_string())
+        );
+        assert_eq!(
+            run("func a(n) { return n == 0 ? 1 / 0 : 1 + b(n) }\nfunc b(n) { return 1 + a(n - 1) }\na(2)"),
+            Err("ZeroDivisionError: Division by zero in line 1 column 31\nin a (line 3) -> (in b (line 1) -> in a (line 2)) repeated 2 times".to_string())
+        );
+    }
+
+    #[test]
+    fn catch_receives_the_error_message() {
+        assert_eq!(
+            run("try { 1 / 0 } catch (e) { \"caught: \" + e }"),
+            Ok("caught: Division by zero".to_string())
+        );
+        assert_eq!(
+            run("try { missing } catch (e) { e }"),
+            Ok("'missing' is undefined".to_string())
+        );
+        assert_eq!(run("try { 1 } catch (e) { 2 }"), Ok("1".to_string()));
+        assert_error("try { 1 / 0 } catch (e) { 2 }\ne", "NameError");
+    }
+
+    #[test]
+    fn catch_receives_the_thrown_value() {
+        assert_eq!(
+            run("try { throw \"oops\" } catch (e) { [e, type(e)] }"),
+            Ok("[\"oops\", \"string\"]".to_string())
+        );
+        assert_eq!(
+            run("try { throw {\"code\": 404, \"path\": [\"a\"]} } catch (e) { [e.code, e.path, type(e)] }"),
+            Ok("[404, [\"a\"], \"dictionary\"]".to_string())
+        );
+        assert_eq!(
+            run("func f() { throw 42 }\ntry { f() } catch (e) { e + 1 }"),
+            Ok("43".to_string())
+        );
+        assert_error("throw \"oops\"", "Error: oops");
+    }
+
+    #[test]
+    fn ternaries_choose_one_branch() {
+        assert_eq!(
+            run("let a = 3\nlet b = 5\nlet x = a > b ? a : b\nx"),
+            Ok("5".to_string())
+        );
+        assert_eq!(run("1 + 1 == 2 ? \"yes\" : \"no\""), Ok("yes".to_string()));
+        assert_eq!(run("true ? 1 : 1 / 0"), Ok("1".to_string()));
+        let sign = |n: i32| run(&format!("let n = {}\nn > 0 ? 1 : n < 0 ? -1 : 0", n));
+        assert_eq!(sign(5), Ok("1".to_string()));
+        assert_eq!(sign(-5), Ok("-1".to_string()));
+        assert_eq!(sign(0), Ok("0".to_string()));
+        assert_eq!(run("true ? false ? 1 : 2 : 3"), Ok("2".to_string()));
+        assert_error("1 ? 2 : 3", "TypeError");
+    }
+
+    #[test]
+    fn match_runs_the_first_arm_that_matches() {
+        let day = |n: i32| {
+            run(&format!(
+                "match {} {{\n    0 => \"Sunday\",\n    6 => \"Saturday\",\n    _ => \"a weekday\",\n}}",
+                n
+            ))
+        };
+        assert_eq!(day(0), Ok("Sunday".to_string()));
+        assert_eq!(day(6), Ok("Saturday".to_string()));
+        assert_eq!(day(3), Ok("a weekday".to_string()));
+        assert_eq!(
+            run("match 2 { 1 => \"one\" 2 => \"two\" }"),
+            Ok("two".to_string())
+        );
+        assert_eq!(
+            run("match 3 { 1 => \"one\" 2 => \"two\" }"),
+            Ok("null".to_string())
+        );
+        assert_eq!(
+            run("match 1 { 1.0 => \"float\" _ => \"other\" }"),
+            Ok("float".to_string())
+        );
+        assert_eq!(
+            run("match 1 { _ => \"first\" 1 => \"second\" }"),
+            Ok("first".to_string())
+        );
+    }
+
+    #[test]
+    fn break_and_continue_leave_or_skip_iterations() {
+        assert_eq!(
+            run("let i = 0\nwhile true {\n    if i >= 5 { break }\n    i += 1\n}\ni"),
+            Ok("5".to_string())
+        );
+        assert_eq!(
+            run("let odd = []\nfor i in range(0, 10) {\n    if i % 2 == 0 { continue }\n    odd = push(odd, i)\n}\nodd"),
+            Ok("[1, 3, 5, 7, 9]".to_string())
+        );
+        assert_eq!(
+            run("let found = []\nfor i in range(0, 3) {\n    for j in range(0, 3) {\n        if j > i { break }\n        found = push(found, [i, j])\n    }\n}\nlen(found)"),
+            Ok("6".to_string())
+        );
+    }
+
+    #[test]
+    fn compound_assignment_applies_its_operator() {
+        for (operator, expected) in [
+            ("+=", "9"),
+            ("-=", "5"),
+            ("*=", "14"),
+            ("/=", "3.5"),
+            ("~/=", "3"),
+            ("%=", "1"),
+        ] {
+            assert_eq!(
+                run(&format!("let x = 7\nx {} 2\nx", operator)),
+                Ok(expected.to_string()),
+                "{}",
+                operator
+            );
+        }
+        assert_eq!(run("let s = \"a\"\ns += \"b\"\ns"), Ok("ab".to_string()));
+        assert_eq!(
+            run("let counts = [1, 2]\ncounts[1] += 5\ncounts"),
+            Ok("[1, 7]".to_string())
+        );
+        assert_eq!(
+            run("let x = 1\nlet y = x += 2\n[x, y]"),
+            Ok("[3, 3]".to_string())
+        );
+    }
+
+    #[test]
+    fn compound_assignment_to_an_undefined_variable_is_an_error() {
+        for operator in ["+=", "-=", "*=", "/=", "~/=", "%="] {
+            assert_error(
+                &format!("missing {} 1", operator),
+                "NameError: 'missing' is undefined",
+            );
+        }
+        assert_error("let x = 1\nx /= 0", "ZeroDivisionError");
+    }
+
+    #[test]
+    fn increments_yield_the_old_or_new_value() {
+        assert_eq!(run("let i = 0\n[i++, i]"), Ok("[0, 1]".to_string()));
+        assert_eq!(run("let i = 0\n[++i, i]"), Ok("[1, 1]".to_string()));
+        assert_eq!(run("let i = 5\n[i--, --i, i]"), Ok("[5, 3, 3]".to_string()));
+        assert_eq!(run("let f = 1.5\nf++\nf"), Ok("2.5".to_string()));
+        assert_eq!(
+            run("let count = 0\nlet i = 0\nwhile i < 10 { i++; count++ }\n[i, count]"),
+            Ok("[10, 10]".to_string())
+        );
+        assert_eq!(run("let a = [1, 2]\na[0]++\na"), Ok("[2, 2]".to_string()));
+    }
+
+    #[test]
+    fn increments_need_a_defined_number() {
+        assert_error("missing++", "NameError: 'missing' is undefined");
+        assert_error("let s = \"a\"\ns++", "TypeError");
+        assert_error("let n = null\n--n", "TypeError");
+    }
+
+    #[test]
+    fn unbounded_recursion_stops_at_the_call_depth_limit() {
+        assert_error(
+            "func f(n) { return 1 + f(n + 1) }\nf(0)",
+            "RecursionError: Stack overflow: maximum call depth 1000 exceeded",
+        );
+        assert_eq!(
+            run("func f(n) { return n == 0 ? 0 : 1 + f(n - 1) }\nf(999)"),
+            Ok("999".to_string())
+        );
+    }
+
+    #[test]
+    fn sessions_set_their_own_call_depth_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.max_call_depth = 10;
+        interpreter
+            .eval_str("func f(n) { return n == 0 ? 0 : 1 + f(n - 1) }")
+            .unwrap();
+        assert_eq!(interpreter.eval_str("f(9)").unwrap().to_string(), "9");
+        let errors = interpreter.eval_str("f(10)").unwrap_err();
+        assert!(errors[0]
+            .to_string()
+            .starts_with("RecursionError: Stack overflow: maximum call depth 10 exceeded"));
+    }
+
+    #[test]
+    fn remainders_take_the_sign_of_the_left_operand() {
+        for (source_code, expected) in [
+            ("7 % 3", "1"),
+            ("-7 % 3", "-1"),
+            ("7 % -3", "1"),
+            ("-7 % -3", "-1"),
+            ("7.5 % 2", "1.5"),
+            ("-7.5 % 2", "-1.5"),
+            ("7 % 2.5", "2.0"),
+            ("-7 % 2.5", "-2.0"),
+            ("7.5 % -2.0", "1.5"),
+        ] {
+            assert_eq!(
+                run(source_code),
+                Ok(expected.to_string()),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn floor_division_rounds_towards_negative_infinity() {
+        for (source_code, expected) in [
+            ("7 ~/ 2", "3"),
+            ("-7 ~/ 2", "-4"),
+            ("7 ~/ -2", "-4"),
+            ("-7 ~/ -2", "3"),
+            ("7.5 ~/ 2", "3.0"),
+            ("7 ~/ 2.0", "3.0"),
+            ("-7 ~/ 2.0", "-4.0"),
+        ] {
+            assert_eq!(
+                run(source_code),
+                Ok(expected.to_string()),
+                "{}",
+                source_code
+            );
+        }
+        // `//` starts a comment, so only the `7` is left.
+        assert_eq!(run("7 // 2"), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn bitwise_operators_work_on_integer_bits() {
+        for (source_code, expected) in [
+            ("12 & 10", "8"),
+            ("12 | 3", "15"),
+            ("~5", "-6"),
+            ("~-1", "0"),
+            ("1 << 4", "16"),
+            ("-8 >> 1", "-4"),
+            ("1 << 127", &i128::MIN.to_string()),
+            ("3 << 127", &i128::MIN.to_string()),
+            ("MIN_INT >> 127", "-1"),
+            ("MAX_INT >> 127", "0"),
+            ("12 & 4 == 4", "true"),
+        ] {
+            assert_eq!(
+                run(source_code),
+                Ok(expected.to_string()),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn bitwise_operators_reject_bad_operands() {
+        for source_code in ["1 << 128", "1 >> 200", "1 << -1"] {
+            assert_error(source_code, "Error: Shift amount");
+        }
+        for source_code in ["1.0 & 1", "1 | true", "~1.5", "\"a\" << 1"] {
+            assert_error(source_code, "TypeError");
+        }
+    }
+
+    #[test]
+    fn powers_with_negative_exponents_give_floats() {
+        for (source_code, expected) in [
+            ("2 ^ 10", "1024"),
+            ("2 ^ -2", "0.25"),
+            ("0 ^ 0", "1"),
+            ("(-2) ^ 3", "-8"),
+            ("2.0 ^ 3", "8.0"),
+            ("4 ^ 0.5", "2.0"),
+            ("2 ^ 126", &(1i128 << 126).to_string()),
+        ] {
+            assert_eq!(
+                run(source_code),
+                Ok(expected.to_string()),
+                "{}",
+                source_code
+            );
+        }
+    }
+
+    #[test]
+    fn powers_that_cannot_be_represented_are_errors() {
+        assert_error("2 ^ 127", "OverflowError: Integer overflow");
+        assert_error("10 ^ 100000000000", "OverflowError: Integer overflow");
+        assert_error(
+            "0 ^ -1",
+            "ZeroDivisionError: Zero cannot be raised to a negative power",
+        );
+    }
+
+    #[test]
+    fn characters_compare_and_convert() {
+        assert_eq!(run("'a'"), Ok("a".to_string()));
+        assert_eq!(run("type('\\n')"), Ok("char".to_string()));
+        assert_eq!(run("'a' == 'a'"), Ok("true".to_string()));
+        assert_eq!(run("'a' == 'b'"), Ok("false".to_string()));
+        assert_eq!(run("'a' < 'b'"), Ok("true".to_string()));
+        assert_eq!(run("'a' == \"a\""), Ok("false".to_string()));
+        assert_eq!(run("int('A')"), Ok("65".to_string()));
+        assert_eq!(run("char_from(233)"), Ok("é".to_string()));
+        assert_eq!(run("char_from(int('x')) == 'x'"), Ok("true".to_string()));
+        assert_eq!(run("str('c') + \"d\""), Ok("cd".to_string()));
+        assert_error(
+            "char_from(55296)",
+            "Error: '55296' is not a valid unicode code point",
+        );
+    }
+
+    #[test]
+    fn integers_and_floats_compare_exactly_past_two_to_the_53() {
+        // 2^53 + 1 has no float of its own; `as f64` would round it to 2^53.
+        assert_eq!(
+            run("9007199254740993 == 9007199254740992.0"),
+            Ok("false".to_string())
+        );
+        assert_eq!(
+            run("9007199254740993 > 9007199254740992.0"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            run("9007199254740992.0 < 9007199254740993"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            run("9007199254740992 == 9007199254740992.0"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            run("9007199254740992 >= 9007199254740992.0"),
+            Ok("true".to_string())
+        );
+        assert_eq!(
+            run("-9007199254740993 < -9007199254740992.0"),
+            Ok("true".to_string())
+        );
+        assert_eq!(run("3 < 3.5"), Ok("true".to_string()));
+        assert_eq!(run("-3 > -3.5"), Ok("true".to_string()));
+    }
+
+    #[test]
+    fn integers_compare_with_floats_beyond_their_range() {
+        assert_eq!(
+            compare_integer_to_float(i128::MAX, 1e39),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_integer_to_float(i128::MIN, -1e39),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_integer_to_float(i128::MIN, -(2.0f64.powi(127))),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_integer_to_float(0, f64::INFINITY),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_integer_to_float(0, f64::NAN), None);
+    }
+
+    #[test]
+    fn arithmetic_operators_give_the_same_results_for_every_pair_of_number_types() {
+        let cases = [
+            ("+", ["9", "9.0", "9.5", "-5.5"]),
+            ("-", ["5", "5.0", "5.5", "-9.5"]),
+            ("*", ["14", "14.0", "15.0", "-15.0"]),
+            ("/", ["3.5", "3.5", "3.75", "-3.75"]),
+            ("~/", ["3", "3.0", "3.0", "-4.0"]),
+            ("%", ["1", "1.0", "1.5", "-1.5"]),
+            ("^", ["49", "49.0", "56.25", "56.25"]),
+        ];
+        for (operator, expected) in cases {
+            for (operands, expected) in ["7 _ 2", "7 _ 2.0", "7.5 _ 2", "-7.5 _ 2.0"]
+                .iter()
+                .zip(expected)
+            {
+                let source_code = operands.replace('_', operator);
+                assert_eq!(
+                    run(&source_code),
+                    Ok(expected.to_string()),
+                    "{}",
+                    source_code
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_operators_reject_operands_that_are_not_numbers() {
+        for operator in ["-", "*", "/", "~/", "%", "^"] {
+            assert_error(
+                &format!("true {} 1", operator),
+                "TypeError: Incompatible types: 'Boolean(true)' and 'Integer(1)'",
+            );
+        }
+    }
+
+    #[test]
+    fn destructuring_takes_each_element_in_order() {
+        assert_eq!(run("let [a, b] = [1, 2]; [b, a]"), Ok("[2, 1]".to_string()));
+        assert_eq!(
+            run("const [a, [b, c]] = [1, [2, 3]]; a + b + c"),
+            Ok("6".to_string())
+        );
+        assert_eq!(
+            run("let a = 1; let b = 2; [a, b] = [b, a]; [a, b]"),
+            Ok("[2, 1]".to_string())
+        );
+    }
+
+    #[test]
+    fn destructuring_needs_one_element_per_target() {
+        assert_error(
+            "let [a, b] = [1, 2, 3]",
+            "Error: Cannot destructure an array of 3 elements into 2 targets",
+        );
+        assert_error(
+            "let a = 0; let b = 0; [a, b] = [1]",
+            "Error: Cannot destructure an array of 1 elements into 2 targets",
+        );
+        assert_error(
+            "let [a, b] = 1",
+            "TypeError: Cannot destructure '1', expected an array",
+        );
+    }
+
+    #[test]
+    fn null_operands_are_a_clear_error() {
+        for operator in ["+", "-", "*", "/", "~/", "%", "^", "&", "|", "<<", ">>"] {
+            for source_code in [
+                format!("null {} 1", operator),
+                format!("1.5 {} null", operator),
+            ] {
+                assert_error(
+                    &source_code,
+                    "TypeError: Cannot use null in arithmetic expression",
+                );
+            }
+        }
+        assert_error(
+            "-null",
+            "TypeError: Cannot use null in arithmetic expression",
+        );
+        assert_error(
+            "~null",
+            "TypeError: Cannot use null in arithmetic expression",
+        );
+        assert_error(
+            "let total = null\ntotal += 1",
+            "TypeError: Cannot use null in arithmetic expression",
+        );
+    }
+
+    #[test]
+    fn named_arguments_bind_by_name_in_any_order() {
+        let draw =
+            "func draw(x, y, color = \"red\") { return format(\"{} {} {}\", x, y, color) }\n";
+        assert_eq!(
+            run(&format!("{}draw(y: 2, x: 1)", draw)),
+            Ok("1 2 red".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}draw(1, color: \"blue\", y: 2)", draw)),
+            Ok("1 2 blue".to_string())
+        );
+    }
+
+    #[test]
+    fn named_arguments_must_name_a_parameter_once() {
+        let draw = "func draw(x, y) { return x + y }\n";
+        assert_error(
+            &format!("{}draw(1, z: 2)", draw),
+            "TypeError: Function 'draw' has no parameter named 'z'",
+        );
+        assert_error(
+            &format!("{}draw(1, x: 2)", draw),
+            "TypeError: Argument 'x' was given more than once",
+        );
+        assert_error(
+            &format!("{}draw(x: 1, 2)", draw),
+            "SyntaxError: Positional arguments must come before named arguments",
+        );
+        assert_error(
+            "len(value: [1])",
+            "TypeError: Function 'len' does not take named arguments",
+        );
+    }
+
+    #[test]
+    fn defaults_fill_arguments_that_are_left_out() {
+        let greet = "func greet(name, greeting = \"Hello\") { return greeting + \", \" + name }\n";
+        assert_eq!(
+            run(&format!("{}greet(\"Ada\")", greet)),
+            Ok("Hello, Ada".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}greet(\"Ada\", \"Hi\")", greet)),
+            Ok("Hi, Ada".to_string())
+        );
+        assert_error(
+            &format!("{}greet()", greet),
+            "TypeError: Function 'greet' expected 1 to 2 arguments, found 0",
+        );
+    }
+
+    #[test]
+    fn defaults_are_evaluated_at_each_call() {
+        assert_eq!(
+            run("let calls = 0\n\
+                 func count() { calls += 1; return calls }\n\
+                 func f(n = count()) { return n }\n\
+                 [calls, f(), f(10), f(), calls]"),
+            Ok("[0, 1, 10, 2, 2]".to_string())
+        );
+        assert_eq!(
+            run("func f(a, b = a * 2) { return b }\nf(4)"),
+            Ok("8".to_string())
+        );
+    }
+
+    #[test]
+    fn rest_parameters_collect_the_arguments_left_over() {
+        let sum = "func sum(first, ...numbers) { return [first, numbers] }\n";
+        assert_eq!(run(&format!("{}sum(1)", sum)), Ok("[1, []]".to_string()));
+        assert_eq!(
+            run(&format!("{}sum(1, 2)", sum)),
+            Ok("[1, [2]]".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}sum(1, 2, 3, 4)", sum)),
+            Ok("[1, [2, 3, 4]]".to_string())
+        );
+        assert_error(
+            &format!("{}sum()", sum),
+            "TypeError: Function 'sum' expected at least 1 arguments, found 0",
+        );
+        assert_error(
+            &format!("{}sum(1, numbers: [2])", sum),
+            "TypeError: Rest parameter 'numbers' cannot be given by name",
+        );
+    }
+
+    #[test]
+    fn rest_parameters_come_last() {
+        assert_error(
+            "func f(...rest, last) { }",
+            "SyntaxError: A rest parameter must be the last parameter and cannot have a default",
+        );
+    }
+
+    #[test]
+    fn function_expressions_can_be_stored_and_called() {
+        assert_eq!(
+            run("let double = func(x) => x * 2\ndouble(21)"),
+            Ok("42".to_string())
+        );
+        assert_eq!(
+            run("let add = func(a, b) { return a + b }\nadd(1, 2)"),
+            Ok("3".to_string())
+        );
+        assert_eq!(run("type(func() => 1)"), Ok("function".to_string()));
+    }
+
+    #[test]
+    fn function_expressions_can_be_passed_inline() {
+        assert_eq!(
+            run("func apply(f, value) { return f(value) }\napply(func(x) => x + 1, 1)"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run("func adder(n) { return func(x) => x + n }\nadder(10)(5)"),
+            Ok("15".to_string())
+        );
+    }
+
+    #[test]
+    fn dots_read_and_write_dictionary_entries() {
+        let user = "let user = {\"name\": \"Ada\", \"address\": {\"city\": \"London\"}}\n";
+        assert_eq!(run(&format!("{}user.name", user)), Ok("Ada".to_string()));
+        assert_eq!(
+            run(&format!("{}user.address.city", user)),
+            Ok("London".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}user.name = \"Grace\"; user[\"name\"]", user)),
+            Ok("Grace".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{}user.address.city = \"Paris\"; user[\"address\"][\"city\"]",
+                user
+            )),
+            Ok("Paris".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}user.age = 36; user.age", user)),
+            Ok("36".to_string())
+        );
+    }
+
+    #[test]
+    fn dots_on_a_missing_key_are_an_error() {
+        assert_error("let d = {\"a\": 1}\nd.b", "KeyError: Key 'b' not found");
+        assert_error(
+            "let d = {\"a\": {:}}\nd.a.b.c = 1",
+            "KeyError: Key 'b' not found",
+        );
+    }
+
+    #[test]
+    fn do_while_loops_run_once_before_checking() {
+        assert_eq!(
+            run("let tries = 0\ndo {\n    tries = tries + 1\n} while false\ntries"),
+            Ok("1".to_string())
+        );
+        assert_eq!(
+            run("let i = 0\ndo { i += 1 } while i < 5\ni"),
+            Ok("5".to_string())
+        );
+        assert_eq!(
+            run("let i = 0\n\
+                 let odd = 0\n\
+                 do {\n\
+                 i += 1\n\
+                 if i % 2 == 0 { continue }\n\
+                 odd += 1\n\
+                 } while i < 6\n\
+                 odd"),
+            Ok("3".to_string())
+        );
+    }
+
+    #[test]
+    fn loop_else_runs_only_when_the_loop_did_not_break() {
+        let search = |target: &str| {
+            format!(
+                "let result = \"\"\n\
+                 for x in [1, 2, 3] {{\n\
+                 if x == {} {{ result = \"found\"; break }}\n\
+                 }} else {{\n\
+                 result = \"not found\"\n\
+                 }}\n\
+                 result",
+                target
+            )
+        };
+        assert_eq!(run(&search("2")), Ok("found".to_string()));
+        assert_eq!(run(&search("5")), Ok("not found".to_string()));
+        assert_eq!(
+            run("let i = 0\nwhile i < 3 { i += 1 } else { \"done\" }"),
+            Ok("done".to_string())
+        );
+        assert_eq!(
+            run("let i = 0\nlet ran = false\n\
+                 while i < 3 { i += 1; if i == 2 { break } } else { ran = true }\n\
+                 [i, ran]"),
+            Ok("[2, false]".to_string())
+        );
+        assert_eq!(
+            run("for x in [] { } else { \"empty\" }"),
+            Ok("empty".to_string())
+        );
+    }
+
+    #[test]
+    fn match_arms_take_alternatives_and_inclusive_ranges() {
+        let size = |n: &str| {
+            run(&format!(
+                "match {} {{\n\
+                 0 => \"none\",\n\
+                 1 | 2 | 3 => \"a few\",\n\
+                 4..10 => \"several\",\n\
+                 _ => \"many\",\n\
+                 }}",
+                n
+            ))
+        };
+        assert_eq!(size("0"), Ok("none".to_string()));
+        assert_eq!(size("2"), Ok("a few".to_string()));
+        assert_eq!(size("3"), Ok("a few".to_string()));
+        assert_eq!(size("4"), Ok("several".to_string()));
+        assert_eq!(size("10"), Ok("several".to_string()));
+        assert_eq!(size("11"), Ok("many".to_string()));
+        assert_eq!(size("4.0"), Ok("many".to_string()));
+        assert_eq!(
+            run("match 5 { 1..10 => \"first\", 5 => \"second\" }"),
+            Ok("first".to_string())
+        );
+    }
+
+    #[test]
+    fn step_hooks_run_before_every_statement() {
+        let steps = Rc::new(Cell::new(0));
+        let mut interpreter = Interpreter::new();
+        let counted = Rc::clone(&steps);
+        interpreter.on_step(move |_, _| {
+            counted.set(counted.get() + 1);
+            Step::Continue
+        });
+        let value = interpreter
+            .eval_str("let total = 0\nfor i in range(0, 3) { total += i }\ntotal")
+            .unwrap();
+        assert_eq!(value.to_string(), "3");
+        // Three statements at the top level and one for each iteration.
+        assert_eq!(steps.get(), 6);
+    }
+
+    #[test]
+    fn step_hooks_can_stop_the_program() {
+        let mut interpreter = Interpreter::new();
+        interpreter.on_step(|_, environment| match environment.get("total") {
+            Some(RuntimeValue::Integer(total)) if total > 5 => Step::Stop,
+            _ => Step::Continue,
+        });
+        let errors = interpreter
+            .eval_str("let total = 0\nwhile true {\n    total += 1\n}")
+            .unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Error: Execution stopped by the step hook in line 3 column 11"
+        );
+        assert_eq!(interpreter.get_global("total").unwrap().to_string(), "6");
+    }
+
+    #[test]
+    fn tail_calls_do_not_count_towards_the_call_depth() {
+        assert_eq!(
+            run("func sum_to(n, total) {\n\
+                 if n == 0 { return total }\n\
+                 return sum_to(n - 1, total + n)\n\
+                 }\n\
+                 sum_to(100000, 0)"),
+            Ok("5000050000".to_string())
+        );
+    }
+
+    #[test]
+    fn calls_that_are_not_returned_still_count() {
+        assert_error(
+            "func sum_to(n) {\n\
+             if n == 0 { return 0 }\n\
+             return n + sum_to(n - 1)\n\
+             }\n\
+             sum_to(100000)",
+            "RecursionError: Stack overflow: maximum call depth 1000 exceeded",
+        );
+        assert_error(
+            "func count(n) { if n > 0 { count(n - 1) } }\ncount(100000)",
+            "RecursionError: Stack overflow: maximum call depth 1000 exceeded",
+        );
+    }
+
+    #[test]
+    fn step_hooks_only_run_for_their_own_interpreter() {
+        let steps = Rc::new(Cell::new(0));
+        let mut debugged = Interpreter::new();
+        let counted = Rc::clone(&steps);
+        debugged.on_step(move |_, _| {
+            counted.set(counted.get() + 1);
+            Step::Continue
+        });
+        debugged.eval_str("let x = 1\nx + 1").unwrap();
+        assert_eq!(steps.get(), 2);
+        debugged.eval_str("undefined_name").unwrap_err();
+        assert_eq!(steps.get(), 3);
+
+        Interpreter::new().eval_str("let x = 1\nx + 1").unwrap();
+        let program = parser::parse("let y = 2\ny * 2").unwrap();
+        evaluate_program(program, &generate_environment()).unwrap();
+        assert_eq!(steps.get(), 3);
+
+        drop(debugged);
+        assert_eq!(Rc::strong_count(&steps), 1);
+    }
+
+    #[test]
+    fn tracing_stops_when_the_traced_interpreter_returns() {
+        let mut traced = Interpreter::new();
+        traced.trace = true;
+        traced.eval_str("1 + 1").unwrap();
+        assert!(!TRACE.get());
+        traced.eval_str("1 / 0").unwrap_err();
+        assert!(!TRACE.get());
+
+        set_trace(true);
+        Interpreter::new().eval_str("1 + 1").unwrap();
+        assert!(TRACE.get());
+        set_trace(false);
+    }
+
+    #[test]
+    fn call_depth_limits_end_with_their_session() {
+        let recurse = "func f(n) { return n == 0 ? 0 : 1 + f(n - 1) }\nf(50)";
+        let mut limited = Interpreter::new();
+        limited.max_call_depth = 10;
+        limited.eval_str(recurse).unwrap_err();
+        assert_eq!(MAX_CALL_DEPTH.get(), DEFAULT_MAX_CALL_DEPTH);
+
+        assert_eq!(
+            Interpreter::new().eval_str(recurse).unwrap().to_string(),
+            "50"
+        );
+        let program = parser::parse(recurse).unwrap();
+        assert_eq!(
+            evaluate_program(program, &generate_environment())
+                .unwrap()
+                .to_string(),
+            "50"
+        );
     }
 }
@@ -0,0 +1,391 @@
+use crate::{
+    error::{Error, ErrorType},
+    interpreter::RuntimeValue,
+    parser::MAX_NESTING,
+};
+use core::iter::Peekable;
+use std::{collections::HashMap, str::Chars};
+
+impl RuntimeValue {
+    // Dictionary keys are written in sorted order, so equal values always give the same text.
+    // Characters become one-character strings. Functions, iterables and modules have no JSON form, and neither do NaN and infinite floats.
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut json = String::new();
+        write_value(self, &mut json)?;
+        Ok(json)
+    }
+
+    // Numbers without a fraction or exponent become integers, unless they do not fit in one.
+    pub fn from_json(json: &str) -> Result<RuntimeValue, Error> {
+        let mut reader = Reader {
+            characters: json.chars().peekable(),
+            line: 1,
+            column: 1,
+        };
+
+        let value = reader.read_value(0)?;
+        reader.skip_whitespace();
+        match reader.characters.peek() {
+            None => Ok(value),
+            Some(&character) => Err(reader.unexpected(Some(character))),
+        }
+    }
+}
+
+fn write_value(value: &RuntimeValue, json: &mut String) -> Result<(), Error> {
+    match value {
+        RuntimeValue::Null => json.push_str("null"),
+        RuntimeValue::Boolean(b) => json.push_str(&b.to_string()),
+        RuntimeValue::Integer(i) => json.push_str(&i.to_string()),
+        RuntimeValue::Float(f) if f.is_finite() => json.push_str(&format!("{:?}", f)),
+        RuntimeValue::Float(f) => return Err(not_convertible(&f.to_string())),
+        RuntimeValue::String(s) => write_string(s, json),
+        RuntimeValue::Char(c) => write_string(&c.to_string(), json),
+        RuntimeValue::Array(elements) => {
+            json.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_value(element, json)?;
+            }
+            json.push(']');
+        }
+        RuntimeValue::Dictionary(entries) => {
+            let mut keys = entries.keys().collect::<Vec<&String>>();
+            keys.sort();
+            json.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_string(key, json);
+                json.push(':');
+                write_value(&entries[key], json)?;
+            }
+            json.push('}');
+        }
+        RuntimeValue::Iterable(_) => return Err(not_convertible("an iterable")),
+        RuntimeValue::Module { .. } => return Err(not_convertible("a module")),
+        RuntimeValue::Function { .. } | RuntimeValue::NativeFunction { .. } => {
+            return Err(not_convertible("a function"))
+        }
+    }
+
+    Ok(())
+}
+
+fn write_string(s: &str, json: &mut String) {
+    json.push('"');
+    for character in s.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn not_convertible(what: &str) -> Error {
+    Error::new(
+        ErrorType::TypeError,
+        format!("Cannot convert {} to JSON", what),
+        0,
+        0,
+    )
+}
+
+struct Reader<'a> {
+    characters: Peekable<Chars<'a>>,
+    line: u128,
+    column: u128,
+}
+
+impl Reader<'_> {
+    fn next(&mut self) -> Option<char> {
+        let character = self.characters.next()?;
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(character)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.characters.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.next();
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::new(ErrorType::SyntaxError, message, self.line, self.column)
+    }
+
+    fn unexpected(&self, character: Option<char>) -> Error {
+        match character {
+            Some(character) => self.error(format!("Unexpected character '{}' in JSON", character)),
+            None => self.error("Unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        match self.characters.peek().copied() {
+            Some(character) if character == expected => {
+                self.next();
+                Ok(())
+            }
+            character => Err(self.unexpected(character)),
+        }
+    }
+
+    fn read_value(&mut self, depth: usize) -> Result<RuntimeValue, Error> {
+        if depth >= MAX_NESTING {
+            return Err(self.error("Too many levels of nesting in JSON".to_string()));
+        }
+
+        self.skip_whitespace();
+        match self.characters.peek().copied() {
+            Some('n') => self.read_keyword("null", RuntimeValue::Null),
+            Some('t') => self.read_keyword("true", RuntimeValue::Boolean(true)),
+            Some('f') => self.read_keyword("false", RuntimeValue::Boolean(false)),
+            Some('"') => Ok(RuntimeValue::String(self.read_string()?.into())),
+            Some('[') => self.read_array(depth),
+            Some('{') => self.read_object(depth),
+            Some('-' | '0'..='9') => self.read_number(),
+            character => Err(self.unexpected(character)),
+        }
+    }
+
+    fn read_keyword(&mut self, keyword: &str, value: RuntimeValue) -> Result<RuntimeValue, Error> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn read_array(&mut self, depth: usize) -> Result<RuntimeValue, Error> {
+        self.next();
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.characters.peek() == Some(&']') {
+            self.next();
+            return Ok(RuntimeValue::Array(elements.into()));
+        }
+
+        loop {
+            elements.push(self.read_value(depth + 1)?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => (),
+                Some(']') => return Ok(RuntimeValue::Array(elements.into())),
+                character => return Err(self.unexpected(character)),
+            }
+        }
+    }
+
+    // A key that appears twice keeps its last value.
+    fn read_object(&mut self, depth: usize) -> Result<RuntimeValue, Error> {
+        self.next();
+        let mut entries = HashMap::new();
+
+        self.skip_whitespace();
+        if self.characters.peek() == Some(&'}') {
+            self.next();
+            return Ok(RuntimeValue::Dictionary(entries.into()));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.characters.peek() != Some(&'"') {
+                let character = self.characters.peek().copied();
+                return Err(self.unexpected(character));
+            }
+            let key = self.read_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.insert(key, self.read_value(depth + 1)?);
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => (),
+                Some('}') => return Ok(RuntimeValue::Dictionary(entries.into())),
+                character => return Err(self.unexpected(character)),
+            }
+        }
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        self.next();
+        let mut string = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.read_escape()?),
+                Some(character) if character.is_control() => {
+                    return Err(self.unexpected(Some(character)))
+                }
+                Some(character) => string.push(character),
+                None => return Err(self.unexpected(None)),
+            }
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, Error> {
+        match self.next() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let high = self.read_code_unit()?;
+                if !(0xD800..0xDC00).contains(&high) {
+                    return char::from_u32(high)
+                        .ok_or_else(|| self.error("Invalid unicode escape in JSON".to_string()));
+                }
+
+                // Characters outside the basic plane are written as a pair of UTF-16 surrogates.
+                self.expect('\\')?;
+                self.expect('u')?;
+                let low = self.read_code_unit()?;
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(self.error("Invalid unicode escape in JSON".to_string()));
+                }
+                char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    .ok_or_else(|| self.error("Invalid unicode escape in JSON".to_string()))
+            }
+            character => Err(self.unexpected(character)),
+        }
+    }
+
+    fn read_code_unit(&mut self) -> Result<u32, Error> {
+        let mut code_unit = 0;
+        for _ in 0..4 {
+            let character = self.next();
+            match character.and_then(|character| character.to_digit(16)) {
+                Some(digit) => code_unit = code_unit * 16 + digit,
+                None => return Err(self.unexpected(character)),
+            }
+        }
+        Ok(code_unit)
+    }
+
+    // A number is an optional `-`, an integer part without leading zeros, and then an optional
+    // fraction and exponent, each with at least one digit. `+1`, `01`, `1.` and `.5` are not numbers.
+    fn read_number(&mut self) -> Result<RuntimeValue, Error> {
+        let mut number = String::new();
+        if self.characters.peek() == Some(&'-') {
+            number.extend(self.next());
+        }
+        if self.characters.peek() == Some(&'0') {
+            number.extend(self.next());
+        } else {
+            self.read_digits(&mut number)?;
+        }
+        if self.characters.peek() == Some(&'.') {
+            number.extend(self.next());
+            self.read_digits(&mut number)?;
+        }
+        if matches!(self.characters.peek(), Some('e' | 'E')) {
+            number.extend(self.next());
+            if matches!(self.characters.peek(), Some('+' | '-')) {
+                number.extend(self.next());
+            }
+            self.read_digits(&mut number)?;
+        }
+
+        if !number.contains(['.', 'e', 'E']) {
+            if let Ok(integer) = number.parse::<i128>() {
+                return Ok(RuntimeValue::Integer(integer));
+            }
+        }
+        match number.parse::<f64>() {
+            Ok(float) if float.is_finite() => Ok(RuntimeValue::Float(float)),
+            _ => Err(self.error(format!("Invalid number '{}' in JSON", number))),
+        }
+    }
+
+    // At least one digit.
+    fn read_digits(&mut self, number: &mut String) -> Result<(), Error> {
+        let character = self.characters.peek().copied();
+        if !matches!(character, Some('0'..='9')) {
+            return Err(self.unexpected(character));
+        }
+        while matches!(self.characters.peek(), Some('0'..='9')) {
+            number.extend(self.next());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::is_equal;
+
+    fn round_trip(json: &str) {
+        let value = RuntimeValue::from_json(json).unwrap();
+        assert_eq!(value.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn nested_arrays_and_dictionaries_round_trip() {
+        round_trip("[]");
+        round_trip("{}");
+        round_trip("[1,[2,[3,[]]],\"a\"]");
+        round_trip("{\"a\":{\"b\":[1,2.5,{\"c\":null}]},\"d\":[true,false]}");
+        round_trip("[{\"key\":[{},[]]},-3,1e300,0.5]");
+    }
+
+    #[test]
+    fn strings_round_trip_with_escapes() {
+        round_trip("\"quote \\\" backslash \\\\ newline \\n tab \\t\"");
+        round_trip("\"\\u0001 é 😀\"");
+        assert!(is_equal(
+            &RuntimeValue::from_json("\"\\ud83d\\ude00\"").unwrap(),
+            &RuntimeValue::String("😀".into())
+        ));
+    }
+
+    #[test]
+    fn numbers_follow_the_json_grammar() {
+        for json in [
+            "0", "-0", "12", "-12", "1.5", "0.25", "1e5", "1E-5", "-2.5e+3",
+        ] {
+            assert!(RuntimeValue::from_json(json).is_ok(), "{}", json);
+        }
+        for json in [
+            "+1", "01", "-01", "1.", ".5", "1e", "1e+", "-", "1.e5", "--1", "1-2",
+        ] {
+            assert!(RuntimeValue::from_json(json).is_err(), "{}", json);
+        }
+        assert!(matches!(
+            RuntimeValue::from_json("42"),
+            Ok(RuntimeValue::Integer(42))
+        ));
+        assert!(matches!(
+            RuntimeValue::from_json("42.0"),
+            Ok(RuntimeValue::Float(_))
+        ));
+    }
+
+    #[test]
+    fn values_without_a_json_form_are_errors() {
+        assert!(RuntimeValue::Float(f64::NAN).to_json().is_err());
+        assert!(RuntimeValue::from_json("[1,]").is_err());
+        assert!(RuntimeValue::from_json("{\"a\" 1}").is_err());
+        assert!(RuntimeValue::from_json(&"[".repeat(MAX_NESTING + 1)).is_err());
+    }
+}
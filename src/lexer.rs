@@ -1,12 +1,23 @@
 use crate::error::{Error, ErrorType};
+use std::{iter::Peekable, str::Chars};
 
-#[derive(Debug, Clone)]
-pub enum TokenType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
     BinaryOperator,
     Float,
     Integer,
+    String,
+    Char,
     Identifier,
     Dot,
+    Ellipsis,
+    Range,
+    Comma,
+    Semicolon,
+    Colon,
+    QuestionMark,
+    Arrow,
+    IncrementOperator,
     OpenParenthesis,
     CloseParenthesis,
     OpenBracket,
@@ -14,160 +25,821 @@ pub enum TokenType {
     OpenBrace,
     CloseBrace,
     Keyword,
+    InterpolatedStringStart,
+    InterpolatedStringEnd,
     AssignmentOperator,
+    ComparisonOperator,
+    LogicalOperator,
     EOF,
 }
 
-#[derive(Debug, Clone)]
-pub enum Token {
-    Token {
-        token_type: TokenType,
-        value: String,
-        line: u128,
-        column: u128,
-    },
+// `value` is the token's source text, except for string and character literals, where it is the
+// text with escapes resolved and without the quotes. `line` and `column` count from one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub value: String,
+    pub line: u128,
+    pub column: u128,
 }
 
-pub const KEYWORDS: [&str; 5] = ["let", "if", "else", "while", "for"];
+pub const KEYWORDS: [&str; 20] = [
+    "let", "const", "if", "else", "while", "for", "in", "func", "return", "true", "false", "null",
+    "try", "catch", "throw", "match", "break", "continue", "import", "do",
+];
 
+fn name_token(name: String, line: u128, column: u128) -> Result<Token, Error> {
+    if name.starts_with('_')
+        && name.chars().all(|c| c == '_' || c.is_ascii_digit())
+        && name.chars().any(|c| c.is_ascii_digit())
+    {
+        return Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Number literal '{}' cannot start with '_'", name),
+            line,
+            column,
+        ));
+    }
+
+    let kind = if KEYWORDS.contains(&name.as_str()) {
+        TokenKind::Keyword
+    } else {
+        TokenKind::Identifier
+    };
+
+    Ok(Token {
+        kind,
+        value: name,
+        line,
+        column,
+    })
+}
+
+fn number_token(number: String, line: u128, column: u128) -> Result<Token, Error> {
+    let value = strip_separators(&number, &number, line, column)?;
+    let (kind, description, in_range) = if value.contains(['.', 'e', 'E']) {
+        let in_range = value.parse::<f64>().is_ok_and(|f| f.is_finite());
+        (TokenKind::Float, "Float", in_range)
+    } else {
+        (TokenKind::Integer, "Integer", value.parse::<i128>().is_ok())
+    };
+
+    // The parser relies on every number token fitting its type.
+    if !in_range {
+        return Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("{} literal '{}' out of range", description, number),
+            line,
+            column,
+        ));
+    }
+
+    Ok(Token {
+        kind,
+        value,
+        line,
+        column,
+    })
+}
+
+// Underscores may only appear between two digits, so `1_000` is allowed but
+// `100_`, `1__0` and `1_.5` are not.
+fn strip_separators(
+    digits: &str,
+    literal: &str,
+    line: u128,
+    column: u128,
+) -> Result<String, Error> {
+    let characters = digits.chars().collect::<Vec<char>>();
+    for (i, character) in characters.iter().enumerate() {
+        if *character != '_' {
+            continue;
+        }
+
+        let before = i.checked_sub(1).map(|j| characters[j]);
+        let after = characters.get(i + 1).copied();
+        if !matches!(before, Some(c) if c.is_alphanumeric())
+            || !matches!(after, Some(c) if c.is_alphanumeric())
+        {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                format!("Misplaced '_' in number literal '{}'", literal),
+                line,
+                column,
+            ));
+        }
+    }
+
+    Ok(digits.replace('_', ""))
+}
+
+fn lex_unicode_escape(
+    characters: &mut Peekable<Chars>,
+    line: u128,
+    column: u128,
+) -> Result<char, Error> {
+    let invalid = || {
+        Error::new(
+            ErrorType::SyntaxError,
+            "Invalid unicode escape, expected '\\u{XXXX}'".to_string(),
+            line,
+            column,
+        )
+    };
+
+    if characters.next() != Some('{') {
+        return Err(invalid());
+    }
+
+    let mut digits = String::new();
+    loop {
+        match characters.next() {
+            Some('}') => break,
+            Some(digit) if digit.is_ascii_hexdigit() && digits.len() < 6 => digits.push(digit),
+            _ => return Err(invalid()),
+        }
+    }
+
+    u32::from_str_radix(&digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(invalid)
+}
+
+// Reads a character literal after its opening quote, returning the character and how many columns
+// the rest of the literal takes up. Escapes are the same as in strings, with `\'` for the quote.
+fn lex_char_literal(
+    characters: &mut Peekable<Chars>,
+    line: u128,
+    column: u128,
+) -> Result<(char, u128), Error> {
+    let error =
+        |message: &str| Error::new(ErrorType::SyntaxError, message.to_string(), line, column);
+
+    let (character, width) = match characters.next() {
+        Some('\\') => {
+            let escaped = match characters.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('\'') => '\'',
+                Some('"') => '"',
+                Some('$') => '$',
+                Some('u') => lex_unicode_escape(characters, line, column)?,
+                Some(other) => {
+                    return Err(error(&format!("Unknown escape sequence '\\{}'", other)))
+                }
+                None => return Err(error("Character literal not closed.")),
+            };
+            (escaped, 2)
+        }
+        Some('\'') => return Err(error("Empty character literal")),
+        Some('\n') | None => return Err(error("Character literal not closed.")),
+        Some(character) => (character, 1),
+    };
+
+    match characters.next() {
+        Some('\'') => Ok((character, width + 1)),
+        Some('\n') | None => Err(error("Character literal not closed.")),
+        Some(_) => Err(error(
+            "Character literal must contain exactly one character",
+        )),
+    }
+}
+
+// Collects the source of an interpolated expression up to its matching '}',
+// skipping over braces that appear inside nested blocks or string literals.
+fn lex_interpolation_source(
+    characters: &mut Peekable<Chars>,
+    line: u128,
+    column: u128,
+) -> Result<String, Error> {
+    let mut source = String::new();
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut in_char = false;
+
+    while let Some(character) = characters.next() {
+        if in_char {
+            source.push(character);
+            match character {
+                '\\' => {
+                    if let Some(escaped) = characters.next() {
+                        source.push(escaped);
+                    }
+                }
+                '\'' => in_char = false,
+                _ => (),
+            }
+            continue;
+        }
+
+        if in_string {
+            source.push(character);
+            match character {
+                '\\' => {
+                    if let Some(escaped) = characters.next() {
+                        source.push(escaped);
+                    }
+                }
+                '"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+
+        match character {
+            '"' => in_string = true,
+            '\'' => in_char = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(source);
+                }
+            }
+            _ => (),
+        }
+        source.push(character);
+    }
+
+    Err(Error::new(
+        ErrorType::SyntaxError,
+        "Interpolation not closed.".to_string(),
+        line,
+        column,
+    ))
+}
+
+// Splits source code into tokens for tools such as syntax highlighters. Comments and whitespace are
+// skipped. A string containing `${...}` comes out as an `InterpolatedStringStart` token, then the
+// literal parts as `String` tokens with the tokens of each embedded expression between them, then an
+// `InterpolatedStringEnd` token.
+pub fn lex(source_code: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = tokenize(source_code)?;
+    tokens.pop();
+    Ok(tokens)
+}
+
+// Like `lex`, but ends with an `EOF` token for the parser.
 pub fn tokenize(source_code: &str) -> Result<Vec<Token>, Error> {
     let mut tokens = Vec::new();
     let mut number = String::new();
     let mut name = String::new();
+    let mut string = String::new();
     let mut parsing_number = false;
     let mut parsing_comment = false;
+    let mut parsing_line_comment = false;
+    let mut block_comment_starts = Vec::new();
+    let mut parsing_string = false;
+    let mut interpolation = Vec::new();
     let mut line = 1;
     let mut column = 1;
+    // Names, numbers and strings are only turned into tokens once they end, so
+    // the column they started at is kept to give the token its position.
+    let mut start_column = 1;
+    let mut string_start = (1, 1);
+
+    let mut characters = source_code.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '\n' && (parsing_comment || !block_comment_starts.is_empty()) {
+            line += 1;
+            column = 1;
+            continue;
+        }
 
-    for character in source_code.chars() {
         if parsing_comment {
-            if character == '!' {
+            if character == '#' {
                 parsing_comment = false;
             }
+            column += 1;
+            continue;
+        }
+
+        if parsing_line_comment {
+            if character == '\n' {
+                parsing_line_comment = false;
+                line += 1;
+                column = 1;
+            }
+            continue;
+        }
+
+        if !block_comment_starts.is_empty() {
+            match (character, characters.peek()) {
+                ('*', Some('/')) => {
+                    characters.next();
+                    column += 1;
+                    block_comment_starts.pop();
+                }
+                ('/', Some('*')) => {
+                    characters.next();
+                    column += 1;
+                    block_comment_starts.push((line, column));
+                }
+                _ => (),
+            }
+            column += 1;
             continue;
         }
 
-        if !character.is_alphanumeric() && '.' != character {
-            if !name.is_empty() {
-                if KEYWORDS.contains(&name.as_str()) {
-                    tokens.push(Token::Token {
-                        token_type: TokenType::Keyword,
-                        value: name,
+        if parsing_string {
+            match character {
+                '"' if interpolation.is_empty() => {
+                    tokens.push(Token {
+                        kind: TokenKind::String,
+                        value: string,
                         line,
-                        column,
+                        column: start_column,
                     });
-                } else {
-                    tokens.push(Token::Token {
-                        token_type: TokenType::Identifier,
-                        value: name,
+                    string = String::new();
+                    parsing_string = false;
+                }
+                '"' => {
+                    tokens.push(Token {
+                        kind: TokenKind::InterpolatedStringStart,
+                        value: String::new(),
+                        line,
+                        column: start_column,
+                    });
+                    tokens.append(&mut interpolation);
+                    tokens.push(Token {
+                        kind: TokenKind::String,
+                        value: string,
                         line,
                         column,
                     });
-                }
-                name = String::new();
-            }
-
-            if !number.is_empty() {
-                if number.contains('.') {
-                    tokens.push(Token::Token {
-                        token_type: TokenType::Float,
-                        value: number,
+                    tokens.push(Token {
+                        kind: TokenKind::InterpolatedStringEnd,
+                        value: String::new(),
                         line,
                         column,
                     });
-                } else {
-                    tokens.push(Token::Token {
-                        token_type: TokenType::Integer,
-                        value: number,
+                    string = String::new();
+                    parsing_string = false;
+                }
+                '$' if characters.peek() == Some(&'{') => {
+                    characters.next();
+                    let source = lex_interpolation_source(&mut characters, line, column)?;
+                    let mut expression = tokenize(&source)?;
+                    expression.pop();
+                    // The expression was lexed on its own, so its positions are moved to where it
+                    // sits in the string, just after the `${`.
+                    for token in expression.iter_mut() {
+                        if token.line == 1 {
+                            token.column += column + 1;
+                        }
+                        token.line += line - 1;
+                    }
+
+                    if expression.is_empty() {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Expected an expression inside '${}'".to_string(),
+                            line,
+                            column,
+                        ));
+                    }
+
+                    interpolation.push(Token {
+                        kind: TokenKind::String,
+                        value: string,
                         line,
                         column,
                     });
+                    interpolation.append(&mut expression);
+                    string = String::new();
+                    column += source.chars().count() as u128 + 2;
+                }
+                '\n' => {
+                    string.push(character);
+                    line += 1;
+                    column = 1;
+                    continue;
                 }
-                number = String::new();
-                parsing_number = false;
+                '\\' => {
+                    let escaped = match characters.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some('$') => '$',
+                        Some('u') => lex_unicode_escape(&mut characters, line, column)?,
+                        Some(other) => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                format!("Unknown escape sequence '\\{}'", other),
+                                line,
+                                column,
+                            ))
+                        }
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "String not closed.".to_string(),
+                                line,
+                                column,
+                            ))
+                        }
+                    };
+                    string.push(escaped);
+                    column += 1;
+                }
+                _ => string.push(character),
             }
+            column += 1;
+            continue;
+        }
+
+        // A `.` ends a name, as in `module.member`, but not a number, as in `1.5`.
+        if !character.is_alphanumeric() && '_' != character && !name.is_empty() {
+            tokens.push(name_token(name, line, start_column)?);
+            name = String::new();
+        }
+
+        if !character.is_alphanumeric()
+            && '.' != character
+            && '_' != character
+            && !number.is_empty()
+        {
+            tokens.push(number_token(number, line, start_column)?);
+            number = String::new();
+            parsing_number = false;
+        }
+
+        if name.is_empty() && number.is_empty() {
+            start_column = column;
         }
 
         match character {
-            ' ' | '\t' => continue,
+            ' ' | '\t' | '\r' => (),
             '#' => parsing_comment = true,
-            '\n' | '\r' => {
+            '"' => {
+                parsing_string = true;
+                string_start = (line, column);
+            }
+            '\'' => {
+                let (value, width) = lex_char_literal(&mut characters, line, column)?;
+                tokens.push(Token {
+                    kind: TokenKind::Char,
+                    value: value.to_string(),
+                    line,
+                    column,
+                });
+                column += width;
+            }
+            '\n' => {
                 line += 1;
                 column = 1;
+                continue;
+            }
+            '=' => {
+                if let Some('=') = characters.peek() {
+                    characters.next();
+                    tokens.push(Token {
+                        kind: TokenKind::ComparisonOperator,
+                        value: String::from("=="),
+                        line,
+                        column,
+                    });
+                    column += 1;
+                } else if let Some('>') = characters.peek() {
+                    characters.next();
+                    tokens.push(Token {
+                        kind: TokenKind::Arrow,
+                        value: String::from("=>"),
+                        line,
+                        column,
+                    });
+                    column += 1;
+                } else {
+                    tokens.push(Token {
+                        kind: TokenKind::AssignmentOperator,
+                        value: String::from(character),
+                        line,
+                        column,
+                    })
+                }
+            }
+            '!' => {
+                if let Some('=') = characters.peek() {
+                    characters.next();
+                    tokens.push(Token {
+                        kind: TokenKind::ComparisonOperator,
+                        value: String::from("!="),
+                        line,
+                        column,
+                    });
+                    column += 1;
+                } else {
+                    tokens.push(Token {
+                        kind: TokenKind::LogicalOperator,
+                        value: String::from(character),
+                        line,
+                        column,
+                    })
+                }
             }
-            '=' => tokens.push(Token::Token {
-                token_type: TokenType::AssignmentOperator,
+            '&' | '|' if characters.peek() == Some(&character) => {
+                characters.next();
+                tokens.push(Token {
+                    kind: TokenKind::LogicalOperator,
+                    value: format!("{}{}", character, character),
+                    line,
+                    column,
+                });
+                column += 1;
+            }
+            '&' | '|' => tokens.push(Token {
+                kind: TokenKind::BinaryOperator,
                 value: String::from(character),
                 line,
                 column,
             }),
-            '+' | '-' | '*' | '/' | '%' | '^' => tokens.push(Token::Token {
-                token_type: TokenType::BinaryOperator,
+            '<' | '>' if characters.peek() == Some(&character) => {
+                characters.next();
+                tokens.push(Token {
+                    kind: TokenKind::BinaryOperator,
+                    value: character.to_string().repeat(2),
+                    line,
+                    column,
+                });
+                column += 1;
+            }
+            '<' | '>' => {
+                let mut value = String::from(character);
+                if let Some('=') = characters.peek() {
+                    value.push('=');
+                    characters.next();
+                }
+                let width = value.len() as u128;
+                tokens.push(Token {
+                    kind: TokenKind::ComparisonOperator,
+                    value,
+                    line,
+                    column,
+                });
+                column += width - 1;
+            }
+            '/' if characters.peek() == Some(&'/') => {
+                characters.next();
+                parsing_line_comment = true;
+            }
+            '/' if characters.peek() == Some(&'*') => {
+                block_comment_starts.push((line, column));
+                characters.next();
+                column += 1;
+            }
+            // Floor division is spelled `~/`, since `//` already starts a comment.
+            '~' if characters.peek() == Some(&'/') => {
+                characters.next();
+                let (kind, value) = match characters.next_if_eq(&'=') {
+                    Some(_) => (TokenKind::AssignmentOperator, "~/="),
+                    None => (TokenKind::BinaryOperator, "~/"),
+                };
+                tokens.push(Token {
+                    kind,
+                    value: value.to_string(),
+                    line,
+                    column,
+                });
+                column += value.len() as u128 - 1;
+            }
+            '~' => tokens.push(Token {
+                kind: TokenKind::BinaryOperator,
                 value: String::from(character),
                 line,
                 column,
             }),
-            '(' => tokens.push(Token::Token {
-                token_type: TokenType::OpenParenthesis,
+            '+' | '-' if characters.peek() == Some(&character) => {
+                characters.next();
+                tokens.push(Token {
+                    kind: TokenKind::IncrementOperator,
+                    value: character.to_string().repeat(2),
+                    line,
+                    column,
+                });
+                column += 1;
+            }
+            '+' | '-' | '*' | '/' | '%' if characters.peek() == Some(&'=') => {
+                characters.next();
+                tokens.push(Token {
+                    kind: TokenKind::AssignmentOperator,
+                    value: format!("{}=", character),
+                    line,
+                    column,
+                });
+                column += 1;
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => tokens.push(Token {
+                kind: TokenKind::BinaryOperator,
                 value: String::from(character),
                 line,
                 column,
             }),
-            ')' => tokens.push(Token::Token {
-                token_type: TokenType::CloseParenthesis,
+            '(' => tokens.push(Token {
+                kind: TokenKind::OpenParenthesis,
                 value: String::from(character),
                 line,
                 column,
             }),
-            '[' => tokens.push(Token::Token {
-                token_type: TokenType::OpenBracket,
+            ')' => tokens.push(Token {
+                kind: TokenKind::CloseParenthesis,
                 value: String::from(character),
                 line,
                 column,
             }),
-            ']' => tokens.push(Token::Token {
-                token_type: TokenType::CloseBracket,
+            ',' => tokens.push(Token {
+                kind: TokenKind::Comma,
                 value: String::from(character),
                 line,
                 column,
             }),
-            '{' => tokens.push(Token::Token {
-                token_type: TokenType::OpenBrace,
+            ';' => tokens.push(Token {
+                kind: TokenKind::Semicolon,
                 value: String::from(character),
                 line,
                 column,
             }),
-            '}' => tokens.push(Token::Token {
-                token_type: TokenType::OpenBrace,
+            '?' => tokens.push(Token {
+                kind: TokenKind::QuestionMark,
                 value: String::from(character),
                 line,
                 column,
             }),
-            'a'..='z' | 'A'..='Z' | '_' => {
-                name.push(character);
-                continue;
+            ':' => tokens.push(Token {
+                kind: TokenKind::Colon,
+                value: String::from(character),
+                line,
+                column,
+            }),
+            '[' => tokens.push(Token {
+                kind: TokenKind::OpenBracket,
+                value: String::from(character),
+                line,
+                column,
+            }),
+            ']' => tokens.push(Token {
+                kind: TokenKind::CloseBracket,
+                value: String::from(character),
+                line,
+                column,
+            }),
+            '{' => tokens.push(Token {
+                kind: TokenKind::OpenBrace,
+                value: String::from(character),
+                line,
+                column,
+            }),
+            '}' => tokens.push(Token {
+                kind: TokenKind::CloseBrace,
+                value: String::from(character),
+                line,
+                column,
+            }),
+            'e' | 'E' if parsing_number => {
+                number.push(character);
+                if let Some(&sign) = characters.peek() {
+                    if sign == '+' || sign == '-' {
+                        number.push(sign);
+                        characters.next();
+                    }
+                }
+
+                let mut has_digits = false;
+                while let Some(&digit) = characters.peek() {
+                    if !digit.is_ascii_digit() && digit != '_' {
+                        break;
+                    }
+                    has_digits |= digit.is_ascii_digit();
+                    number.push(digit);
+                    characters.next();
+                }
+                column = start_column + number.chars().count() as u128 - 1;
+
+                if !has_digits {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        format!("Expected exponent digits in number literal '{}'", number),
+                        line,
+                        column,
+                    ));
+                }
+            }
+            '_' if parsing_number => number.push(character),
+            'a'..='z' | 'A'..='Z' | '_' => name.push(character),
+            '0'..='9' if !name.is_empty() => name.push(character),
+            '0' if number.is_empty() && matches!(characters.peek(), Some('x' | 'o' | 'b')) => {
+                let prefix = characters.next().unwrap();
+                let radix = match prefix {
+                    'x' => 16,
+                    'o' => 8,
+                    _ => 2,
+                };
+
+                let mut digits = String::new();
+                while let Some(&digit) = characters.peek() {
+                    if !digit.is_alphanumeric() && digit != '_' {
+                        break;
+                    }
+                    digits.push(digit);
+                    characters.next();
+                }
+
+                let literal = format!("0{}{}", prefix, digits);
+                let digits = strip_separators(&digits, &literal, line, column)?;
+                if digits.is_empty() {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        format!("Expected digits after '{}'", literal),
+                        line,
+                        column,
+                    ));
+                }
+                if let Some(digit) = digits.chars().find(|digit| !digit.is_digit(radix)) {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        format!("Invalid digit '{}' in literal '{}'", digit, literal),
+                        line,
+                        column,
+                    ));
+                }
+                let value = i128::from_str_radix(&digits, radix).map_err(|_| {
+                    Error::new(
+                        ErrorType::SyntaxError,
+                        format!("Integer literal '{}' out of range", literal),
+                        line,
+                        column,
+                    )
+                })?;
+
+                tokens.push(Token {
+                    kind: TokenKind::Integer,
+                    value: value.to_string(),
+                    line,
+                    column,
+                });
+                column += literal.len() as u128 - 1;
             }
             '0'..='9' => {
                 number.push(character);
                 parsing_number = true;
-                continue;
             }
             '.' => {
+                // `1..10` is a range, not the start of the float `1.`.
+                if parsing_number && characters.peek() == Some(&'.') {
+                    tokens.push(number_token(number, line, start_column)?);
+                    number = String::new();
+                    parsing_number = false;
+                }
+
                 if parsing_number {
                     if number.contains('.') {
                         return Err(Error::new(
                             ErrorType::SyntaxError,
-                            format!("Number cannot contain more than one decimal."),
+                            "Number cannot contain more than one decimal.".to_string(),
+                            line,
+                            column,
+                        ));
+                    }
+                    if number.contains(['e', 'E']) {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Exponent cannot contain a decimal.".to_string(),
                             line,
                             column,
                         ));
                     }
 
                     number.push(character);
-                    continue;
+                } else if characters.peek() == Some(&'.') {
+                    characters.next();
+                    if characters.peek() == Some(&'.') {
+                        characters.next();
+                        tokens.push(Token {
+                            kind: TokenKind::Ellipsis,
+                            value: String::from("..."),
+                            line,
+                            column,
+                        });
+                        column += 2;
+                    } else {
+                        tokens.push(Token {
+                            kind: TokenKind::Range,
+                            value: String::from(".."),
+                            line,
+                            column,
+                        });
+                        column += 1;
+                    }
                 } else {
-                    tokens.push(Token::Token {
-                        token_type: TokenType::Dot,
+                    tokens.push(Token {
+                        kind: TokenKind::Dot,
                         value: String::from(character),
                         line,
                         column,
@@ -187,55 +859,281 @@ pub fn tokenize(source_code: &str) -> Result<Vec<Token>, Error> {
     }
 
     if !name.is_empty() {
-        if KEYWORDS.contains(&name.as_str()) {
-            tokens.push(Token::Token {
-                token_type: TokenType::Keyword,
-                value: name,
-                line,
-                column,
-            });
-        } else {
-            tokens.push(Token::Token {
-                token_type: TokenType::Identifier,
-                value: name,
-                line,
-                column,
-            });
-        }
+        tokens.push(name_token(name, line, start_column)?);
     }
 
     if !number.is_empty() {
-        if number.contains('.') {
-            tokens.push(Token::Token {
-                token_type: TokenType::Float,
-                value: number,
-                line,
-                column,
-            });
-        } else {
-            tokens.push(Token::Token {
-                token_type: TokenType::Integer,
-                value: number,
-                line,
-                column,
-            });
-        }
+        tokens.push(number_token(number, line, start_column)?);
     }
 
     if parsing_comment {
         return Err(Error::new(
             ErrorType::SyntaxError,
-            format!("Comment not closed."),
+            "Comment not closed.".to_string(),
             line,
             column,
         ));
     }
 
-    tokens.push(Token::Token {
-        token_type: TokenType::EOF,
+    if let Some((start_line, start_column)) = block_comment_starts.pop() {
+        return Err(Error::new(
+            ErrorType::SyntaxError,
+            format!(
+                "Block comment opened in line {} column {} is not closed.",
+                start_line, start_column
+            ),
+            line,
+            column,
+        ));
+    }
+
+    if parsing_string {
+        return Err(Error::new(
+            ErrorType::SyntaxError,
+            "String not closed.".to_string(),
+            string_start.0,
+            string_start.1,
+        ));
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::EOF,
         value: String::new(),
         line,
         column,
     });
     Ok(tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source_code: &str) -> Vec<(TokenKind, String)> {
+        lex(source_code)
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.kind, token.value))
+            .collect()
+    }
+
+    #[test]
+    fn true_and_false_are_keywords() {
+        assert_eq!(
+            tokens("true false"),
+            [
+                (TokenKind::Keyword, "true".to_string()),
+                (TokenKind::Keyword, "false".to_string()),
+            ]
+        );
+    }
+
+    fn error(source_code: &str) -> String {
+        lex(source_code).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn comments_produce_no_tokens() {
+        assert_eq!(tokens("x // y\nz"), tokens("x\nz"));
+        assert_eq!(tokens("x /* y\n z */ w"), tokens("x w"));
+        assert_eq!(tokens("x /* a /* b */ c */ w"), tokens("x w"));
+        assert_eq!(
+            tokens("\"// /* \""),
+            [(TokenKind::String, "// /* ".to_string())]
+        );
+    }
+
+    #[test]
+    fn unclosed_block_comments_are_an_error() {
+        assert!(error("x\n/* a\nb")
+            .starts_with("SyntaxError: Block comment opened in line 2 column 1 is not closed."));
+        assert!(error("/* a /* b */")
+            .starts_with("SyntaxError: Block comment opened in line 1 column 1 is not closed."));
+    }
+
+    #[test]
+    fn prefixed_integers_are_read_in_their_base() {
+        let integer = |value: &str| (TokenKind::Integer, value.to_string());
+        assert_eq!(
+            tokens("0xFF 0o17 0b1010 0xff"),
+            [integer("255"), integer("15"), integer("10"), integer("255")]
+        );
+        assert_eq!(
+            tokens(&format!("0x7{}", "F".repeat(31))),
+            [integer(&i128::MAX.to_string())]
+        );
+        assert_eq!(
+            tokens(&format!("0b1{}", "1".repeat(126))),
+            [integer(&i128::MAX.to_string())]
+        );
+    }
+
+    #[test]
+    fn malformed_prefixed_integers_are_an_error() {
+        assert!(error("0x").starts_with("SyntaxError: Expected digits after '0x'"));
+        assert!(error("0b2").starts_with("SyntaxError: Invalid digit '2' in literal '0b2'"));
+        assert!(error("0o8").starts_with("SyntaxError: Invalid digit '8' in literal '0o8'"));
+        assert!(error(&format!("0x8{}", "0".repeat(31)))
+            .starts_with("SyntaxError: Integer literal '0x8"));
+    }
+
+    #[test]
+    fn underscores_separate_digits() {
+        assert_eq!(tokens("1_000"), tokens("1000"));
+        assert_eq!(tokens("3.141_592"), tokens("3.141592"));
+        assert_eq!(tokens("0xFF_FF"), tokens("65535"));
+    }
+
+    #[test]
+    fn misplaced_underscores_are_an_error() {
+        assert!(
+            error("_100").starts_with("SyntaxError: Number literal '_100' cannot start with '_'")
+        );
+        for literal in ["100_", "1__0", "1_.5", "0x_FF"] {
+            assert!(
+                error(literal).starts_with(&format!(
+                    "SyntaxError: Misplaced '_' in number literal '{}'",
+                    literal
+                )),
+                "{}",
+                literal
+            );
+        }
+    }
+
+    #[test]
+    fn exponents_make_floats() {
+        for (literal, expected) in [
+            ("1e10", 1e10),
+            ("2.5e-3", 2.5e-3),
+            ("6.022E23", 6.022e23),
+            ("1e+5", 1e5),
+        ] {
+            let [(TokenKind::Float, value)] = &tokens(literal)[..] else {
+                panic!("expected a float for {}", literal);
+            };
+            let value = value.parse::<f64>().unwrap();
+            assert!(
+                (value - expected).abs() <= expected * f64::EPSILON,
+                "{}",
+                literal
+            );
+        }
+    }
+
+    #[test]
+    fn exponents_without_digits_are_an_error() {
+        for literal in ["1e", "2.5e-", "3E+"] {
+            assert!(error(literal).starts_with(&format!(
+                "SyntaxError: Expected exponent digits in number literal '{}'",
+                literal
+            )));
+        }
+    }
+
+    #[test]
+    fn escapes_in_strings_become_the_characters_they_stand_for() {
+        assert_eq!(
+            tokens(r#""say \"hi\"\n""#),
+            [(TokenKind::String, "say \"hi\"\n".to_string())]
+        );
+        assert_eq!(
+            tokens(r#""\t\\\r\0\u{1F600}""#),
+            [(TokenKind::String, "\t\\\r\0\u{1F600}".to_string())]
+        );
+    }
+
+    #[test]
+    fn unknown_escapes_are_an_error() {
+        assert!(error(r#""\q""#).starts_with("SyntaxError: Unknown escape sequence '\\q'"));
+        assert!(error(r#""\u{110000}""#)
+            .starts_with("SyntaxError: Invalid unicode escape, expected '\\u{XXXX}'"));
+    }
+
+    #[test]
+    fn character_literals_hold_one_character() {
+        assert_eq!(
+            tokens(r"'a' '\'' '\n' 'é'"),
+            [
+                (TokenKind::Char, "a".to_string()),
+                (TokenKind::Char, "'".to_string()),
+                (TokenKind::Char, "\n".to_string()),
+                (TokenKind::Char, "é".to_string()),
+            ]
+        );
+        assert!(error("'ab'").starts_with("SyntaxError"));
+        assert!(error("'a").starts_with("SyntaxError: Character literal not closed."));
+    }
+
+    #[test]
+    fn lex_gives_each_token_with_its_position() {
+        let token = |kind, value: &str, line, column| Token {
+            kind,
+            value: value.to_string(),
+            line,
+            column,
+        };
+        assert_eq!(
+            lex("let x = 3 + 4.5 * y\nx >= \"s\" && !z; f(x)[0xF]").unwrap(),
+            [
+                token(TokenKind::Keyword, "let", 1, 1),
+                token(TokenKind::Identifier, "x", 1, 5),
+                token(TokenKind::AssignmentOperator, "=", 1, 7),
+                token(TokenKind::Integer, "3", 1, 9),
+                token(TokenKind::BinaryOperator, "+", 1, 11),
+                token(TokenKind::Float, "4.5", 1, 13),
+                token(TokenKind::BinaryOperator, "*", 1, 17),
+                token(TokenKind::Identifier, "y", 1, 19),
+                token(TokenKind::Identifier, "x", 2, 1),
+                token(TokenKind::ComparisonOperator, ">=", 2, 3),
+                token(TokenKind::String, "s", 2, 6),
+                token(TokenKind::LogicalOperator, "&&", 2, 10),
+                token(TokenKind::LogicalOperator, "!", 2, 13),
+                token(TokenKind::Identifier, "z", 2, 14),
+                token(TokenKind::Semicolon, ";", 2, 15),
+                token(TokenKind::Identifier, "f", 2, 17),
+                token(TokenKind::OpenParenthesis, "(", 2, 18),
+                token(TokenKind::Identifier, "x", 2, 19),
+                token(TokenKind::CloseParenthesis, ")", 2, 20),
+                token(TokenKind::OpenBracket, "[", 2, 21),
+                token(TokenKind::Integer, "15", 2, 22),
+                token(TokenKind::CloseBracket, "]", 2, 25),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_ends_with_an_eof_token() {
+        let tokens = tokenize("x").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1].kind, TokenKind::EOF);
+        assert_eq!(lex("").unwrap(), []);
+    }
+
+    #[test]
+    fn numbers_that_do_not_fit_are_an_error() {
+        assert_eq!(
+            error("x = 170141183460469231731687303715884105728"),
+            "SyntaxError: Integer literal '170141183460469231731687303715884105728' out of range in line 1 column 5"
+        );
+        assert_eq!(
+            error("1.5e309"),
+            "SyntaxError: Float literal '1.5e309' out of range in line 1 column 1"
+        );
+        assert_eq!(
+            error("0x1_0000_0000_0000_0000_0000_0000_0000_0000"),
+            "SyntaxError: Integer literal '0x1_0000_0000_0000_0000_0000_0000_0000_0000' out of range in line 1 column 1"
+        );
+    }
+
+    #[test]
+    fn numbers_at_the_limits_are_kept() {
+        assert_eq!(
+            tokens("170141183460469231731687303715884105727 1.7e308"),
+            [
+                (TokenKind::Integer, i128::MAX.to_string()),
+                (TokenKind::Float, "1.7e308".to_string()),
+            ]
+        );
+    }
+}
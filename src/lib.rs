@@ -0,0 +1,9 @@
+pub mod checker;
+pub mod error;
+pub mod interpreter;
+pub mod json;
+pub mod lexer;
+pub mod native_functions;
+pub mod parser;
+pub mod resolver;
+pub mod unparser;
@@ -2,14 +2,19 @@ use std::{
     env,
     fs::File,
     io::{self, Read, Write},
+    path::Path,
+    process::ExitCode,
+    thread,
 };
 
-use error::Error;
-
-pub mod error;
-pub mod interpreter;
-pub mod lexer;
-pub mod parser;
+use jm::{
+    checker,
+    error::Error,
+    interpreter::{self, Interpreter, RuntimeValue},
+    lexer::{self, Token, TokenKind},
+    parser::{self, Node},
+    unparser,
+};
 
 fn read_line(prompt: &str) -> String {
     let mut buffer = String::new();
@@ -32,57 +37,175 @@ fn write_file(path: &str, content: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn run_program(path: &str) -> Result<(), Error> {
-    let source_code = read_file(path).unwrap();
-    let mut environment = interpreter::generate_environment();
+fn report(errors: Vec<Error>, source_code: &str) -> ExitCode {
+    for error in errors {
+        eprintln!("{}", error.render(source_code));
+    }
+    ExitCode::FAILURE
+}
 
-    let tokens = lexer::tokenize(&source_code)?;
-    let (ast, errors) = parser::generate_ast(tokens);
-    if errors.len() > 0 {
-        for error in errors {
-            println!("{}", error);
+fn run_program(path: &str) -> ExitCode {
+    let source_code = match read_file(path) {
+        Ok(source_code) => source_code,
+        Err(error) => {
+            eprintln!("Cannot read '{}': {}", path, error);
+            return ExitCode::FAILURE;
         }
-        return Ok(());
-    }
+    };
+    let environment = interpreter::generate_environment();
+
+    let ast = match parser::parse(&source_code) {
+        Ok(ast) => ast,
+        Err(errors) => return report(errors, &source_code),
+    };
 
     write_file("ast.json", &format!("{:?}", ast)).unwrap();
 
-    interpreter::evaluate(ast, &mut environment)?;
-    Ok(())
+    match interpreter::evaluate_script(ast, Path::new(path), &environment) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => report(vec![error], &source_code),
+    }
 }
 
-fn program_loop() -> Result<(), Error> {
-    let mut source_code = String::from(' ');
-    let mut environment = interpreter::generate_environment();
-
-    while !source_code.is_empty() {
-        source_code = read_line("> ");
-        let tokens = lexer::tokenize(&source_code)?;
-        let (ast, errors) = parser::generate_ast(tokens);
-        if errors.len() > 0 {
-            for error in errors {
-                println!("{}", error);
-            }
-            return Ok(());
+fn parse_file(path: &str) -> Result<Node, ExitCode> {
+    let source_code = match read_file(path) {
+        Ok(source_code) => source_code,
+        Err(error) => {
+            eprintln!("Cannot read '{}': {}", path, error);
+            return Err(ExitCode::FAILURE);
         }
+    };
 
-        let result = interpreter::evaluate(ast, &mut environment)?;
+    parser::parse(&source_code).map_err(|errors| report(errors, &source_code))
+}
 
-        println!("{:?}", result);
+// `--ast` prints the syntax tree of a script and `--format` prints its source as the unparser
+// writes it, without running it.
+fn print_script(flag: &str, path: &str) -> ExitCode {
+    match parse_file(path) {
+        Ok(ast) if flag == "--ast" => println!("{}", ast),
+        Ok(ast) => println!("{}", unparser::unparse(&ast)),
+        Err(exit_code) => return exit_code,
     }
+    ExitCode::SUCCESS
+}
 
-    Ok(())
+// `--check` reports the type errors the checker finds in a script without running it.
+fn check_script(path: &str) -> ExitCode {
+    let source_code = match read_file(path) {
+        Ok(source_code) => source_code,
+        Err(error) => {
+            eprintln!("Cannot read '{}': {}", path, error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match parser::parse(&source_code) {
+        Ok(ast) => match checker::check(&ast) {
+            errors if errors.is_empty() => ExitCode::SUCCESS,
+            errors => report(errors, &source_code),
+        },
+        Err(errors) => report(errors, &source_code),
+    }
 }
 
-fn main() -> Result<(), Error> {
-    let arguments = env::args().collect::<Vec<String>>()[1..].to_vec();
+// Keeps reading lines while brackets are left open, so that a block can be
+// typed over several lines.
+fn read_statement() -> String {
+    let mut source_code = read_line("> ");
+    while !source_code.is_empty() && has_unclosed_brackets(&source_code) {
+        let line = read_line("... ");
+        if line.is_empty() {
+            break;
+        }
+        source_code.push_str(&line);
+    }
+    source_code
+}
 
-    if arguments.len() > 0 {
-        let file_path = arguments[0].as_str();
-        run_program(file_path)?;
-    } else {
-        program_loop()?;
+fn has_unclosed_brackets(source_code: &str) -> bool {
+    let tokens = match lexer::tokenize(source_code) {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+
+    let mut depth = 0;
+    for Token { kind, .. } in tokens {
+        match kind {
+            TokenKind::OpenParenthesis | TokenKind::OpenBracket | TokenKind::OpenBrace => {
+                depth += 1
+            }
+            TokenKind::CloseParenthesis | TokenKind::CloseBracket | TokenKind::CloseBrace => {
+                depth -= 1
+            }
+            _ => (),
+        }
     }
+    depth > 0
+}
 
-    Ok(())
+fn program_loop() {
+    let mut interpreter = Interpreter::new();
+
+    loop {
+        let source_code = read_statement();
+        if source_code.is_empty() {
+            println!();
+            break;
+        }
+
+        match interpreter.eval_str(&source_code) {
+            Ok(RuntimeValue::Null) => (),
+            Ok(value) => println!("{}", value),
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}", error.render(&source_code));
+                }
+            }
+        }
+    }
+}
+
+// Parsing and evaluation recurse once per level of nesting. They run on a thread with a large
+// stack so that `interpreter::MAX_DEPTH` is reached well before the stack runs out.
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+fn main() -> ExitCode {
+    let arguments = env::args().collect::<Vec<String>>()[1..].to_vec();
+
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || match arguments.first().map(String::as_str) {
+            None | Some("--repl") => {
+                program_loop();
+                ExitCode::SUCCESS
+            }
+            Some(flag @ ("--ast" | "--format")) => match arguments.get(1) {
+                Some(file_path) => print_script(flag, file_path),
+                None => {
+                    eprintln!("Usage: jm {} path/to/script.jm", flag);
+                    ExitCode::FAILURE
+                }
+            },
+            Some("--check") => match arguments.get(1) {
+                Some(file_path) => check_script(file_path),
+                None => {
+                    eprintln!("Usage: jm --check path/to/script.jm");
+                    ExitCode::FAILURE
+                }
+            },
+            Some("--trace") => match arguments.get(1) {
+                Some(file_path) => {
+                    interpreter::set_trace(true);
+                    run_program(file_path)
+                }
+                None => {
+                    eprintln!("Usage: jm --trace path/to/script.jm");
+                    ExitCode::FAILURE
+                }
+            },
+            Some(file_path) => run_program(file_path),
+        })
+        .unwrap();
+    interpreter.join().unwrap_or(ExitCode::FAILURE)
 }
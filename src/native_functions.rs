@@ -0,0 +1,1182 @@
+use crate::{
+    error::{Error, ErrorType},
+    interpreter::{
+        call_function, compare_numbers, integer_overflow, is_equal, LazyIterable, RuntimeValue,
+    },
+};
+use std::{cmp::Ordering, io::Write, rc::Rc};
+
+type NativeFunction = fn(Vec<RuntimeValue>) -> Result<RuntimeValue, Error>;
+
+pub const NATIVE_FUNCTIONS: [(&str, NativeFunction); 33] = [
+    ("print", nf_print),
+    ("println", nf_println),
+    ("len", nf_len),
+    ("int", nf_int),
+    ("float", nf_float),
+    ("str", nf_str),
+    ("bool", nf_bool),
+    ("char_from", nf_char_from),
+    ("type", nf_type),
+    ("sqrt", nf_sqrt),
+    ("abs", nf_abs),
+    ("floor", nf_floor),
+    ("ceil", nf_ceil),
+    ("round", nf_round),
+    ("min", nf_min),
+    ("max", nf_max),
+    ("clamp", nf_clamp),
+    ("range", nf_range),
+    ("push", nf_push),
+    ("pop", nf_pop),
+    ("map", nf_map),
+    ("filter", nf_filter),
+    ("reduce", nf_reduce),
+    ("sort", nf_sort),
+    ("upper", nf_upper),
+    ("lower", nf_lower),
+    ("trim", nf_trim),
+    ("split", nf_split),
+    ("format", nf_format),
+    ("contains", nf_contains),
+    ("index_of", nf_index_of),
+    ("assert", nf_assert),
+    ("assert_eq", nf_assert_eq),
+];
+
+// Declared as constants in the global environment, so they can be shadowed in an inner scope but
+// not assigned to or declared again at the top level.
+pub const NATIVE_CONSTANTS: [(&str, RuntimeValue); 6] = [
+    ("PI", RuntimeValue::Float(std::f64::consts::PI)),
+    ("E", RuntimeValue::Float(std::f64::consts::E)),
+    ("INFINITY", RuntimeValue::Float(f64::INFINITY)),
+    ("NAN", RuntimeValue::Float(f64::NAN)),
+    ("MAX_INT", RuntimeValue::Integer(i128::MAX)),
+    ("MIN_INT", RuntimeValue::Integer(i128::MIN)),
+];
+
+fn expect_arguments(name: &str, arguments: &[RuntimeValue], count: usize) -> Result<(), Error> {
+    if arguments.len() == count {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Function '{}' expected {} arguments, found {}",
+                name,
+                count,
+                arguments.len()
+            ),
+            0,
+            0,
+        ))
+    }
+}
+
+fn float_to_integer(value: f64) -> Option<i128> {
+    if value.is_finite() && value.abs() < 2f64.powi(127) {
+        Some(value as i128)
+    } else {
+        None
+    }
+}
+
+fn expect_number(name: &str, value: &RuntimeValue) -> Result<f64, Error> {
+    match value {
+        RuntimeValue::Integer(i) => Ok(*i as f64),
+        RuntimeValue::Float(f) => Ok(*f),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Function '{}' expected a number, found '{}'", name, value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn join_arguments(arguments: &[RuntimeValue]) -> String {
+    arguments
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn nf_print(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    print!("{}", join_arguments(&arguments));
+    std::io::stdout().flush().unwrap();
+    Ok(RuntimeValue::Null)
+}
+
+fn nf_println(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    println!("{}", join_arguments(&arguments));
+    Ok(RuntimeValue::Null)
+}
+
+// Strings are measured in unicode scalar values, not bytes.
+fn nf_len(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("len", &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::String(s) => Ok(RuntimeValue::Integer(s.chars().count() as i128)),
+        RuntimeValue::Array(elements) => Ok(RuntimeValue::Integer(elements.len() as i128)),
+        RuntimeValue::Iterable(iterable) => i128::try_from(iterable.len())
+            .map(RuntimeValue::Integer)
+            .map_err(|_| integer_overflow()),
+        RuntimeValue::Dictionary(entries) => Ok(RuntimeValue::Integer(entries.len() as i128)),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("'{}' has no length", value),
+            0,
+            0,
+        )),
+    }
+}
+
+// Floats are truncated towards zero, so `int(3.9)` is 3 and `int(-3.9)` is -3.
+fn nf_int(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("int", &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::Integer(i) => Ok(RuntimeValue::Integer(*i)),
+        RuntimeValue::Char(c) => Ok(RuntimeValue::Integer(*c as i128)),
+        RuntimeValue::Float(f) if float_to_integer(f.trunc()).is_some() => {
+            Ok(RuntimeValue::Integer(f.trunc() as i128))
+        }
+        RuntimeValue::String(s) => {
+            s.trim()
+                .parse::<i128>()
+                .map(RuntimeValue::Integer)
+                .map_err(|_| {
+                    Error::new(
+                        ErrorType::TypeError,
+                        format!("Cannot convert '{}' to an integer", s),
+                        0,
+                        0,
+                    )
+                })
+        }
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot convert '{}' to an integer", value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_float(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("float", &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::Integer(i) => Ok(RuntimeValue::Float(*i as f64)),
+        RuntimeValue::Float(f) => Ok(RuntimeValue::Float(*f)),
+        RuntimeValue::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .map(RuntimeValue::Float)
+            .map_err(|_| {
+                Error::new(
+                    ErrorType::TypeError,
+                    format!("Cannot convert '{}' to a float", s),
+                    0,
+                    0,
+                )
+            }),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot convert '{}' to a float", value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_str(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("str", &arguments, 1)?;
+    Ok(RuntimeValue::String(arguments[0].to_string().into()))
+}
+
+// Zero, empty collections and null are false, and everything else is true.
+fn nf_bool(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("bool", &arguments, 1)?;
+    let b = match &arguments[0] {
+        RuntimeValue::Null => false,
+        RuntimeValue::Boolean(b) => *b,
+        RuntimeValue::Integer(i) => *i != 0,
+        RuntimeValue::Float(f) => *f != 0.0,
+        RuntimeValue::String(s) => !s.is_empty(),
+        RuntimeValue::Array(elements) => !elements.is_empty(),
+        RuntimeValue::Dictionary(entries) => !entries.is_empty(),
+        RuntimeValue::Iterable(iterable) => !iterable.is_empty(),
+        RuntimeValue::Char(_)
+        | RuntimeValue::Function { .. }
+        | RuntimeValue::NativeFunction { .. }
+        | RuntimeValue::Module { .. } => true,
+    };
+    Ok(RuntimeValue::Boolean(b))
+}
+
+fn nf_char_from(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("char_from", &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::Integer(i) => u32::try_from(*i)
+            .ok()
+            .and_then(char::from_u32)
+            .map(RuntimeValue::Char)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorType::Error,
+                    format!("'{}' is not a valid unicode code point", i),
+                    0,
+                    0,
+                )
+            }),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Cannot convert '{}' to a character", value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_type(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("type", &arguments, 1)?;
+    Ok(RuntimeValue::String(arguments[0].type_name().into()))
+}
+
+fn nf_sqrt(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("sqrt", &arguments, 1)?;
+    let value = expect_number("sqrt", &arguments[0])?;
+    if value < 0.0 {
+        return Err(Error::new(
+            ErrorType::Error,
+            format!(
+                "Cannot take the square root of negative number '{}'",
+                arguments[0]
+            ),
+            0,
+            0,
+        ));
+    }
+    Ok(RuntimeValue::Float(value.sqrt()))
+}
+
+fn nf_abs(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("abs", &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::Integer(i) => i
+            .checked_abs()
+            .map(RuntimeValue::Integer)
+            .ok_or_else(integer_overflow),
+        value => Ok(RuntimeValue::Float(expect_number("abs", value)?.abs())),
+    }
+}
+
+fn round_with(
+    name: &str,
+    arguments: Vec<RuntimeValue>,
+    round: fn(f64) -> f64,
+) -> Result<RuntimeValue, Error> {
+    expect_arguments(name, &arguments, 1)?;
+    match &arguments[0] {
+        RuntimeValue::Integer(i) => Ok(RuntimeValue::Integer(*i)),
+        value => float_to_integer(round(expect_number(name, value)?))
+            .map(RuntimeValue::Integer)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorType::OverflowError,
+                    format!("'{}' does not fit in an integer", value),
+                    0,
+                    0,
+                )
+            }),
+    }
+}
+
+fn nf_floor(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    round_with("floor", arguments, f64::floor)
+}
+
+fn nf_ceil(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    round_with("ceil", arguments, f64::ceil)
+}
+
+// Halfway cases round away from zero, so `round(2.5)` is 3 and `round(-2.5)` is -3.
+fn nf_round(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    round_with("round", arguments, f64::round)
+}
+
+// When an integer is picked over a float, or the other way around, the result becomes a float so
+// that `min(1, 2.5)` and `min(2.5, 1)` agree on the type.
+fn pick(chosen: &RuntimeValue, other: &RuntimeValue) -> RuntimeValue {
+    match (chosen, other) {
+        (RuntimeValue::Integer(i), RuntimeValue::Float(_)) => RuntimeValue::Float(*i as f64),
+        _ => chosen.clone(),
+    }
+}
+
+fn nf_min(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("min", &arguments, 2)?;
+    let (a, b) = (&arguments[0], &arguments[1]);
+    match compare_numbers(b, a)? {
+        Some(Ordering::Less) => Ok(pick(b, a)),
+        _ => Ok(pick(a, b)),
+    }
+}
+
+fn nf_max(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("max", &arguments, 2)?;
+    let (a, b) = (&arguments[0], &arguments[1]);
+    match compare_numbers(b, a)? {
+        Some(Ordering::Greater) => Ok(pick(b, a)),
+        _ => Ok(pick(a, b)),
+    }
+}
+
+fn nf_clamp(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("clamp", &arguments, 3)?;
+    let (value, low, high) = (&arguments[0], &arguments[1], &arguments[2]);
+    if compare_numbers(low, high)? == Some(Ordering::Greater) {
+        return Err(Error::new(
+            ErrorType::Error,
+            format!(
+                "Lower bound '{}' is greater than upper bound '{}'",
+                low, high
+            ),
+            0,
+            0,
+        ));
+    }
+    let result = if compare_numbers(value, low)? == Some(Ordering::Less) {
+        low
+    } else if compare_numbers(value, high)? == Some(Ordering::Greater) {
+        high
+    } else {
+        value
+    };
+    let is_float = arguments
+        .iter()
+        .any(|argument| matches!(argument, RuntimeValue::Float(_)));
+    match result {
+        RuntimeValue::Integer(i) if is_float => Ok(RuntimeValue::Float(*i as f64)),
+        _ => Ok(result.clone()),
+    }
+}
+
+fn expect_integer(name: &str, value: &RuntimeValue) -> Result<i128, Error> {
+    match value {
+        RuntimeValue::Integer(i) => Ok(*i),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Function '{}' expected an integer, found '{}'", name, value),
+            0,
+            0,
+        )),
+    }
+}
+
+// The range is half-open: `range(0, 5)` yields 0 to 4 and `range(5, 0, -1)` yields 5 down to 1.
+fn nf_range(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if arguments.len() != 3 {
+        expect_arguments("range", &arguments, 2)?;
+    }
+    let start = expect_integer("range", &arguments[0])?;
+    let end = expect_integer("range", &arguments[1])?;
+    let step = match arguments.get(2) {
+        Some(step) => expect_integer("range", step)?,
+        None => 1,
+    };
+    if step == 0 {
+        return Err(Error::new(
+            ErrorType::Error,
+            "Range step cannot be zero".to_string(),
+            0,
+            0,
+        ));
+    }
+
+    // The distance between the bounds always fits in a u128, even when it does not fit in an i128.
+    let distance = if (step > 0 && start < end) || (step < 0 && start > end) {
+        end.abs_diff(start)
+    } else {
+        0
+    };
+    let length = distance.div_ceil(step.unsigned_abs());
+
+    Ok(RuntimeValue::Iterable(LazyIterable::new(
+        length,
+        move || {
+            Box::new(
+                std::iter::successors(Some(start), move |i| i.checked_add(step))
+                    .take_while(move |&i| (step > 0 && i < end) || (step < 0 && i > end))
+                    .map(RuntimeValue::Integer),
+            )
+        },
+    )))
+}
+
+fn expect_array(name: &str, value: RuntimeValue) -> Result<Vec<RuntimeValue>, Error> {
+    match value {
+        RuntimeValue::Array(elements) => Ok(Rc::unwrap_or_clone(elements)),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Function '{}' expected an array, found '{}'", name, value),
+            0,
+            0,
+        )),
+    }
+}
+
+// Arrays are values, so push and pop leave their argument untouched and return a new array.
+fn nf_push(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("push", &arguments, 2)?;
+    let mut arguments = arguments.into_iter();
+    let mut elements = expect_array("push", arguments.next().unwrap())?;
+    elements.push(arguments.next().unwrap());
+    Ok(RuntimeValue::Array(elements.into()))
+}
+
+fn nf_pop(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("pop", &arguments, 1)?;
+    let mut elements = expect_array("pop", arguments.into_iter().next().unwrap())?;
+    if elements.pop().is_none() {
+        return Err(Error::new(
+            ErrorType::Error,
+            "Cannot pop from an empty array".to_string(),
+            0,
+            0,
+        ));
+    }
+    Ok(RuntimeValue::Array(elements.into()))
+}
+
+fn nf_map(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("map", &arguments, 2)?;
+    let mut arguments = arguments.into_iter();
+    let elements = expect_array("map", arguments.next().unwrap())?;
+    let function = arguments.next().unwrap();
+    let mapped = elements
+        .into_iter()
+        .map(|element| call_function(function.clone(), vec![element]))
+        .collect::<Result<Vec<RuntimeValue>, Error>>()?;
+    Ok(RuntimeValue::Array(mapped.into()))
+}
+
+fn nf_filter(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("filter", &arguments, 2)?;
+    let mut arguments = arguments.into_iter();
+    let elements = expect_array("filter", arguments.next().unwrap())?;
+    let function = arguments.next().unwrap();
+    let mut kept = Vec::new();
+    for element in elements {
+        match call_function(function.clone(), vec![element.clone()])? {
+            RuntimeValue::Boolean(true) => kept.push(element),
+            RuntimeValue::Boolean(false) => (),
+            value => {
+                return Err(Error::new(
+                    ErrorType::TypeError,
+                    format!(
+                        "Function 'filter' expected the callback to return a boolean, found '{}'",
+                        value
+                    ),
+                    0,
+                    0,
+                ))
+            }
+        }
+    }
+    Ok(RuntimeValue::Array(kept.into()))
+}
+
+// The callback is given the value so far and the next element.
+fn nf_reduce(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("reduce", &arguments, 3)?;
+    let mut arguments = arguments.into_iter();
+    let elements = expect_array("reduce", arguments.next().unwrap())?;
+    let function = arguments.next().unwrap();
+    elements
+        .into_iter()
+        .try_fold(arguments.next().unwrap(), |accumulator, element| {
+            call_function(function.clone(), vec![accumulator, element])
+        })
+}
+
+// Without a comparator, numbers are sorted by value and strings and characters by code point. A
+// comparator returns a negative integer when its first argument goes first, a positive one when it
+// goes second, and zero to keep the two in their original order.
+fn nf_sort(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if !(1..=2).contains(&arguments.len()) {
+        return Err(Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Function 'sort' expected 1 to 2 arguments, found {}",
+                arguments.len()
+            ),
+            0,
+            0,
+        ));
+    }
+    let mut arguments = arguments.into_iter();
+    let elements = expect_array("sort", arguments.next().unwrap())?;
+    let sorted = match arguments.next() {
+        Some(comparator) => merge_sort(elements, &mut |a, b| match call_function(
+            comparator.clone(),
+            vec![a.clone(), b.clone()],
+        )? {
+            RuntimeValue::Integer(i) => Ok(i.cmp(&0)),
+            value => Err(Error::new(
+                ErrorType::TypeError,
+                format!(
+                    "Function 'sort' expected the comparator to return an integer, found '{}'",
+                    value
+                ),
+                0,
+                0,
+            )),
+        })?,
+        None => merge_sort(elements, &mut |a, b| {
+            let ordering = match (a, b) {
+                (RuntimeValue::String(a), RuntimeValue::String(b)) => Some(a.cmp(b)),
+                _ => compare_numbers(a, b).ok().flatten(),
+            };
+            ordering.ok_or_else(|| {
+                Error::new(
+                    ErrorType::TypeError,
+                    format!("Function 'sort' cannot compare '{}' and '{}'", a, b),
+                    0,
+                    0,
+                )
+            })
+        })?,
+    };
+    Ok(RuntimeValue::Array(sorted.into()))
+}
+
+// A merge sort of our own rather than `sort_by`, since a comparator written in JM can fail part way
+// through and is not guaranteed to be consistent. Equal elements keep their order.
+fn merge_sort(
+    mut elements: Vec<RuntimeValue>,
+    compare: &mut impl FnMut(&RuntimeValue, &RuntimeValue) -> Result<Ordering, Error>,
+) -> Result<Vec<RuntimeValue>, Error> {
+    if elements.len() <= 1 {
+        return Ok(elements);
+    }
+    let right = elements.split_off(elements.len() / 2);
+    let mut left = merge_sort(elements, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::new();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if compare(l, r)? == Ordering::Greater {
+            merged.push(right.next().unwrap());
+        } else {
+            merged.push(left.next().unwrap());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+fn expect_string<'a>(name: &str, value: &'a RuntimeValue) -> Result<&'a str, Error> {
+    match value {
+        RuntimeValue::String(s) => Ok(s),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Function '{}' expected a string, found '{}'", name, value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_upper(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("upper", &arguments, 1)?;
+    Ok(RuntimeValue::String(
+        expect_string("upper", &arguments[0])?.to_uppercase().into(),
+    ))
+}
+
+fn nf_lower(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("lower", &arguments, 1)?;
+    Ok(RuntimeValue::String(
+        expect_string("lower", &arguments[0])?.to_lowercase().into(),
+    ))
+}
+
+fn nf_trim(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("trim", &arguments, 1)?;
+    Ok(RuntimeValue::String(
+        expect_string("trim", &arguments[0])?.trim().into(),
+    ))
+}
+
+// An empty separator splits the string into its characters.
+fn nf_split(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("split", &arguments, 2)?;
+    let s = expect_string("split", &arguments[0])?;
+    let separator = expect_string("split", &arguments[1])?;
+    let parts = if separator.is_empty() {
+        s.chars()
+            .map(|character| RuntimeValue::String(character.to_string().into()))
+            .collect::<Vec<RuntimeValue>>()
+    } else {
+        s.split(separator)
+            .map(|part| RuntimeValue::String(part.into()))
+            .collect()
+    };
+    Ok(RuntimeValue::Array(parts.into()))
+}
+
+// Each `{}` in the template is replaced by the next argument as it would be printed, and `{{` and `}}`
+// stand for literal braces. There must be exactly one argument per placeholder.
+fn nf_format(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if arguments.is_empty() {
+        expect_arguments("format", &arguments, 1)?;
+    }
+    let template = expect_string("format", &arguments[0])?;
+    let invalid = |message: &str| Error::new(ErrorType::Error, message.to_string(), 0, 0);
+
+    let mut result = String::new();
+    let mut values = arguments[1..].iter();
+    let mut placeholders = 0;
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        match (character, characters.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                characters.next();
+                result.push(character);
+            }
+            ('{', Some('}')) => {
+                characters.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    result.push_str(&value.to_string());
+                }
+            }
+            ('{', _) => return Err(invalid("Expected '}' after '{' in format string")),
+            ('}', _) => return Err(invalid("Unmatched '}' in format string")),
+            _ => result.push(character),
+        }
+    }
+
+    let count = arguments.len() - 1;
+    if placeholders != count {
+        return Err(invalid(&format!(
+            "Format string has {} placeholders but {} values were given",
+            placeholders, count
+        )));
+    }
+    Ok(RuntimeValue::String(result.into()))
+}
+
+// Strings are searched for a substring and arrays for an element. Positions in strings count
+// unicode scalar values, like indexing does.
+fn find(
+    name: &str,
+    collection: &RuntimeValue,
+    value: &RuntimeValue,
+) -> Result<Option<usize>, Error> {
+    match (collection, value) {
+        (RuntimeValue::Array(elements), value) => {
+            Ok(elements.iter().position(|element| is_equal(element, value)))
+        }
+        (RuntimeValue::String(s), RuntimeValue::String(part)) => Ok(s
+            .find(&**part)
+            .map(|byte_index| s[..byte_index].chars().count())),
+        (collection, value) => Err(Error::new(
+            ErrorType::TypeError,
+            format!(
+                "Function '{}' cannot search '{}' for '{}'",
+                name, collection, value
+            ),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_contains(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("contains", &arguments, 2)?;
+    if let (RuntimeValue::Dictionary(entries), RuntimeValue::String(key)) =
+        (&arguments[0], &arguments[1])
+    {
+        return Ok(RuntimeValue::Boolean(entries.contains_key(&**key)));
+    }
+    let position = find("contains", &arguments[0], &arguments[1])?;
+    Ok(RuntimeValue::Boolean(position.is_some()))
+}
+
+fn nf_index_of(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    expect_arguments("index_of", &arguments, 2)?;
+    let position = find("index_of", &arguments[0], &arguments[1])?;
+    Ok(RuntimeValue::Integer(
+        position.map_or(-1, |position| position as i128),
+    ))
+}
+
+// An optional last argument replaces the default message of a failed assertion.
+fn assertion_failed(message: Option<&RuntimeValue>, default: String) -> Error {
+    let message = match message {
+        Some(message) => format!("Assertion failed: {}", message),
+        None => format!("Assertion failed: {}", default),
+    };
+    Error::new(ErrorType::AssertionError, message, 0, 0)
+}
+
+fn nf_assert(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if arguments.len() != 2 {
+        expect_arguments("assert", &arguments, 1)?;
+    }
+    match &arguments[0] {
+        RuntimeValue::Boolean(true) => Ok(RuntimeValue::Null),
+        RuntimeValue::Boolean(false) => Err(assertion_failed(
+            arguments.get(1),
+            "condition is false".to_string(),
+        )),
+        value => Err(Error::new(
+            ErrorType::TypeError,
+            format!("Function 'assert' expected a boolean, found '{}'", value),
+            0,
+            0,
+        )),
+    }
+}
+
+fn nf_assert_eq(arguments: Vec<RuntimeValue>) -> Result<RuntimeValue, Error> {
+    if arguments.len() != 3 {
+        expect_arguments("assert_eq", &arguments, 2)?;
+    }
+    let (left, right) = (&arguments[0], &arguments[1]);
+    if is_equal(left, right) {
+        return Ok(RuntimeValue::Null);
+    }
+    Err(assertion_failed(
+        arguments.get(2),
+        format!(
+            "{} is not equal to {}",
+            left.to_nested_string(),
+            right.to_nested_string()
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::{assert_error, run};
+
+    #[test]
+    fn len_counts_elements_and_characters() {
+        assert_eq!(run("len(\"hello\")"), Ok("5".to_string()));
+        assert_eq!(run("len(\"héllo\")"), Ok("5".to_string()));
+        assert_eq!(run("len([1, 2, 3])"), Ok("3".to_string()));
+        assert_eq!(run("len({\"a\": 1})"), Ok("1".to_string()));
+        assert_eq!(run("len(range(0, 10))"), Ok("10".to_string()));
+        assert_error("len(5)", "TypeError: '5' has no length");
+    }
+
+    #[test]
+    fn int_float_and_str_convert_values() {
+        assert_eq!(run("int(\"42\")"), Ok("42".to_string()));
+        assert_eq!(run("int(\" -7 \")"), Ok("-7".to_string()));
+        assert_eq!(run("int(3.9)"), Ok("3".to_string()));
+        assert_eq!(run("int(-3.9)"), Ok("-3".to_string()));
+        assert_eq!(run("int('a')"), Ok("97".to_string()));
+        assert_eq!(run("float(\"2.5\")"), Ok("2.5".to_string()));
+        assert_eq!(run("float(5)"), Ok("5.0".to_string()));
+        assert_eq!(
+            run("str(1.5) + str([1, \"a\"]) + str(null)"),
+            Ok("1.5[1, \"a\"]null".to_string())
+        );
+    }
+
+    #[test]
+    fn conversions_that_cannot_succeed_are_errors() {
+        assert_error(
+            "int(\"abc\")",
+            "TypeError: Cannot convert 'abc' to an integer",
+        );
+        assert_error(
+            "int(\"2.5\")",
+            "TypeError: Cannot convert '2.5' to an integer",
+        );
+        assert_error(
+            "int(1e40)",
+            "TypeError: Cannot convert '1e40' to an integer",
+        );
+        assert_error("int(NAN)", "TypeError: Cannot convert 'NaN' to an integer");
+        assert_error(
+            "int(null)",
+            "TypeError: Cannot convert 'null' to an integer",
+        );
+        assert_error(
+            "float(\"abc\")",
+            "TypeError: Cannot convert 'abc' to a float",
+        );
+        assert_error("float([])", "TypeError: Cannot convert '[]' to a float");
+        assert_error("str()", "TypeError");
+    }
+
+    #[test]
+    fn type_names_each_kind_of_value() {
+        for (value, name) in [
+            ("1", "integer"),
+            ("1.5", "float"),
+            ("\"a\"", "string"),
+            ("'a'", "char"),
+            ("true", "boolean"),
+            ("null", "null"),
+            ("[1]", "array"),
+            ("range(0, 1)", "range"),
+            ("{\"a\": 1}", "dictionary"),
+            ("func () {}", "function"),
+            ("len", "function"),
+        ] {
+            assert_eq!(run(&format!("type({})", value)), Ok(name.to_string()));
+        }
+        let module = RuntimeValue::Module {
+            name: "m".to_string(),
+            members: Default::default(),
+        };
+        assert!(matches!(
+            nf_type(vec![module]),
+            Ok(RuntimeValue::String(name)) if &*name == "module"
+        ));
+    }
+
+    #[test]
+    fn math_functions_keep_or_choose_their_types() {
+        assert_eq!(run("sqrt(16)"), Ok("4.0".to_string()));
+        assert_eq!(run("sqrt(2.25)"), Ok("1.5".to_string()));
+        assert_eq!(run("abs(-3)"), Ok("3".to_string()));
+        assert_eq!(run("abs(-2.5)"), Ok("2.5".to_string()));
+        assert_eq!(run("floor(2.7)"), Ok("2".to_string()));
+        assert_eq!(run("floor(-2.2)"), Ok("-3".to_string()));
+        assert_eq!(run("ceil(2.2)"), Ok("3".to_string()));
+        assert_eq!(run("ceil(-2.7)"), Ok("-2".to_string()));
+        assert_eq!(run("round(2.5)"), Ok("3".to_string()));
+        assert_eq!(run("round(-2.5)"), Ok("-3".to_string()));
+        assert_eq!(run("round(2.4)"), Ok("2".to_string()));
+        assert_eq!(run("floor(7)"), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn math_functions_reject_values_they_cannot_handle() {
+        assert_error(
+            "sqrt(-4)",
+            "Error: Cannot take the square root of negative number '-4'",
+        );
+        assert_error("abs(MIN_INT)", "OverflowError: Integer overflow");
+        assert_error(
+            "round(1e40)",
+            "OverflowError: '1e40' does not fit in an integer",
+        );
+        assert_error("floor(\"a\")", "TypeError");
+    }
+
+    #[test]
+    fn min_max_and_clamp_promote_mixed_numbers() {
+        assert_eq!(run("min(1, 2)"), Ok("1".to_string()));
+        assert_eq!(run("max(1, 2)"), Ok("2".to_string()));
+        assert_eq!(run("min(1, 2.5)"), Ok("1.0".to_string()));
+        assert_eq!(run("min(2.5, 1)"), Ok("1.0".to_string()));
+        assert_eq!(run("max(3, 2.5)"), Ok("3.0".to_string()));
+        assert_eq!(run("clamp(5, 0, 10)"), Ok("5".to_string()));
+        assert_eq!(run("clamp(-1, 0, 10)"), Ok("0".to_string()));
+        assert_eq!(run("clamp(11, 0, 10)"), Ok("10".to_string()));
+        assert_eq!(run("clamp(11, 0, 10.5)"), Ok("10.5".to_string()));
+        assert_eq!(run("clamp(5, 0.5, 10)"), Ok("5.0".to_string()));
+        assert_eq!(run("clamp(5, 5, 5)"), Ok("5".to_string()));
+    }
+
+    #[test]
+    fn clamp_rejects_bounds_the_wrong_way_round() {
+        assert_error(
+            "clamp(5, 10, 0)",
+            "Error: Lower bound '10' is greater than upper bound '0'",
+        );
+        assert_error(
+            "clamp(5, 1.5, 1)",
+            "Error: Lower bound '1.5' is greater than upper bound '1'",
+        );
+    }
+
+    #[test]
+    fn ranges_are_half_open_and_can_count_down() {
+        assert_eq!(
+            run("let seen = []\nfor i in range(0, 5) { seen = push(seen, i) }\nseen"),
+            Ok("[0, 1, 2, 3, 4]".to_string())
+        );
+        assert_eq!(
+            run("let seen = []\nfor i in range(5, 0, -2) { seen = push(seen, i) }\nseen"),
+            Ok("[5, 3, 1]".to_string())
+        );
+        assert_eq!(run("len(range(5, 0))"), Ok("0".to_string()));
+        assert_eq!(
+            run("len(range(MIN_INT, MAX_INT, MAX_INT))"),
+            Ok("3".to_string())
+        );
+        assert_error("range(0, 5, 0)", "Error: Range step cannot be zero");
+        assert_error("range(0, 1.5)", "TypeError");
+    }
+
+    #[test]
+    fn push_and_pop_return_new_arrays() {
+        assert_eq!(
+            run("let stack = []\nfor i in range(0, 4) { stack = push(stack, i * i) }\nstack"),
+            Ok("[0, 1, 4, 9]".to_string())
+        );
+        assert_eq!(
+            run("let stack = [1, 2, 3]\nlet popped = pop(stack)\n[stack, popped]"),
+            Ok("[[1, 2, 3], [1, 2]]".to_string())
+        );
+        assert_error("pop([])", "Error: Cannot pop from an empty array");
+        assert_error("push(1, 2)", "TypeError");
+    }
+
+    #[test]
+    fn string_functions_return_new_strings() {
+        assert_eq!(run("upper(\"héllo\")"), Ok("HÉLLO".to_string()));
+        assert_eq!(run("lower(\"HeLLo\")"), Ok("hello".to_string()));
+        assert_eq!(run("trim(\"  a b \\n\")"), Ok("a b".to_string()));
+        assert_eq!(
+            run("split(\"a,b,c\", \",\")"),
+            Ok("[\"a\", \"b\", \"c\"]".to_string())
+        );
+        assert_eq!(run("len(split(\"a,b,c\", \",\"))"), Ok("3".to_string()));
+        assert_eq!(
+            run("split(\"a,,b\", \",\")"),
+            Ok("[\"a\", \"\", \"b\"]".to_string())
+        );
+        assert_eq!(
+            run("split(\"héj\", \"\")"),
+            Ok("[\"h\", \"é\", \"j\"]".to_string())
+        );
+        assert_error(
+            "upper(1)",
+            "TypeError: Function 'upper' expected a string, found '1'",
+        );
+    }
+
+    #[test]
+    fn contains_and_index_of_search_arrays_and_strings() {
+        assert_eq!(run("contains([1, 2, 3], 2)"), Ok("true".to_string()));
+        assert_eq!(run("contains([1, 2, 3], 2.0)"), Ok("true".to_string()));
+        assert_eq!(run("contains([1, 2, 3], 4)"), Ok("false".to_string()));
+        assert_eq!(run("contains(\"hello\", \"ell\")"), Ok("true".to_string()));
+        assert_eq!(run("contains(\"hello\", \"xyz\")"), Ok("false".to_string()));
+        assert_eq!(run("index_of([1, 2, 3], 3)"), Ok("2".to_string()));
+        assert_eq!(run("index_of([1, 2, 3], 4)"), Ok("-1".to_string()));
+        assert_eq!(run("index_of(\"héllo\", \"llo\")"), Ok("2".to_string()));
+        assert_eq!(run("index_of(\"hello\", \"xyz\")"), Ok("-1".to_string()));
+        assert_error(
+            "contains(5, 1)",
+            "TypeError: Function 'contains' cannot search '5' for '1'",
+        );
+    }
+
+    // A range of MAX_INT elements could never be held in memory, so these only pass if ranges make
+    // their elements one at a time.
+    #[test]
+    fn ranges_do_not_hold_their_elements() {
+        assert_eq!(run("len(range(0, MAX_INT))"), Ok(i128::MAX.to_string()));
+        assert_eq!(
+            run("let total = 0\nfor i in range(0, MAX_INT) {\n    if i == 100000 { break }\n    total += i\n}\ntotal"),
+            Ok("4999950000".to_string())
+        );
+        assert_eq!(
+            run("let r = range(MAX_INT - 2, MAX_INT)\nlet seen = []\nfor i in r { seen = push(seen, i) }\nfor i in r { seen = push(seen, i) }\nlen(seen)"),
+            Ok("4".to_string())
+        );
+    }
+
+    #[test]
+    fn passing_assertions_return_null() {
+        assert_eq!(run("assert(1 < 2)"), Ok("null".to_string()));
+        assert_eq!(
+            run("assert_eq([1, \"a\"], [1.0, \"a\"])"),
+            Ok("null".to_string())
+        );
+        assert_eq!(run("assert_eq(1, 1, \"message\")"), Ok("null".to_string()));
+    }
+
+    #[test]
+    fn failing_assertions_describe_what_failed() {
+        assert_error(
+            "assert(1 > 2)",
+            "AssertionError: Assertion failed: condition is false",
+        );
+        assert_error(
+            "assert(false, \"x must be positive\")",
+            "AssertionError: Assertion failed: x must be positive",
+        );
+        assert_error(
+            "assert_eq([1, \"a\"], [1, \"b\"])",
+            "AssertionError: Assertion failed: [1, \"a\"] is not equal to [1, \"b\"]",
+        );
+        assert_error(
+            "assert_eq(\"1\", 1)",
+            "AssertionError: Assertion failed: \"1\" is not equal to 1",
+        );
+        assert_error(
+            "assert(1)",
+            "TypeError: Function 'assert' expected a boolean, found '1'",
+        );
+    }
+
+    #[test]
+    fn format_fills_placeholders_in_order() {
+        assert_eq!(
+            run("format(\"{} + {} = {}\", 1, 2, 1 + 2)"),
+            Ok("1 + 2 = 3".to_string())
+        );
+        assert_eq!(
+            run("format(\"[{}] {}\", [1, \"a\"], null)"),
+            Ok("[[1, \"a\"]] null".to_string())
+        );
+        assert_eq!(
+            run("format(\"no placeholders\")"),
+            Ok("no placeholders".to_string())
+        );
+        assert_eq!(
+            run("format(\"{{{}}} {{}}\", \"braced\")"),
+            Ok("{braced} {}".to_string())
+        );
+    }
+
+    #[test]
+    fn format_needs_one_value_per_placeholder() {
+        assert_error(
+            "format(\"{} {}\", 1)",
+            "Error: Format string has 2 placeholders but 1 values were given",
+        );
+        assert_error(
+            "format(\"{}\", 1, 2)",
+            "Error: Format string has 1 placeholders but 2 values were given",
+        );
+        assert_error(
+            "format(\"{x}\", 1)",
+            "Error: Expected '}' after '{' in format string",
+        );
+        assert_error("format(\"a } b\")", "Error: Unmatched '}' in format string");
+    }
+
+    #[test]
+    fn map_filter_and_reduce_call_the_function_for_each_element() {
+        assert_eq!(
+            run("map([1, 2, 3], func(x) => x * 2)"),
+            Ok("[2, 4, 6]".to_string())
+        );
+        assert_eq!(
+            run("filter([1, 2, 3, 4], func(x) => x % 2 == 0)"),
+            Ok("[2, 4]".to_string())
+        );
+        assert_eq!(
+            run("reduce([1, 2, 3, 4], func(total, x) => total + x, 0)"),
+            Ok("10".to_string())
+        );
+        assert_eq!(run("map([], func(x) => x)"), Ok("[]".to_string()));
+        assert_eq!(
+            run("reduce([], func(total, x) => total + x, 5)"),
+            Ok("5".to_string())
+        );
+    }
+
+    #[test]
+    fn filter_needs_a_boolean_from_the_function() {
+        assert_error(
+            "filter([1, 2], func(x) => x)",
+            "TypeError: Function 'filter' expected the callback to return a boolean, found '1'",
+        );
+    }
+
+    #[test]
+    fn sort_orders_numbers_and_strings_ascending() {
+        assert_eq!(
+            run("sort([3, 1.5, -2, 10])"),
+            Ok("[-2, 1.5, 3, 10]".to_string())
+        );
+        assert_eq!(
+            run("sort([\"pear\", \"apple\", \"Banana\"])"),
+            Ok("[\"Banana\", \"apple\", \"pear\"]".to_string())
+        );
+        assert_eq!(run("let a = [2, 1]; sort(a); a"), Ok("[2, 1]".to_string()));
+        assert_error(
+            "sort([1, \"a\"])",
+            "TypeError: Function 'sort' cannot compare",
+        );
+    }
+
+    #[test]
+    fn sort_orders_by_a_comparator() {
+        assert_eq!(
+            run("sort([3, 1, 2], func(a, b) => b - a)"),
+            Ok("[3, 2, 1]".to_string())
+        );
+        assert_eq!(
+            run("sort([[1, \"a\"], [0, \"b\"], [1, \"c\"]], func(a, b) => a[0] - b[0])"),
+            Ok("[[0, \"b\"], [1, \"a\"], [1, \"c\"]]".to_string())
+        );
+        assert_error(
+            "sort([1, 2], func(a, b) => true)",
+            "TypeError: Function 'sort' expected the comparator to return an integer, found 'true'",
+        );
+    }
+
+    #[test]
+    fn bool_converts_each_type() {
+        let cases = [
+            ("null", "false"),
+            ("true", "true"),
+            ("false", "false"),
+            ("0", "false"),
+            ("-3", "true"),
+            ("0.0", "false"),
+            ("0.5", "true"),
+            ("\"\"", "false"),
+            ("\"a\"", "true"),
+            ("[]", "false"),
+            ("[0]", "true"),
+            ("{:}", "false"),
+            ("{\"a\": 1}", "true"),
+            ("range(0, 0)", "false"),
+            ("range(0, 1)", "true"),
+            ("'a'", "true"),
+            ("func() => 0", "true"),
+            ("len", "true"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                run(&format!("bool({})", value)),
+                Ok(expected.to_string()),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn constants_hold_well_known_values() {
+        assert_eq!(run("PI"), Ok(std::f64::consts::PI.to_string()));
+        assert_eq!(run("E"), Ok(std::f64::consts::E.to_string()));
+        assert_eq!(run("INFINITY"), Ok("inf".to_string()));
+        assert_eq!(run("-INFINITY < MIN_INT"), Ok("true".to_string()));
+        assert_eq!(run("NAN == NAN"), Ok("false".to_string()));
+        assert_eq!(run("type(NAN)"), Ok("float".to_string()));
+        assert_eq!(run("MAX_INT"), Ok(i128::MAX.to_string()));
+        assert_eq!(run("MIN_INT"), Ok(i128::MIN.to_string()));
+    }
+
+    #[test]
+    fn constants_can_only_be_shadowed() {
+        assert_eq!(
+            run("func f() { let PI = 3; return PI }\n[f(), PI > 3]"),
+            Ok("[3, true]".to_string())
+        );
+        assert_error("PI = 3", "NameError: Cannot assign to constant 'PI'");
+        assert_error(
+            "let PI = 3",
+            "NameError: Variable 'PI' already declared in this scope",
+        );
+    }
+}
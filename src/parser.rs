@@ -1,34 +1,309 @@
 use crate::{
     error::{Error, ErrorType},
-    lexer::{Token, TokenType},
+    lexer::{self, Token, TokenKind},
 };
 use core::iter::Peekable;
-use std::vec::IntoIter;
+use std::{
+    cell::{Cell, RefCell},
+    vec::IntoIter,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    Shl,
+    Shr,
+}
+
+impl std::fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+            BinaryOperator::FloorDiv => "~/",
+            BinaryOperator::Mod => "%",
+            BinaryOperator::Pow => "^",
+            BinaryOperator::Eq => "==",
+            BinaryOperator::Neq => "!=",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::Le => "<=",
+            BinaryOperator::Ge => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::Shl => "<<",
+            BinaryOperator::Shr => ">>",
+        };
+        f.write_str(symbol)
+    }
+}
+
+// A default is evaluated each time the function is called without a value for the parameter. A
+// rest parameter, written `...name`, is only ever the last one and has no default.
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub default: Option<Node>,
+    pub rest: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Literal(Node),
+    Wildcard,
+    // Both bounds are inclusive.
+    Range(i128, i128),
+    Alternatives(Vec<Pattern>),
+}
+
+#[derive(Debug, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expression(Node),
+}
 
 #[derive(Clone)]
 pub enum Node {
     StringLiteral(String),
+    CharLiteral(char),
+    InterpolatedString {
+        parts: Vec<StringPart>,
+    },
     FloatLiteral(f64),
     IntegerLiteral(i128),
-    Identifier(String),
+    BooleanLiteral(bool),
+    NullLiteral,
+    ArrayLiteral(Vec<Node>),
+    DictionaryLiteral(Vec<(String, Node)>),
+    // `depth` is how many scopes out from here the name is declared, and `slot` which of that scope's
+    // variables it is, once the resolver has found them.
+    Identifier {
+        name: String,
+        depth: Option<usize>,
+        slot: Option<usize>,
+        line: u128,
+        column: u128,
+    },
     BinaryExpression {
         left: Box<Node>,
-        operand: char,
+        operand: BinaryOperator,
         right: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    UnaryExpression {
+        operator: char,
+        operand: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    UpdateExpression {
+        target: Box<Node>,
+        operator: String,
+        prefix: bool,
+        line: u128,
+        column: u128,
+    },
+    IndexExpression {
+        collection: Box<Node>,
+        index: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    SliceExpression {
+        collection: Box<Node>,
+        start: Option<Box<Node>>,
+        end: Option<Box<Node>>,
+        line: u128,
+        column: u128,
+    },
+    TernaryExpression {
+        condition: Box<Node>,
+        then: Box<Node>,
+        otherwise: Box<Node>,
+        line: u128,
+        column: u128,
     },
     AssignmentExpression {
         name: Box<Node>,
         value: Box<Node>,
+        line: u128,
+        column: u128,
     },
+    // A declaration with a type only has a single name as its target.
     VariableDeclaration {
         name: Box<Node>,
+        type_name: Option<String>,
+        value: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    ConstantDeclaration {
+        name: Box<Node>,
+        type_name: Option<String>,
+        value: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    IfStatement {
+        condition: Box<Node>,
+        then_branch: Box<Node>,
+        else_branch: Option<Box<Node>>,
+        line: u128,
+        column: u128,
+    },
+    // The else branch of a loop runs when the loop ends without a `break`.
+    WhileLoop {
+        condition: Box<Node>,
+        body: Box<Node>,
+        else_branch: Option<Box<Node>>,
+        line: u128,
+        column: u128,
+    },
+    DoWhileLoop {
+        body: Box<Node>,
+        condition: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    ForLoop {
+        variable: Box<Node>,
+        iterable: Box<Node>,
+        body: Box<Node>,
+        else_branch: Option<Box<Node>>,
+        line: u128,
+        column: u128,
+    },
+    FunctionDeclaration {
+        name: String,
+        parameters: Vec<Parameter>,
+        body: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    FunctionExpression {
+        parameters: Vec<Parameter>,
+        body: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    CallExpression {
+        callee: Box<Node>,
+        arguments: Vec<Node>,
+        line: u128,
+        column: u128,
+    },
+    // Only found in the arguments of a call, after any positional ones.
+    NamedArgument {
+        name: String,
+        value: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    ReturnStatement {
+        value: Option<Box<Node>>,
+        line: u128,
+        column: u128,
+    },
+    Break {
+        line: u128,
+        column: u128,
+    },
+    Continue {
+        line: u128,
+        column: u128,
+    },
+    Match {
+        subject: Box<Node>,
+        arms: Vec<(Pattern, Node)>,
+        line: u128,
+        column: u128,
+    },
+    ThrowStatement {
         value: Box<Node>,
+        line: u128,
+        column: u128,
+    },
+    ImportStatement {
+        path: String,
+        alias: Option<String>,
+        line: u128,
+        column: u128,
+    },
+    MemberAccess {
+        object: Box<Node>,
+        member: String,
+        line: u128,
+        column: u128,
+    },
+    TryCatch {
+        body: Box<Node>,
+        error_name: String,
+        handler: Box<Node>,
+        line: u128,
+        column: u128,
     },
     Scope {
         body: Vec<Node>,
     },
 }
 
+impl Node {
+    // The position of the token a node was parsed from, or (0, 0) for nodes that cannot fail on
+    // their own, such as literals.
+    pub fn position(&self) -> (u128, u128) {
+        match self {
+            Node::Identifier { line, column, .. }
+            | Node::BinaryExpression { line, column, .. }
+            | Node::UnaryExpression { line, column, .. }
+            | Node::UpdateExpression { line, column, .. }
+            | Node::IndexExpression { line, column, .. }
+            | Node::SliceExpression { line, column, .. }
+            | Node::TernaryExpression { line, column, .. }
+            | Node::AssignmentExpression { line, column, .. }
+            | Node::VariableDeclaration { line, column, .. }
+            | Node::ConstantDeclaration { line, column, .. }
+            | Node::IfStatement { line, column, .. }
+            | Node::WhileLoop { line, column, .. }
+            | Node::DoWhileLoop { line, column, .. }
+            | Node::ForLoop { line, column, .. }
+            | Node::FunctionDeclaration { line, column, .. }
+            | Node::FunctionExpression { line, column, .. }
+            | Node::CallExpression { line, column, .. }
+            | Node::NamedArgument { line, column, .. }
+            | Node::ReturnStatement { line, column, .. }
+            | Node::Break { line, column }
+            | Node::Continue { line, column }
+            | Node::Match { line, column, .. }
+            | Node::ThrowStatement { line, column, .. }
+            | Node::ImportStatement { line, column, .. }
+            | Node::MemberAccess { line, column, .. }
+            | Node::TryCatch { line, column, .. } => (*line, *column),
+            _ => (0, 0),
+        }
+    }
+}
+
 impl std::fmt::Debug for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let value = match self {
@@ -37,6 +312,19 @@ impl std::fmt::Debug for Node {
                     \"kind\": \"string literal\",
                     \"value\": \"{}\"
                 }}", s),
+            Node::CharLiteral(c) => format!("
+                {{
+                    \"kind\": \"char literal\",
+                    \"value\": {:?}
+                }}", c.to_string()),
+            Node::InterpolatedString { parts } => format!("
+                {{
+                    \"kind\": \"interpolated string\",
+                    \"parts\": [{}]
+                }}", parts.iter().map(|part| match part {
+                    StringPart::Literal(s) => format!("\"{}\"", s),
+                    StringPart::Expression(node) => format!("{:?}", node),
+                }).collect::<Vec<String>>().join(", ")),
             Node::FloatLiteral(f) => format!("
                 {{
                     \"kind\": \"float literal\",
@@ -47,30 +335,229 @@ impl std::fmt::Debug for Node {
                     \"kind\": \"integer literal\",
                     \"value\": {}
                 }}", i),
-            Node::Identifier(i) => format!("
+            Node::BooleanLiteral(b) => format!("
+                {{
+                    \"kind\": \"boolean literal\",
+                    \"value\": {}
+                }}", b),
+            Node::NullLiteral => "
+                {
+                    \"kind\": \"null literal\"
+                }".to_string(),
+            Node::ArrayLiteral(elements) => format!("
+                {{
+                    \"kind\": \"array literal\",
+                    \"elements\": {:?}
+                }}", elements),
+            Node::DictionaryLiteral(entries) => format!("
+                {{
+                    \"kind\": \"dictionary literal\",
+                    \"entries\": [{}]
+                }}", entries.iter().map(|(key, value)| format!("
+                {{
+                    \"key\": \"{}\",
+                    \"value\": {:?}
+                }}", key, value)).collect::<Vec<String>>().join(", ")),
+            Node::Identifier { name, .. } => format!("
                 {{
                     \"kind\": \"identifier\",
                     \"value\": \"{}\"
-                }}", i),
-            Node::BinaryExpression { left, operand, right } => format!("
+                }}", name),
+            Node::BinaryExpression { left, operand, right, .. } => format!("
                 {{
                     \"kind\": \"binary expression\",
                     \"left\": {:?},
                     \"operand\": \"{}\",
                     \"right\": {:?}
                 }}", left, operand, right),
-            Node::AssignmentExpression { name, value } => format!("
+            Node::UnaryExpression { operator, operand, .. } => format!("
+                {{
+                    \"kind\": \"unary expression\",
+                    \"operator\": \"{}\",
+                    \"operand\": {:?}
+                }}", operator, operand),
+            Node::UpdateExpression { target, operator, prefix, .. } => format!("
+                {{
+                    \"kind\": \"update expression\",
+                    \"operator\": \"{}\",
+                    \"prefix\": {},
+                    \"target\": {:?}
+                }}", operator, prefix, target),
+            Node::IndexExpression { collection, index, .. } => format!("
+                {{
+                    \"kind\": \"index expression\",
+                    \"collection\": {:?},
+                    \"index\": {:?}
+                }}", collection, index),
+            Node::SliceExpression { collection, start, end, .. } => format!("
+                {{
+                    \"kind\": \"slice expression\",
+                    \"collection\": {:?},
+                    \"start\": {},
+                    \"end\": {}
+                }}", collection, match start {
+                    Some(start) => format!("{:?}", start),
+                    None => "null".to_string(),
+                }, match end {
+                    Some(end) => format!("{:?}", end),
+                    None => "null".to_string(),
+                }),
+            Node::TernaryExpression { condition, then, otherwise, .. } => format!("
+                {{
+                    \"kind\": \"ternary expression\",
+                    \"condition\": {:?},
+                    \"then\": {:?},
+                    \"otherwise\": {:?}
+                }}", condition, then, otherwise),
+            Node::AssignmentExpression { name, value, .. } => format!("
                 {{
                     \"kind\": \"assignment expression\",
                     \"name\": \"{:?}\",
                     \"value\": {:?}
                 }}", name, value),
-            Node::VariableDeclaration { name, value } => format!("
+            Node::VariableDeclaration { name, type_name, value, .. } => format!("
                 {{
                     \"kind\": \"variable declaration\",
                     \"name\": \"{:?}\",
+                    \"type\": {},
+                    \"value\": {:?}
+                }}", name, type_json(type_name), value),
+            Node::ConstantDeclaration { name, type_name, value, .. } => format!("
+                {{
+                    \"kind\": \"constant declaration\",
+                    \"name\": \"{:?}\",
+                    \"type\": {},
+                    \"value\": {:?}
+                }}", name, type_json(type_name), value),
+            Node::IfStatement { condition, then_branch, else_branch, .. } => format!("
+                {{
+                    \"kind\": \"if statement\",
+                    \"condition\": {:?},
+                    \"then branch\": {:?},
+                    \"else branch\": {}
+                }}", condition, then_branch, match else_branch {
+                    Some(branch) => format!("{:?}", branch),
+                    None => "null".to_string(),
+                }),
+            Node::WhileLoop { condition, body, else_branch, .. } => format!("
+                {{
+                    \"kind\": \"while loop\",
+                    \"condition\": {:?},
+                    \"body\": {:?},
+                    \"else branch\": {}
+                }}", condition, body, match else_branch {
+                    Some(branch) => format!("{:?}", branch),
+                    None => "null".to_string(),
+                }),
+            Node::DoWhileLoop { body, condition, .. } => format!("
+                {{
+                    \"kind\": \"do while loop\",
+                    \"body\": {:?},
+                    \"condition\": {:?}
+                }}", body, condition),
+            Node::ForLoop { variable, iterable, body, else_branch, .. } => format!("
+                {{
+                    \"kind\": \"for loop\",
+                    \"variable\": {:?},
+                    \"iterable\": {:?},
+                    \"body\": {:?},
+                    \"else branch\": {}
+                }}", variable, iterable, body, match else_branch {
+                    Some(branch) => format!("{:?}", branch),
+                    None => "null".to_string(),
+                }),
+            Node::FunctionDeclaration { name, parameters, body, .. } => format!("
+                {{
+                    \"kind\": \"function declaration\",
+                    \"name\": \"{}\",
+                    \"parameters\": {:?},
+                    \"defaults\": [{}],
+                    \"rest\": {},
+                    \"body\": {:?}
+                }}", name, parameters.iter().map(|parameter| &parameter.name).collect::<Vec<&String>>(),
+                parameters.iter().map(|parameter| match &parameter.default {
+                    Some(default) => format!("{:?}", default),
+                    None => "null".to_string(),
+                }).collect::<Vec<String>>().join(", "),
+                parameters.last().is_some_and(|parameter| parameter.rest), body),
+            Node::FunctionExpression { parameters, body, .. } => format!("
+                {{
+                    \"kind\": \"function expression\",
+                    \"parameters\": {:?},
+                    \"defaults\": [{}],
+                    \"rest\": {},
+                    \"body\": {:?}
+                }}", parameters.iter().map(|parameter| &parameter.name).collect::<Vec<&String>>(),
+                parameters.iter().map(|parameter| match &parameter.default {
+                    Some(default) => format!("{:?}", default),
+                    None => "null".to_string(),
+                }).collect::<Vec<String>>().join(", "),
+                parameters.last().is_some_and(|parameter| parameter.rest), body),
+            Node::CallExpression { callee, arguments, .. } => format!("
+                {{
+                    \"kind\": \"call expression\",
+                    \"callee\": {:?},
+                    \"arguments\": {:?}
+                }}", callee, arguments),
+            Node::NamedArgument { name, value, .. } => format!("
+                {{
+                    \"kind\": \"named argument\",
+                    \"name\": \"{}\",
                     \"value\": {:?}
                 }}", name, value),
+            Node::ReturnStatement { value, .. } => format!("
+                {{
+                    \"kind\": \"return statement\",
+                    \"value\": {}
+                }}", match value {
+                    Some(value) => format!("{:?}", value),
+                    None => "null".to_string(),
+                }),
+            Node::Break { .. } => "
+                {
+                    \"kind\": \"break statement\"
+                }".to_string(),
+            Node::Continue { .. } => "
+                {
+                    \"kind\": \"continue statement\"
+                }".to_string(),
+            Node::Match { subject, arms, .. } => format!("
+                {{
+                    \"kind\": \"match\",
+                    \"subject\": {:?},
+                    \"arms\": [{}]
+                }}", subject, arms.iter().map(|(pattern, body)| format!("
+                {{
+                    \"pattern\": {},
+                    \"body\": {:?}
+                }}", pattern_json(pattern), body)).collect::<Vec<String>>().join(", ")),
+            Node::ThrowStatement { value, .. } => format!("
+                {{
+                    \"kind\": \"throw statement\",
+                    \"value\": {:?}
+                }}", value),
+            Node::ImportStatement { path, alias, .. } => format!("
+                {{
+                    \"kind\": \"import statement\",
+                    \"path\": {:?},
+                    \"alias\": {}
+                }}", path, match alias {
+                    Some(alias) => format!("{:?}", alias),
+                    None => "null".to_string(),
+                }),
+            Node::MemberAccess { object, member, .. } => format!("
+                {{
+                    \"kind\": \"member access\",
+                    \"object\": {:?},
+                    \"member\": {:?}
+                }}", object, member),
+            Node::TryCatch { body, error_name, handler, .. } => format!("
+                {{
+                    \"kind\": \"try catch\",
+                    \"body\": {:?},
+                    \"error name\": \"{}\",
+                    \"handler\": {:?}
+                }}", body, error_name, handler),
             Node::Scope { body } => format!("
                 {{
                     \"kind\": \"scope\",
@@ -82,39 +569,455 @@ impl std::fmt::Debug for Node {
     }
 }
 
+// An indented tree with one node per line, for reading the result of the parser. Children are
+// labelled with the part they play when a node has more than one kind of child.
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        self.tree_lines(String::new(), 0, &mut lines);
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+impl Node {
+    fn tree_lines(&self, label: String, depth: usize, lines: &mut Vec<String>) {
+        fn child<'a>(label: &str, node: &'a Node) -> (String, Option<&'a Node>) {
+            (label.to_string(), Some(node))
+        }
+
+        fn declaration_children<'a>(
+            name: &'a Node,
+            type_name: &Option<String>,
+            value: &'a Node,
+        ) -> Vec<(String, Option<&'a Node>)> {
+            let mut children = vec![child("name: ", name)];
+            if let Some(type_name) = type_name {
+                children.push((format!("type: {}", type_name), None));
+            }
+            children.push(child("value: ", value));
+            children
+        }
+
+        let (header, children) = match self {
+            Node::StringLiteral(s) => (format!("StringLiteral {:?}", s), vec![]),
+            Node::CharLiteral(c) => (format!("CharLiteral {:?}", c), vec![]),
+            Node::InterpolatedString { parts } => (
+                "InterpolatedString".to_string(),
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        StringPart::Literal(s) => (format!("{:?}", s), None),
+                        StringPart::Expression(node) => child("", node),
+                    })
+                    .collect(),
+            ),
+            Node::FloatLiteral(f) => (format!("FloatLiteral {:?}", f), vec![]),
+            Node::IntegerLiteral(i) => (format!("IntegerLiteral {}", i), vec![]),
+            Node::BooleanLiteral(b) => (format!("BooleanLiteral {}", b), vec![]),
+            Node::NullLiteral => ("NullLiteral".to_string(), vec![]),
+            Node::ArrayLiteral(elements) => (
+                "ArrayLiteral".to_string(),
+                elements.iter().map(|element| child("", element)).collect(),
+            ),
+            Node::DictionaryLiteral(entries) => (
+                "DictionaryLiteral".to_string(),
+                entries
+                    .iter()
+                    .map(|(key, value)| (format!("{:?}: ", key), Some(value)))
+                    .collect(),
+            ),
+            Node::Identifier { name, .. } => (format!("Identifier {}", name), vec![]),
+            Node::BinaryExpression {
+                left,
+                operand,
+                right,
+                ..
+            } => (
+                format!("BinaryExpression {}", operand),
+                vec![child("", left), child("", right)],
+            ),
+            Node::UnaryExpression {
+                operator, operand, ..
+            } => (
+                format!("UnaryExpression {}", operator),
+                vec![child("", operand)],
+            ),
+            Node::UpdateExpression {
+                target,
+                operator,
+                prefix,
+                ..
+            } => (
+                format!(
+                    "UpdateExpression {} ({})",
+                    operator,
+                    if *prefix { "prefix" } else { "postfix" }
+                ),
+                vec![child("", target)],
+            ),
+            Node::IndexExpression {
+                collection, index, ..
+            } => (
+                "IndexExpression".to_string(),
+                vec![child("collection: ", collection), child("index: ", index)],
+            ),
+            Node::SliceExpression {
+                collection,
+                start,
+                end,
+                ..
+            } => {
+                let mut children = vec![child("collection: ", collection)];
+                if let Some(start) = start {
+                    children.push(child("start: ", start));
+                }
+                if let Some(end) = end {
+                    children.push(child("end: ", end));
+                }
+                ("SliceExpression".to_string(), children)
+            }
+            Node::TernaryExpression {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => (
+                "TernaryExpression".to_string(),
+                vec![
+                    child("condition: ", condition),
+                    child("then: ", then),
+                    child("otherwise: ", otherwise),
+                ],
+            ),
+            Node::AssignmentExpression { name, value, .. } => (
+                "AssignmentExpression".to_string(),
+                vec![child("name: ", name), child("value: ", value)],
+            ),
+            Node::VariableDeclaration { name, type_name, value, .. } => (
+                "VariableDeclaration".to_string(),
+                declaration_children(name, type_name, value),
+            ),
+            Node::ConstantDeclaration { name, type_name, value, .. } => (
+                "ConstantDeclaration".to_string(),
+                declaration_children(name, type_name, value),
+            ),
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut children = vec![
+                    child("condition: ", condition),
+                    child("then: ", then_branch),
+                ];
+                if let Some(else_branch) = else_branch {
+                    children.push(child("else: ", else_branch));
+                }
+                ("IfStatement".to_string(), children)
+            }
+            Node::WhileLoop {
+                condition,
+                body,
+                else_branch,
+                ..
+            } => {
+                let mut children = vec![child("condition: ", condition), child("body: ", body)];
+                if let Some(else_branch) = else_branch {
+                    children.push(child("else: ", else_branch));
+                }
+                ("WhileLoop".to_string(), children)
+            }
+            Node::DoWhileLoop {
+                body, condition, ..
+            } => (
+                "DoWhileLoop".to_string(),
+                vec![child("body: ", body), child("condition: ", condition)],
+            ),
+            Node::ForLoop {
+                variable,
+                iterable,
+                body,
+                else_branch,
+                ..
+            } => {
+                let mut children = vec![
+                    child("variable: ", variable),
+                    child("iterable: ", iterable),
+                    child("body: ", body),
+                ];
+                if let Some(else_branch) = else_branch {
+                    children.push(child("else: ", else_branch));
+                }
+                ("ForLoop".to_string(), children)
+            }
+            Node::FunctionDeclaration {
+                parameters, body, ..
+            }
+            | Node::FunctionExpression {
+                parameters, body, ..
+            } => {
+                let names = parameters
+                    .iter()
+                    .map(|parameter| match parameter.rest {
+                        true => format!("...{}", parameter.name),
+                        false => parameter.name.clone(),
+                    })
+                    .collect::<Vec<String>>();
+                let mut children = parameters
+                    .iter()
+                    .filter_map(|parameter| {
+                        let default = parameter.default.as_ref()?;
+                        Some((format!("default {}: ", parameter.name), Some(default)))
+                    })
+                    .collect::<Vec<_>>();
+                children.push(child("", body));
+                let header = match self {
+                    Node::FunctionDeclaration { name, .. } => format!("FunctionDeclaration {}", name),
+                    _ => "FunctionExpression ".to_string(),
+                };
+                (format!("{}({})", header, names.join(", ")), children)
+            }
+            Node::CallExpression {
+                callee, arguments, ..
+            } => {
+                let mut children = vec![child("callee: ", callee)];
+                for argument in arguments {
+                    children.push(child("argument: ", argument));
+                }
+                ("CallExpression".to_string(), children)
+            }
+            Node::NamedArgument { name, value, .. } => {
+                (format!("NamedArgument {}", name), vec![child("", value)])
+            }
+            Node::ReturnStatement { value, .. } => (
+                "ReturnStatement".to_string(),
+                value.iter().map(|value| child("", value)).collect(),
+            ),
+            Node::Break { .. } => ("Break".to_string(), vec![]),
+            Node::Continue { .. } => ("Continue".to_string(), vec![]),
+            Node::Match { subject, arms, .. } => {
+                let mut children = vec![child("subject: ", subject)];
+                fn pattern_children<'a>(pattern: &'a Pattern, children: &mut Vec<(String, Option<&'a Node>)>) {
+                    match pattern {
+                        Pattern::Literal(pattern) => children.push(child("pattern: ", pattern)),
+                        Pattern::Wildcard => children.push(("pattern: _".to_string(), None)),
+                        Pattern::Range(start, end) => children.push((format!("pattern: {}..{}", start, end), None)),
+                        Pattern::Alternatives(alternatives) => {
+                            for alternative in alternatives {
+                                pattern_children(alternative, children);
+                            }
+                        }
+                    }
+                }
+
+                for (pattern, result) in arms {
+                    match pattern {
+                        Pattern::Wildcard => children.push(child("default: ", result)),
+                        pattern => {
+                            pattern_children(pattern, &mut children);
+                            children.push(child("result: ", result));
+                        }
+                    }
+                }
+                ("Match".to_string(), children)
+            }
+            Node::ThrowStatement { value, .. } => {
+                ("ThrowStatement".to_string(), vec![child("", value)])
+            }
+            Node::ImportStatement { path, alias: None, .. } => (format!("ImportStatement {:?}", path), Vec::new()),
+            Node::ImportStatement { path, alias: Some(alias), .. } => {
+                (format!("ImportStatement {:?} as {}", path, alias), Vec::new())
+            }
+            Node::MemberAccess { object, member, .. } => {
+                (format!("MemberAccess .{}", member), vec![child("", object)])
+            }
+            Node::TryCatch {
+                body,
+                error_name,
+                handler,
+                ..
+            } => (
+                format!("TryCatch ({})", error_name),
+                vec![child("body: ", body), child("handler: ", handler)],
+            ),
+            Node::Scope { body } => (
+                "Scope".to_string(),
+                body.iter().map(|statement| child("", statement)).collect(),
+            ),
+        };
+
+        lines.push(format!("{}{}{}", "  ".repeat(depth), label, header));
+        for (label, node) in children {
+            match node {
+                Some(node) => node.tree_lines(label, depth + 1, lines),
+                None => lines.push(format!("{}{}", "  ".repeat(depth + 1), label)),
+            }
+        }
+    }
+}
+
+// Parses a whole program without running it. The result is a `Node::Scope` holding the top-level
+// statements. When the source does not parse, every syntax error found is returned.
+pub fn parse(source_code: &str) -> Result<Node, Vec<Error>> {
+    let tokens = lexer::tokenize(source_code).map_err(|error| vec![error])?;
+    let (ast, errors) = generate_ast(tokens);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ast)
+}
+
 pub fn generate_ast(tokens: Vec<Token>) -> (Node, Vec<Error>) {
+    TOKEN_LINES.set(tokens.iter().map(|token| token.line).collect());
+    REPORTED.take();
     let mut program = Vec::new();
     let mut tokens = tokens.into_iter().peekable();
     let mut errors = Vec::new();
 
-    loop {
-        if let Some(Token::Token { token_type, .. }) = tokens.peek().cloned() {
-            match token_type {
-                TokenType::EOF => break,
-                _ => (),
-            }
+    while let Some(Token { kind, .. }) = tokens.peek().cloned() {
+        if let TokenKind::EOF = kind {
+            break;
+        }
 
-            let result = parse(&mut tokens);
-            match result {
-                Ok(expr) => program.push(expr),
-                Err(err) => errors.push(err),
-            }
+        let result = parse_node(&mut tokens).and_then(|node| {
+            end_statement(&mut tokens)?;
+            Ok(node)
+        });
+        match result {
+            Ok(expr) => program.push(expr),
+            Err(err) => errors.push(err),
         }
     }
 
+    let reported = REPORTED.take();
+    if !reported.is_empty() {
+        errors.extend(reported);
+        errors.sort_by_key(Error::position);
+    }
     (Node::Scope { body: program }, errors)
 }
 
-fn parse(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    if let Some(Token::Token { token_type, .. }) = tokens.peek() {
-        match token_type {
-            TokenType::Keyword => parse_statement(tokens),
-            _ => parse_expression(tokens),
+// How deeply statements and expressions may be nested before parsing stops with a `SyntaxError`
+// instead of overflowing the native stack. Only the rules that call themselves count, such as
+// brackets, `^`, unary operators and the branches of a ternary. A chain such as `1 + 1 + 1` is read
+// in a loop, so it can be as long as it likes.
+pub const MAX_NESTING: usize = 1_000;
+
+// The names a declaration can be given as its type: everything `type` can return, and `any`.
+pub const TYPE_NAMES: [&str; 11] = [
+    "integer",
+    "float",
+    "string",
+    "char",
+    "boolean",
+    "array",
+    "range",
+    "dictionary",
+    "function",
+    "module",
+    "any",
+];
+
+thread_local! {
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+    // How many loops the code being parsed is inside, not counting loops outside the function it is in.
+    static LOOPS: Cell<usize> = const { Cell::new(0) };
+    // Errors in statements that still parsed, so parsing went on as if there was no error.
+    static REPORTED: RefCell<Vec<Error>> = const { RefCell::new(Vec::new()) };
+    // The line of each token of the program being parsed, as tokens do not say where they end.
+    static TOKEN_LINES: RefCell<Vec<u128>> = const { RefCell::new(Vec::new()) };
+}
+
+// The line of the token that was taken from `tokens` last.
+fn last_line(tokens: &Peekable<IntoIter<Token>>) -> Option<u128> {
+    TOKEN_LINES.with_borrow(|lines| {
+        let index = lines.len().checked_sub(tokens.len() + 1)?;
+        lines.get(index).copied()
+    })
+}
+
+// A statement ends at a newline or a `;`, or at the `}` or end of input after it. Anything else on
+// the same line is an error, so that `x = 1 y = 2` is not quietly read as two statements.
+fn end_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<(), Error> {
+    let last_line = last_line(tokens);
+    match tokens.peek() {
+        Some(Token { kind: TokenKind::Semicolon, .. }) => {
+            while tokens.next_if(|token| token.kind == TokenKind::Semicolon).is_some() {}
+            Ok(())
+        }
+        Some(Token { kind: TokenKind::CloseBrace | TokenKind::EOF, .. }) | None => Ok(()),
+        Some(Token { value, line, column, .. }) if Some(*line) == last_line => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a newline or ';' before '{}'", value),
+            *line,
+            *column,
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+struct NestingGuard;
+
+impl NestingGuard {
+    // The rest of the input is skipped, so that it is not reported as a cascade of unmatched brackets.
+    fn enter(tokens: &mut Peekable<IntoIter<Token>>) -> Result<NestingGuard, Error> {
+        let nesting = NESTING.get();
+        if nesting >= MAX_NESTING {
+            let (line, column) = peek_position(tokens);
+            while tokens
+                .next_if(|Token { kind, .. }| *kind != TokenKind::EOF)
+                .is_some()
+            {}
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                "Too many levels of nesting".to_string(),
+                line,
+                column,
+            ));
+        }
+        NESTING.set(nesting + 1);
+        Ok(NestingGuard)
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        NESTING.set(NESTING.get() - 1);
+    }
+}
+
+// Keeps the number of loops that was current outside a loop body or function, to restore it.
+struct LoopGuard(usize);
+
+impl LoopGuard {
+    fn enter_loop() -> LoopGuard {
+        LoopGuard(LOOPS.replace(LOOPS.get() + 1))
+    }
+
+    // A `break` in a function cannot leave a loop the function is called from.
+    fn enter_function() -> LoopGuard {
+        LoopGuard(LOOPS.replace(0))
+    }
+}
+
+impl Drop for LoopGuard {
+    fn drop(&mut self) {
+        LOOPS.set(self.0);
+    }
+}
+
+fn parse_node(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let _guard = NestingGuard::enter(tokens)?;
+    if let Some(Token { kind, .. }) = tokens.peek() {
+        match kind {
+            TokenKind::Keyword => parse_statement(tokens),
+            _ => parse_expression_statement(tokens),
         }
     } else {
         Err(Error::new(
             ErrorType::SyntaxError,
-            format!("Expected token"),
+            "Expected token".to_string(),
             0,
             0,
         ))
@@ -122,134 +1025,1549 @@ fn parse(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
 }
 
 fn parse_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    if let Some(Token::Token { value, .. }) = tokens.peek() {
+    if let Some(Token { value, .. }) = tokens.peek() {
         match value.as_str() {
-            "let" => parse_variable_declaration_expression(tokens),
-            _ => Err(Error::new(
-                ErrorType::NameError,
-                format!("Found unknown keyword '{}'", value),
-                0,
-                0,
-            )),
+            "let" | "const" => parse_variable_declaration_expression(tokens),
+            "if" => parse_if_statement(tokens),
+            "while" => parse_while_loop(tokens),
+            "do" => parse_do_while_loop(tokens),
+            "for" => parse_for_loop(tokens),
+            "func" => parse_function_declaration(tokens),
+            "return" => parse_return_statement(tokens),
+            "try" => parse_try_catch(tokens),
+            "throw" => parse_throw_statement(tokens),
+            "import" => parse_import_statement(tokens),
+            "break" | "continue" => {
+                let keyword = value.clone();
+                let (line, column) = peek_position(tokens);
+                tokens.next();
+                if LOOPS.get() == 0 {
+                    REPORTED.with_borrow_mut(|reported| reported.push(Error::new(
+                        ErrorType::SyntaxError,
+                        format!("'{}' outside of a loop", keyword),
+                        line,
+                        column,
+                    )));
+                }
+                if keyword == "break" {
+                    Ok(Node::Break { line, column })
+                } else {
+                    Ok(Node::Continue { line, column })
+                }
+            }
+            "true" | "false" | "null" | "match" => parse_expression(tokens),
+            _ => {
+                let value = value.clone();
+                let (line, column) = peek_position(tokens);
+                tokens.next();
+                Err(Error::new(
+                    ErrorType::SyntaxError,
+                    format!("Found unknown keyword '{}'", value),
+                    line,
+                    column,
+                ))
+            }
         }
     } else {
         Err(Error::new(
             ErrorType::SyntaxError,
-            format!("Expected token"),
+            "Expected token".to_string(),
             0,
             0,
         ))
     }
 }
 
-fn parse_variable_declaration_expression(
+fn peek_position(tokens: &mut Peekable<IntoIter<Token>>) -> (u128, u128) {
+    match tokens.peek() {
+        Some(Token { line, column, .. }) => (*line, *column),
+        None => (0, 0),
+    }
+}
+
+fn expect_token(
     tokens: &mut Peekable<IntoIter<Token>>,
-) -> Result<Node, Error> {
-    tokens.next();
+    expected_type: TokenKind,
+    expected: &str,
+) -> Result<(), Error> {
+    match tokens.next() {
+        Some(Token { kind, .. }) if kind == expected_type => Ok(()),
+        Some(Token {
+            value,
+            line,
+            column,
+            ..
+        }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a '{}' found '{}'", expected, value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a '{}'", expected),
+            0,
+            0,
+        )),
+    }
+}
 
-    let assignment = parse_assignment_expression(tokens)?;
-    match assignment {
-        Node::AssignmentExpression { name, value } => Ok(Node::VariableDeclaration { name, value }),
-        _ => Err(Error::new(
+fn expect_keyword(tokens: &mut Peekable<IntoIter<Token>>, keyword: &str) -> Result<(), Error> {
+    match tokens.next() {
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == keyword => Ok(()),
+        Some(Token {
+            value,
+            line,
+            column,
+            ..
+        }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected '{}' found '{}'", keyword, value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
             ErrorType::SyntaxError,
-            format!("Expected variable assignment"),
+            format!("Expected '{}'", keyword),
             0,
             0,
         )),
     }
 }
 
-fn parse_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    parse_assignment_expression(tokens)
+fn parse_block(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    expect_token(tokens, TokenKind::OpenBrace, "{")?;
+    parse_scope_body(tokens)
 }
 
-fn parse_assignment_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    let left = parse_additive_expression(tokens)?;
+fn parse_scope_body(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut body = Vec::new();
 
-    if let Some(Token::Token { token_type, .. }) = tokens.peek() {
-        match token_type {
-            TokenType::AssignmentOperator => {
+    while let Some(Token {
+        kind,
+        line,
+        column,
+        ..
+    }) = tokens.peek().cloned()
+    {
+        match kind {
+            TokenKind::CloseBrace => {
                 tokens.next();
-                let value = parse_additive_expression(tokens)?;
-
-                Ok(Node::AssignmentExpression {
-                    name: Box::new(left),
-                    value: Box::new(value),
-                })
+                return Ok(Node::Scope { body });
+            }
+            TokenKind::EOF => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    "Expected a '}'".to_string(),
+                    line,
+                    column,
+                ))
+            }
+            _ => {
+                body.push(parse_node(tokens)?);
+                end_statement(tokens)?;
             }
-            _ => Ok(left),
         }
-    } else {
-        Ok(left)
     }
+
+    Err(Error::new(
+        ErrorType::SyntaxError,
+        "Expected a '}'".to_string(),
+        0,
+        0,
+    ))
 }
 
-fn parse_additive_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    let mut left = parse_multiplicative_expression(tokens)?;
+fn parse_if_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let condition = parse_expression(tokens)?;
+    let then_branch = parse_block(tokens)?;
 
-    while let Some(Token::Token { value, .. }) = tokens.peek().cloned() {
-        if ["+", "-"].contains(&value.as_str()) {
+    let else_branch = match tokens.peek() {
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == "else" => {
             tokens.next();
-            let operand = value.chars().next().unwrap();
-            let right = parse_multiplicative_expression(tokens)?;
-            left = Node::BinaryExpression {
-                left: Box::new(left),
-                operand,
-                right: Box::new(right),
-            };
-        } else {
-            break;
+            match tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::Keyword,
+                    value,
+                    ..
+                }) if value == "if" => Some(Box::new(parse_if_statement(tokens)?)),
+                _ => Some(Box::new(parse_block(tokens)?)),
+            }
         }
-    }
+        _ => None,
+    };
 
-    Ok(left)
+    Ok(Node::IfStatement {
+        condition: Box::new(condition),
+        then_branch: Box::new(then_branch),
+        else_branch,
+        line,
+        column,
+    })
 }
 
-fn parse_multiplicative_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    let mut left = parse_primary_expression(tokens)?;
+fn parse_while_loop(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let condition = parse_expression(tokens)?;
+    let body = parse_loop_body(tokens)?;
+    let else_branch = parse_loop_else(tokens)?;
+
+    Ok(Node::WhileLoop {
+        condition: Box::new(condition),
+        body: Box::new(body),
+        else_branch,
+        line,
+        column,
+    })
+}
+
+fn parse_loop_body(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let _guard = LoopGuard::enter_loop();
+    parse_block(tokens)
+}
 
-    while let Some(Token::Token { value, .. }) = tokens.peek().cloned() {
-        if ["*", "/", "%", "^"].contains(&value.as_str()) {
+// The else branch is not part of the loop, so a `break` in it leaves the loop around it instead.
+fn parse_loop_else(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Option<Box<Node>>, Error> {
+    match tokens.peek() {
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == "else" => {
             tokens.next();
-            let operand = value.chars().next().unwrap();
-            let right = parse_primary_expression(tokens)?;
-            left = Node::BinaryExpression {
-                left: Box::new(left),
-                operand,
-                right: Box::new(right),
-            };
-        } else {
-            break;
+            Ok(Some(Box::new(parse_block(tokens)?)))
         }
+        _ => Ok(None),
     }
-
-    Ok(left)
 }
 
-fn parse_primary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
-    if let Some(Token::Token {
-        token_type,
+fn parse_do_while_loop(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let body = parse_loop_body(tokens)?;
+    match tokens.next() {
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == "while" => (),
+        Some(Token { value, line, column, .. }) => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                format!("Expected 'while' after the body of 'do' found '{}'", value),
+                line,
+                column,
+            ))
+        }
+        None => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                "Expected 'while' after the body of 'do'".to_string(),
+                0,
+                0,
+            ))
+        }
+    }
+    let condition = parse_expression(tokens)?;
+
+    Ok(Node::DoWhileLoop {
+        body: Box::new(body),
+        condition: Box::new(condition),
+        line,
+        column,
+    })
+}
+
+fn parse_for_loop(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let (variable_line, variable_column) = peek_position(tokens);
+    let variable = Node::Identifier {
+        name: parse_identifier(tokens, "a loop variable")?,
+        depth: None,
+        slot: None,
+        line: variable_line,
+        column: variable_column,
+    };
+    expect_keyword(tokens, "in")?;
+
+    let iterable = parse_expression(tokens)?;
+    let body = parse_loop_body(tokens)?;
+    let else_branch = parse_loop_else(tokens)?;
+
+    Ok(Node::ForLoop {
+        variable: Box::new(variable),
+        iterable: Box::new(iterable),
+        body: Box::new(body),
+        else_branch,
+        line,
+        column,
+    })
+}
+
+fn parse_identifier(tokens: &mut Peekable<IntoIter<Token>>, expected: &str) -> Result<String, Error> {
+    match tokens.next() {
+        Some(Token {
+            kind: TokenKind::Identifier,
+            value,
+            ..
+        }) => Ok(value),
+        Some(Token {
+            value,
+            line,
+            column,
+            ..
+        }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected {} found '{}'", expected, value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected {}", expected),
+            0,
+            0,
+        )),
+    }
+}
+
+fn parse_function_declaration(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    // A statement can start with a function expression, such as the one a function returns.
+    if next_token_is(tokens, TokenKind::OpenParenthesis) {
+        return parse_function_expression(tokens, line, column);
+    }
+
+    let name = parse_identifier(tokens, "a function name")?;
+    expect_token(tokens, TokenKind::OpenParenthesis, "(")?;
+    let _guard = LoopGuard::enter_function();
+
+    let parameters = parse_parameters(tokens)?;
+    let body = parse_block(tokens)?;
+
+    Ok(Node::FunctionDeclaration {
+        name,
+        parameters,
+        body: Box::new(body),
+        line,
+        column,
+    })
+}
+
+// Parses the parameter list of a function after its opening parenthesis, up to and including the
+// closing one.
+fn parse_parameters(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Vec<Parameter>, Error> {
+    let mut parameters = Vec::new();
+    if let Some(Token {
+        kind: TokenKind::CloseParenthesis,
+        ..
+    }) = tokens.peek()
+    {
+        tokens.next();
+    } else {
+        loop {
+            let rest = tokens
+                .next_if(|Token { kind, .. }| *kind == TokenKind::Ellipsis)
+                .is_some();
+            let name = parse_identifier(tokens, "a parameter name")?;
+            let default = match tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::AssignmentOperator,
+                    value,
+                    ..
+                }) if value == "=" && !rest => {
+                    tokens.next();
+                    Some(parse_ternary_expression(tokens)?)
+                }
+                _ => None,
+            };
+            parameters.push(Parameter { name, default, rest });
+
+            if rest {
+                match tokens.peek() {
+                    Some(Token { kind, line, column, .. }) if *kind != TokenKind::CloseParenthesis => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "A rest parameter must be the last parameter and cannot have a default".to_string(),
+                            *line,
+                            *column,
+                        ))
+                    }
+                    _ => {
+                        expect_token(tokens, TokenKind::CloseParenthesis, ")")?;
+                        break;
+                    }
+                }
+            }
+
+            match tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }) => {
+                    tokens.next();
+                }
+                _ => {
+                    expect_token(tokens, TokenKind::CloseParenthesis, ")")?;
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(parameters)
+}
+
+// `func(x) => x * 2` returns the value of a single expression, while `func(x) { ... }` takes a
+// block like a declaration. Neither gives the function a name in the environment.
+fn parse_function_expression(tokens: &mut Peekable<IntoIter<Token>>, line: u128, column: u128) -> Result<Node, Error> {
+    expect_token(tokens, TokenKind::OpenParenthesis, "(")?;
+    let _guard = LoopGuard::enter_function();
+    let parameters = parse_parameters(tokens)?;
+
+    let body = if next_token_is(tokens, TokenKind::Arrow) {
+        tokens.next();
+        parse_expression(tokens)?
+    } else {
+        parse_block(tokens)?
+    };
+
+    Ok(Node::FunctionExpression {
+        parameters,
+        body: Box::new(body),
+        line,
+        column,
+    })
+}
+
+fn parse_return_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    // A `return` without a value ends where a statement can end, so the line after it is not
+    // taken as its value.
+    let last_line = last_line(tokens);
+    let value = match tokens.peek() {
+        Some(Token {
+            kind: TokenKind::CloseBrace | TokenKind::EOF | TokenKind::Semicolon,
+            ..
+        })
+        | None => None,
+        Some(Token { line, .. }) if Some(*line) != last_line => None,
+        _ => Some(Box::new(parse_expression(tokens)?)),
+    };
+
+    Ok(Node::ReturnStatement {
+        value,
+        line,
+        column,
+    })
+}
+
+fn parse_throw_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let value = parse_expression(tokens)?;
+
+    Ok(Node::ThrowStatement {
+        value: Box::new(value),
+        line,
+        column,
+    })
+}
+
+fn parse_import_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    match tokens.next() {
+        Some(Token {
+            kind: TokenKind::String,
+            value: path,
+            ..
+        }) => {
+            // `as` is only special here, so it can still be used as a name elsewhere.
+            let alias = match tokens.peek() {
+                Some(Token {
+                    kind: TokenKind::Identifier,
+                    value,
+                    ..
+                }) if value == "as" => {
+                    tokens.next();
+                    Some(parse_identifier(tokens, "a module name")?)
+                }
+                _ => None,
+            };
+            Ok(Node::ImportStatement { path, alias, line, column })
+        }
+        Some(Token { value, line, column, .. }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a file path in quotes after 'import' found '{}'", value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
+            ErrorType::SyntaxError,
+            "Expected a file path in quotes after 'import'".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+fn parse_try_catch(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let body = parse_block(tokens)?;
+    expect_keyword(tokens, "catch")?;
+    expect_token(tokens, TokenKind::OpenParenthesis, "(")?;
+    let error_name = parse_identifier(tokens, "an error name")?;
+    expect_token(tokens, TokenKind::CloseParenthesis, ")")?;
+    let handler = parse_block(tokens)?;
+
+    Ok(Node::TryCatch {
+        body: Box::new(body),
+        error_name,
+        handler: Box::new(handler),
+        line,
+        column,
+    })
+}
+
+fn parse_variable_declaration_expression(
+    tokens: &mut Peekable<IntoIter<Token>>,
+) -> Result<Node, Error> {
+    let (line, column) = peek_position(tokens);
+    let constant = matches!(tokens.next(), Some(Token { value, .. }) if value == "const");
+    let (name_line, name_column) = peek_position(tokens);
+
+    let mut lookahead = tokens.clone();
+    if let (Some(Token { kind: TokenKind::Identifier, .. }), Some(Token { kind: TokenKind::Colon, .. })) = (lookahead.next(), lookahead.next()) {
+        return parse_typed_declaration(tokens, constant, line, column);
+    }
+
+    let mut lookahead = tokens.clone().skip(1);
+    if let Some(Token {
+        kind: TokenKind::AssignmentOperator,
+        value,
+        line,
+        column,
+    }) = lookahead.next()
+    {
+        if value != "=" {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                format!("Expected a '=' found '{}'", value),
+                line,
+                column,
+            ));
+        }
+    }
+
+    let assignment = parse_assignment_expression(tokens)?;
+    match assignment {
+        Node::AssignmentExpression { name, .. } if !is_declaration_target(&name) => {
+            Err(Error::new(
+                ErrorType::SyntaxError,
+                "Expected a variable name".to_string(),
+                name_line,
+                name_column,
+            ))
+        }
+        Node::AssignmentExpression { name, value, .. } if constant => {
+            Ok(Node::ConstantDeclaration { name, type_name: None, value, line, column })
+        }
+        Node::AssignmentExpression { name, value, .. } => {
+            Ok(Node::VariableDeclaration { name, type_name: None, value, line, column })
+        }
+        _ => Err(Error::new(
+            ErrorType::SyntaxError,
+            "Expected variable assignment".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+// `let name: type = value` only accepts values of the given type, now and whenever the variable is
+// assigned to later.
+fn parse_typed_declaration(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    constant: bool,
+    line: u128,
+    column: u128,
+) -> Result<Node, Error> {
+    let name = parse_primary_expression(tokens)?;
+    expect_token(tokens, TokenKind::Colon, ":")?;
+
+    let type_name = match tokens.next() {
+        Some(Token { kind: TokenKind::Identifier, value, .. }) if TYPE_NAMES.contains(&value.as_str()) => value,
+        Some(Token { value, line, column, .. }) => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                format!("Unknown type '{}', expected one of {}", value, TYPE_NAMES.join(", ")),
+                line,
+                column,
+            ))
+        }
+        None => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                "Expected a type".to_string(),
+                0,
+                0,
+            ))
+        }
+    };
+
+    match tokens.next() {
+        Some(Token { kind: TokenKind::AssignmentOperator, value, .. }) if value == "=" => (),
+        Some(Token { value, line, column, .. }) => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                format!("Expected a '=' found '{}'", value),
+                line,
+                column,
+            ))
+        }
+        None => {
+            return Err(Error::new(
+                ErrorType::SyntaxError,
+                "Expected a '='".to_string(),
+                0,
+                0,
+            ))
+        }
+    }
+    let value = parse_ternary_expression(tokens)?;
+
+    let (name, type_name, value) = (Box::new(name), Some(type_name), Box::new(value));
+    if constant {
+        Ok(Node::ConstantDeclaration { name, type_name, value, line, column })
+    } else {
+        Ok(Node::VariableDeclaration { name, type_name, value, line, column })
+    }
+}
+
+// `a, b = pair` assigns to several targets at once, the same as `[a, b] = pair`. It is only a
+// statement, since inside an argument list the comma already separates arguments.
+fn parse_expression_statement(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let first = parse_expression(tokens)?;
+    if !matches!(tokens.peek(), Some(Token { kind: TokenKind::Comma, .. })) {
+        return Ok(first);
+    }
+
+    let mut targets = vec![first];
+    while let Some(Token { kind: TokenKind::Comma, .. }) = tokens.peek() {
+        tokens.next();
+        targets.push(parse_ternary_expression(tokens)?);
+    }
+
+    match tokens.next() {
+        Some(Token { kind: TokenKind::AssignmentOperator, value, line, column }) if value == "=" => {
+            let value = parse_expression(tokens)?;
+            Ok(Node::AssignmentExpression {
+                name: Box::new(Node::ArrayLiteral(targets)),
+                value: Box::new(value),
+                line,
+                column,
+            })
+        }
+        Some(Token { value, line, column, .. }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a '=' found '{}'", value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
+            ErrorType::SyntaxError,
+            "Expected a '='".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+// A declaration names a single variable, or takes an array apart with `let [a, b] = pair`.
+fn is_declaration_target(node: &Node) -> bool {
+    match node {
+        Node::Identifier { .. } => true,
+        Node::ArrayLiteral(targets) => targets.iter().all(is_declaration_target),
+        _ => false,
+    }
+}
+
+fn parse_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let _guard = NestingGuard::enter(tokens)?;
+    parse_assignment_expression(tokens)
+}
+
+// Assignment is right-associative, so `a = b = 1` assigns 1 to `b` and then the result to `a`.
+fn parse_assignment_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let left = parse_ternary_expression(tokens)?;
+
+    if let Some(Token { kind, value: operator, line, column }) = tokens.peek().cloned() {
+        match kind {
+            TokenKind::AssignmentOperator => {
+                tokens.next();
+                let _guard = NestingGuard::enter(tokens)?;
+                let mut value = parse_assignment_expression(tokens)?;
+
+                // `x += y` becomes `x = x + y`, so it follows the rules of both operators.
+                let operand = match operator.as_str() {
+                    "+=" => Some(BinaryOperator::Add),
+                    "-=" => Some(BinaryOperator::Sub),
+                    "*=" => Some(BinaryOperator::Mul),
+                    "/=" => Some(BinaryOperator::Div),
+                    "~/=" => Some(BinaryOperator::FloorDiv),
+                    "%=" => Some(BinaryOperator::Mod),
+                    _ => None,
+                };
+                if let Some(operand) = operand {
+                    value = Node::BinaryExpression {
+                        left: Box::new(left.clone()),
+                        operand,
+                        right: Box::new(value),
+                        line,
+                        column,
+                    };
+                }
+
+                Ok(Node::AssignmentExpression {
+                    name: Box::new(left),
+                    value: Box::new(value),
+                    line,
+                    column,
+                })
+            }
+            _ => Ok(left),
+        }
+    } else {
+        Ok(left)
+    }
+}
+
+// Both branches may be ternaries themselves, so `a ? b : c ? d : e` groups as `a ? b : (c ? d : e)`.
+fn parse_ternary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let condition = parse_or_expression(tokens)?;
+
+    if !next_token_is(tokens, TokenKind::QuestionMark) {
+        return Ok(condition);
+    }
+    let (line, column) = peek_position(tokens);
+    tokens.next();
+
+    let _guard = NestingGuard::enter(tokens)?;
+    let then = parse_ternary_expression(tokens)?;
+    expect_token(tokens, TokenKind::Colon, ":")?;
+    let otherwise = parse_ternary_expression(tokens)?;
+
+    Ok(Node::TernaryExpression {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+        line,
+        column,
+    })
+}
+
+// Operators are told apart by their kind as well as their text, since a string literal such as "+"
+// holds the same text.
+fn parse_or_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_and_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::LogicalOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "||" => BinaryOperator::Or,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_and_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_and_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_equality_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::LogicalOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "&&" => BinaryOperator::And,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_equality_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_equality_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_comparison_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::ComparisonOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "==" => BinaryOperator::Eq,
+            "!=" => BinaryOperator::Neq,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_comparison_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_comparison_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_bitwise_or_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::ComparisonOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "<" => BinaryOperator::Lt,
+            ">" => BinaryOperator::Gt,
+            "<=" => BinaryOperator::Le,
+            ">=" => BinaryOperator::Ge,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_bitwise_or_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+// The bitwise operators bind tighter than comparisons, so `flags & mask == 0` compares the masked
+// value rather than masking a boolean.
+fn parse_bitwise_or_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_bitwise_and_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "|" => BinaryOperator::BitOr,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_bitwise_and_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_bitwise_and_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_shift_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "&" => BinaryOperator::BitAnd,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_shift_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_shift_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_additive_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "<<" => BinaryOperator::Shl,
+            ">>" => BinaryOperator::Shr,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_additive_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_additive_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_multiplicative_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "+" => BinaryOperator::Add,
+            "-" => BinaryOperator::Sub,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_multiplicative_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_multiplicative_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut left = parse_power_expression(tokens)?;
+
+    while let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        let operand = match value.as_str() {
+            "*" => BinaryOperator::Mul,
+            "/" => BinaryOperator::Div,
+            "~/" => BinaryOperator::FloorDiv,
+            "%" => BinaryOperator::Mod,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_power_expression(tokens)?;
+        left = Node::BinaryExpression {
+            left: Box::new(left),
+            operand,
+            right: Box::new(right),
+            line,
+            column,
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_power_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let left = parse_unary_expression(tokens)?;
+
+    if let Some(Token { kind: TokenKind::BinaryOperator, value, line, column }) = tokens.peek().cloned() {
+        if value == "^" {
+            tokens.next();
+            let _guard = NestingGuard::enter(tokens)?;
+            let right = parse_power_expression(tokens)?;
+            return Ok(Node::BinaryExpression {
+                left: Box::new(left),
+                operand: BinaryOperator::Pow,
+                right: Box::new(right),
+                line,
+                column,
+            });
+        }
+    }
+
+    Ok(left)
+}
+
+fn parse_unary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    if let Some(Token { kind, value, line, column }) = tokens.peek().cloned() {
+        // A literal such as '-' holds the same text as the operator, so only operator tokens count.
+        let literal = matches!(kind, TokenKind::String | TokenKind::Char);
+        if !literal && (value == "-" || value == "!" || value == "~") {
+            let operator = value.chars().next().unwrap();
+            tokens.next();
+            let _guard = NestingGuard::enter(tokens)?;
+            let operand = parse_unary_expression(tokens)?;
+            return Ok(Node::UnaryExpression {
+                operator,
+                operand: Box::new(operand),
+                line,
+                column,
+            });
+        }
+    }
+
+    if let Some(Token {
+        kind: TokenKind::IncrementOperator,
+        value,
+        line,
+        column,
+    }) = tokens.peek().cloned()
+    {
+        tokens.next();
+        let _guard = NestingGuard::enter(tokens)?;
+        let target = parse_unary_expression(tokens)?;
+        return Ok(Node::UpdateExpression {
+            target: Box::new(target),
+            operator: value,
+            prefix: true,
+            line,
+            column,
+        });
+    }
+
+    parse_postfix_expression(tokens)
+}
+
+fn parse_postfix_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut expression = parse_primary_expression(tokens)?;
+
+    // A bracket or `++` on the next line starts a new statement rather than continuing this one.
+    while let Some(Token { kind, value, line, column }) = tokens.peek().cloned() {
+        if last_line(tokens) != Some(line) {
+            break;
+        }
+        match kind {
+            TokenKind::OpenBracket => {
+                tokens.next();
+                expression = parse_index_or_slice(tokens, expression, line, column)?;
+            }
+            TokenKind::OpenParenthesis => {
+                tokens.next();
+                let arguments = parse_arguments(tokens)?;
+
+                expression = Node::CallExpression {
+                    callee: Box::new(expression),
+                    arguments,
+                    line,
+                    column,
+                };
+            }
+            TokenKind::Dot => {
+                tokens.next();
+                let member = parse_identifier(tokens, "a member name")?;
+                expression = Node::MemberAccess {
+                    object: Box::new(expression),
+                    member,
+                    line,
+                    column,
+                };
+            }
+            TokenKind::IncrementOperator => {
+                tokens.next();
+                expression = Node::UpdateExpression {
+                    target: Box::new(expression),
+                    operator: value,
+                    prefix: false,
+                    line,
+                    column,
+                };
+            }
+            _ => break,
+        }
+    }
+
+    Ok(expression)
+}
+
+fn next_token_is(tokens: &mut Peekable<IntoIter<Token>>, expected_type: TokenKind) -> bool {
+    matches!(tokens.peek(), Some(Token { kind, .. }) if *kind == expected_type)
+}
+
+// Either bound of a slice can be left out, so `s[:3]`, `s[2:]` and `s[:]` are all valid.
+fn parse_index_or_slice(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    collection: Node,
+    line: u128,
+    column: u128,
+) -> Result<Node, Error> {
+    let start = if next_token_is(tokens, TokenKind::Colon) {
+        None
+    } else {
+        Some(Box::new(parse_expression(tokens)?))
+    };
+
+    if !next_token_is(tokens, TokenKind::Colon) {
+        expect_token(tokens, TokenKind::CloseBracket, "]")?;
+        return Ok(Node::IndexExpression {
+            collection: Box::new(collection),
+            index: start.unwrap(),
+            line,
+            column,
+        });
+    }
+
+    tokens.next();
+    let end = if next_token_is(tokens, TokenKind::CloseBracket) {
+        None
+    } else {
+        Some(Box::new(parse_expression(tokens)?))
+    };
+    expect_token(tokens, TokenKind::CloseBracket, "]")?;
+
+    Ok(Node::SliceExpression {
+        collection: Box::new(collection),
+        start,
+        end,
+        line,
+        column,
+    })
+}
+
+fn parse_arguments(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Vec<Node>, Error> {
+    let mut arguments = Vec::new();
+
+    if let Some(Token {
+        kind: TokenKind::CloseParenthesis,
+        ..
+    }) = tokens.peek()
+    {
+        tokens.next();
+        return Ok(arguments);
+    }
+
+    // A named argument is an identifier followed by a colon, which cannot follow a complete
+    // positional argument. A misplaced positional argument is reported once the list is closed, so
+    // the rest of it is not reported as well.
+    let mut named = false;
+    let mut misplaced = None;
+    loop {
+        let (line, column) = peek_position(tokens);
+        let argument = parse_expression(tokens)?;
+        match (argument, tokens.peek()) {
+            (Node::Identifier { name, line, column, .. }, Some(Token { kind: TokenKind::Colon, .. })) => {
+                tokens.next();
+                named = true;
+                arguments.push(Node::NamedArgument {
+                    name,
+                    value: Box::new(parse_expression(tokens)?),
+                    line,
+                    column,
+                });
+            }
+            _ if named => {
+                misplaced.get_or_insert((line, column));
+            }
+            (argument, _) => arguments.push(argument),
+        }
+
+        match tokens.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                tokens.next();
+            }
+            _ => {
+                expect_token(tokens, TokenKind::CloseParenthesis, ")")?;
+                return match misplaced {
+                    Some((line, column)) => Err(Error::new(
+                        ErrorType::SyntaxError,
+                        "Positional arguments must come before named arguments".to_string(),
+                        line,
+                        column,
+                    )),
+                    None => Ok(arguments),
+                };
+            }
+        }
+    }
+}
+
+// A trailing comma before the closing bracket is allowed, so `[1, 2,]` is the same as `[1, 2]`.
+fn parse_array_literal(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut elements = Vec::new();
+
+    loop {
+        if let Some(Token {
+            kind: TokenKind::CloseBracket,
+            ..
+        }) = tokens.peek()
+        {
+            tokens.next();
+            return Ok(Node::ArrayLiteral(elements));
+        }
+
+        elements.push(parse_expression(tokens)?);
+
+        match tokens.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                tokens.next();
+            }
+            _ => {
+                expect_token(tokens, TokenKind::CloseBracket, "]")?;
+                return Ok(Node::ArrayLiteral(elements));
+            }
+        }
+    }
+}
+
+// A '{' starts a dictionary when it is followed by a string key and a ':', or by ':}' for an
+// empty dictionary. Anything else, including '{}', is a block.
+fn is_dictionary_literal(tokens: &Peekable<IntoIter<Token>>) -> bool {
+    let mut lookahead = tokens.clone();
+    match lookahead.next() {
+        Some(Token {
+            kind: TokenKind::Colon,
+            ..
+        }) => true,
+        Some(Token {
+            kind: TokenKind::String,
+            ..
+        }) => matches!(
+            lookahead.next(),
+            Some(Token {
+                kind: TokenKind::Colon,
+                ..
+            })
+        ),
+        _ => false,
+    }
+}
+
+fn parse_dictionary_literal(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut entries = Vec::new();
+
+    if let Some(Token {
+        kind: TokenKind::Colon,
+        ..
+    }) = tokens.peek()
+    {
+        tokens.next();
+        expect_token(tokens, TokenKind::CloseBrace, "}")?;
+        return Ok(Node::DictionaryLiteral(entries));
+    }
+
+    loop {
+        if let Some(Token {
+            kind: TokenKind::CloseBrace,
+            ..
+        }) = tokens.peek()
+        {
+            tokens.next();
+            return Ok(Node::DictionaryLiteral(entries));
+        }
+
+        let key = match tokens.next() {
+            Some(Token {
+                kind: TokenKind::String,
+                value,
+                ..
+            }) => value,
+            Some(Token {
+                value,
+                line,
+                column,
+                ..
+            }) => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    format!("Expected a string key found '{}'", value),
+                    line,
+                    column,
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    "Expected a string key".to_string(),
+                    0,
+                    0,
+                ))
+            }
+        };
+        expect_token(tokens, TokenKind::Colon, ":")?;
+        entries.push((key, parse_expression(tokens)?));
+
+        match tokens.peek() {
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            }) => {
+                tokens.next();
+            }
+            _ => {
+                expect_token(tokens, TokenKind::CloseBrace, "}")?;
+                return Ok(Node::DictionaryLiteral(entries));
+            }
+        }
+    }
+}
+
+fn type_json(type_name: &Option<String>) -> String {
+    match type_name {
+        Some(type_name) => format!("{:?}", type_name),
+        None => "null".to_string(),
+    }
+}
+
+fn pattern_json(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Literal(node) => format!("{:?}", node),
+        Pattern::Wildcard => "\"_\"".to_string(),
+        Pattern::Range(start, end) => format!("
+                    {{
+                        \"kind\": \"range pattern\",
+                        \"start\": {},
+                        \"end\": {}
+                    }}", start, end),
+        Pattern::Alternatives(alternatives) => format!("[{}]", alternatives.iter().map(pattern_json).collect::<Vec<String>>().join(", ")),
+    }
+}
+
+// `|` separates alternatives, so `1 | 2 | 3` matches any of the three values.
+fn parse_pattern(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Pattern, Error> {
+    let mut alternatives = vec![parse_range_pattern(tokens)?];
+    while matches!(tokens.peek(), Some(Token { kind: TokenKind::BinaryOperator, value, .. }) if value == "|") {
+        tokens.next();
+        alternatives.push(parse_range_pattern(tokens)?);
+    }
+
+    if alternatives.len() == 1 {
+        Ok(alternatives.pop().unwrap())
+    } else {
+        Ok(Pattern::Alternatives(alternatives))
+    }
+}
+
+fn parse_range_pattern(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Pattern, Error> {
+    let start = parse_literal_pattern(tokens)?;
+    let (line, column) = match tokens.peek() {
+        Some(Token { kind: TokenKind::Range, line, column, .. }) => (*line, *column),
+        _ => return Ok(start),
+    };
+    tokens.next();
+
+    match (start, parse_literal_pattern(tokens)?) {
+        (Pattern::Literal(Node::IntegerLiteral(start)), Pattern::Literal(Node::IntegerLiteral(end))) => Ok(Pattern::Range(start, end)),
+        _ => Err(Error::new(
+            ErrorType::SyntaxError,
+            "Both bounds of a range pattern must be integers".to_string(),
+            line,
+            column,
+        )),
+    }
+}
+
+fn parse_literal_pattern(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Pattern, Error> {
+    match tokens.next() {
+        Some(Token {
+            kind: TokenKind::Identifier,
+            value,
+            ..
+        }) if value == "_" => Ok(Pattern::Wildcard),
+        Some(Token {
+            kind: TokenKind::Integer,
+            value,
+            ..
+        }) => Ok(Pattern::Literal(Node::IntegerLiteral(value.parse::<i128>().unwrap()))),
+        Some(Token {
+            kind: TokenKind::Float,
+            value,
+            ..
+        }) => Ok(Pattern::Literal(Node::FloatLiteral(value.parse::<f64>().unwrap()))),
+        Some(Token {
+            kind: TokenKind::String,
+            value,
+            ..
+        }) => Ok(Pattern::Literal(Node::StringLiteral(value))),
+        Some(Token {
+            kind: TokenKind::Char,
+            value,
+            ..
+        }) => Ok(Pattern::Literal(Node::CharLiteral(value.chars().next().unwrap()))),
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == "true" || value == "false" => Ok(Pattern::Literal(Node::BooleanLiteral(value == "true"))),
+        Some(Token {
+            kind: TokenKind::Keyword,
+            value,
+            ..
+        }) if value == "null" => Ok(Pattern::Literal(Node::NullLiteral)),
+        Some(Token {
+            kind: TokenKind::BinaryOperator,
+            value,
+            line,
+            column,
+        }) if value == "-" => match parse_literal_pattern(tokens)? {
+            Pattern::Literal(Node::IntegerLiteral(i)) => Ok(Pattern::Literal(Node::IntegerLiteral(-i))),
+            Pattern::Literal(Node::FloatLiteral(f)) => Ok(Pattern::Literal(Node::FloatLiteral(-f))),
+            _ => Err(Error::new(
+                ErrorType::SyntaxError,
+                "Expected a number after '-' in a pattern".to_string(),
+                line,
+                column,
+            )),
+        },
+        Some(Token {
+            value,
+            line,
+            column,
+            ..
+        }) => Err(Error::new(
+            ErrorType::SyntaxError,
+            format!("Expected a pattern found '{}'", value),
+            line,
+            column,
+        )),
+        None => Err(Error::new(
+            ErrorType::SyntaxError,
+            "Expected a pattern".to_string(),
+            0,
+            0,
+        )),
+    }
+}
+
+// Arms may be separated by commas.
+fn parse_match(tokens: &mut Peekable<IntoIter<Token>>, line: u128, column: u128) -> Result<Node, Error> {
+    let subject = parse_expression(tokens)?;
+    expect_token(tokens, TokenKind::OpenBrace, "{")?;
+
+    let mut arms = Vec::new();
+    while !next_token_is(tokens, TokenKind::CloseBrace) {
+        let pattern = parse_pattern(tokens)?;
+        expect_token(tokens, TokenKind::Arrow, "=>")?;
+        arms.push((pattern, parse_expression(tokens)?));
+
+        if next_token_is(tokens, TokenKind::Comma) {
+            tokens.next();
+        }
+    }
+    expect_token(tokens, TokenKind::CloseBrace, "}")?;
+
+    Ok(Node::Match {
+        subject: Box::new(subject),
+        arms,
+        line,
+        column,
+    })
+}
+
+fn parse_interpolated_string(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    let mut parts = Vec::new();
+
+    loop {
+        match tokens.next() {
+            Some(Token {
+                kind: TokenKind::String,
+                value,
+                ..
+            }) => {
+                if !value.is_empty() {
+                    parts.push(StringPart::Literal(value));
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    "Malformed interpolated string".to_string(),
+                    0,
+                    0,
+                ))
+            }
+        }
+
+        if let Some(Token {
+            kind: TokenKind::InterpolatedStringEnd,
+            ..
+        }) = tokens.peek()
+        {
+            tokens.next();
+            return Ok(Node::InterpolatedString { parts });
+        }
+
+        parts.push(StringPart::Expression(parse_expression(tokens)?));
+    }
+}
+
+fn parse_primary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Node, Error> {
+    if let Some(Token {
+        kind,
         value,
         line,
         column,
     }) = tokens.next()
     {
-        match token_type {
-            TokenType::Integer => Ok(Node::IntegerLiteral(value.parse::<i128>().unwrap())),
-            TokenType::Float => Ok(Node::FloatLiteral(value.parse::<f64>().unwrap())),
-            TokenType::Identifier => Ok(Node::Identifier(value.to_string())),
-            TokenType::OpenParenthesis => {
+        match kind {
+            TokenKind::Integer => Ok(Node::IntegerLiteral(value.parse::<i128>().unwrap())),
+            TokenKind::Float => Ok(Node::FloatLiteral(value.parse::<f64>().unwrap())),
+            TokenKind::String => Ok(Node::StringLiteral(value)),
+            TokenKind::Char => Ok(Node::CharLiteral(value.chars().next().unwrap())),
+            TokenKind::InterpolatedStringStart => parse_interpolated_string(tokens),
+            TokenKind::Keyword if value == "true" => Ok(Node::BooleanLiteral(true)),
+            TokenKind::Keyword if value == "false" => Ok(Node::BooleanLiteral(false)),
+            TokenKind::Keyword if value == "null" => Ok(Node::NullLiteral),
+            TokenKind::Keyword if value == "match" => parse_match(tokens, line, column),
+            TokenKind::Keyword if value == "func" => parse_function_expression(tokens, line, column),
+            TokenKind::Identifier => Ok(Node::Identifier {
+                name: value.to_string(),
+                depth: None,
+                slot: None,
+                line,
+                column,
+            }),
+            TokenKind::OpenParenthesis => {
                 let node = parse_expression(tokens)?;
 
-                if let Some(Token::Token {
-                    token_type,
+                if let Some(Token {
+                    kind,
                     value,
                     line,
                     column,
                 }) = tokens.next()
                 {
-                    match token_type {
-                        TokenType::CloseParenthesis => Ok(node),
+                    match kind {
+                        TokenKind::CloseParenthesis => Ok(node),
                         _ => Err(Error::new(
                             ErrorType::SyntaxError,
                             format!("Expected a ')' found '{}'", value),
@@ -260,35 +2578,21 @@ fn parse_primary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<No
                 } else {
                     Err(Error::new(
                         ErrorType::SyntaxError,
-                        format!("Expected a ')'"),
+                        "Expected a ')'".to_string(),
                         0,
                         0,
                     ))
                 }
             }
-            TokenType::OpenBrace => {
-                let mut body = Vec::new();
-
-                loop {
-                    if let Some(Token::Token { token_type, .. }) = tokens.peek().cloned() {
-                        match token_type {
-                            TokenType::CloseBrace => {
-                                tokens.next();
-                                break;
-                            }
-                            _ => {
-                                let result = parse(tokens);
-                                match result {
-                                    Ok(expr) => body.push(expr),
-                                    Err(err) => return Err(err),
-                                }
-                            }
-                        }
-                    }
-                }
-
-                Ok(Node::Scope { body })
-            }
+            TokenKind::OpenBrace if is_dictionary_literal(tokens) => parse_dictionary_literal(tokens),
+            TokenKind::OpenBrace => parse_scope_body(tokens),
+            TokenKind::OpenBracket => parse_array_literal(tokens),
+            TokenKind::EOF => Err(Error::new(
+                ErrorType::SyntaxError,
+                "Unexpected end of input".to_string(),
+                line,
+                column,
+            )),
             _ => Err(Error::new(
                 ErrorType::SyntaxError,
                 format!("Unexpected token '{}'", value),
@@ -299,9 +2603,286 @@ fn parse_primary_expression(tokens: &mut Peekable<IntoIter<Token>>) -> Result<No
     } else {
         Err(Error::new(
             ErrorType::SyntaxError,
-            format!("Expected a token."),
+            "Expected a token.".to_string(),
             0,
             0,
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parsing recurses once per level of nesting, so the deep programs here are parsed on a thread
+    // with a stack as large as the one `jm` runs on. Errors are not `Send`, so their text comes back.
+    fn parse_deep(source_code: String) -> Result<(), Vec<String>> {
+        std::thread::Builder::new()
+            .stack_size(512 * 1024 * 1024)
+            .spawn(move || match parse(&source_code) {
+                Ok(_) => Ok(()),
+                Err(errors) => Err(errors.iter().map(Error::to_string).collect()),
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn assert_too_deep(source_code: String) {
+        let errors = parse_deep(source_code).unwrap_err();
+        assert!(errors[0].starts_with("SyntaxError: Too many levels of nesting"));
+    }
+
+    #[test]
+    fn deep_brackets_are_a_syntax_error() {
+        assert_too_deep(format!("{}1{}", "(".repeat(300_000), ")".repeat(300_000)));
+        assert_too_deep(format!("{}1{}", "[".repeat(300_000), "]".repeat(300_000)));
+    }
+
+    #[test]
+    fn deep_right_recursive_expressions_are_a_syntax_error() {
+        assert_too_deep(vec!["2"; 300_000].join(" ^ "));
+        assert_too_deep(format!("{}true", "!".repeat(300_000)));
+        assert_too_deep(format!("{}x", "-".repeat(300_000)));
+        assert_too_deep(format!("{}0", "true ? 1 : ".repeat(300_000)));
+        assert_too_deep(format!("{}1{}", "true ? ".repeat(300_000), " : 0".repeat(300_000)));
+    }
+
+    #[test]
+    fn deep_blocks_are_a_syntax_error() {
+        assert_too_deep(format!("{}{}", "if true { ".repeat(300_000), "}".repeat(300_000)));
+    }
+
+    #[test]
+    fn long_operator_chains_are_not_nesting() {
+        assert!(parse_deep(vec!["1"; MAX_NESTING + 1].join(" + ")).is_ok());
+        assert!(parse_deep(vec!["x"; MAX_NESTING + 1].join(" && ")).is_ok());
+        assert!(parse_deep(format!("f{}", "()".repeat(MAX_NESTING + 1))).is_ok());
+    }
+
+    fn statements(source_code: &str) -> Vec<Node> {
+        match parse(source_code) {
+            Ok(Node::Scope { body }) => body,
+            Ok(node) => panic!("expected a scope, got {:?}", node),
+            Err(errors) => panic!("{:?}", errors),
+        }
+    }
+
+    fn first_error(source_code: &str) -> String {
+        parse(source_code).unwrap_err()[0].to_string()
+    }
+
+    #[test]
+    fn statements_end_at_semicolons_and_newlines() {
+        assert_eq!(statements("x = 1; y = 2").len(), 2);
+        assert_eq!(statements("x = 1\ny = 2").len(), 2);
+        assert_eq!(statements("x = 1;;\n\ny = 2;").len(), 2);
+        assert_eq!(statements("x = 1 +\n    2").len(), 1);
+    }
+
+    #[test]
+    fn statements_run_together_are_an_error() {
+        assert_eq!(
+            first_error("x = 1 y = 2"),
+            "SyntaxError: Expected a newline or ';' before 'y' in line 1 column 7"
+        );
+    }
+
+    #[test]
+    fn bare_return_ends_with_its_statement() {
+        let body = |source_code: &str| match statements(source_code).remove(0) {
+            Node::FunctionDeclaration { body, .. } => match *body {
+                Node::Scope { body } => body,
+                node => vec![node],
+            },
+            node => panic!("expected a function, got {:?}", node),
+        };
+        for source_code in [
+            "func f() {\n    return\n    g()\n}",
+            "func f() { return; g() }",
+        ] {
+            let body = body(source_code);
+            assert!(matches!(body[0], Node::ReturnStatement { value: None, .. }));
+            assert!(matches!(body[1], Node::CallExpression { .. }));
+        }
+        assert!(matches!(
+            body("func f() { return }")[..],
+            [Node::ReturnStatement { value: None, .. }]
+        ));
+        assert!(matches!(
+            body("func f() { return 1 +\n 2 }")[..],
+            [Node::ReturnStatement { value: Some(_), .. }]
+        ));
+    }
+
+    #[test]
+    fn not_applies_to_a_parenthesized_comparison() {
+        let Node::UnaryExpression {
+            operator: '!',
+            operand,
+            ..
+        } = statements("!(a == b)").remove(0)
+        else {
+            panic!("expected a negation");
+        };
+        assert!(matches!(
+            *operand,
+            Node::BinaryExpression {
+                operand: BinaryOperator::Eq,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn code_after_a_line_comment_parses() {
+        assert_eq!(statements("x = 1 // set x\ny = 2").len(), 2);
+        assert_eq!(statements("// only a comment\nx = 1").len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_report_where_the_parser_stopped() {
+        assert_eq!(
+            first_error("print((1 + 2}"),
+            "SyntaxError: Expected a ')' found '}' in line 1 column 13"
+        );
+        assert_eq!(
+            first_error("let = 5"),
+            "SyntaxError: Unexpected token '=' in line 1 column 5"
+        );
+        assert_eq!(
+            first_error("let x = [1, 2\nlet y = 3"),
+            "SyntaxError: Expected a ']' found 'let' in line 2 column 1"
+        );
+        assert_eq!(
+            first_error("func f( {\n}"),
+            "SyntaxError: Expected a parameter name found '{' in line 1 column 9"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_render_with_a_caret() {
+        let source_code = "x = 1\nprint((1 + 2}";
+        assert_eq!(
+            parse(source_code).unwrap_err()[0].render(source_code),
+            "SyntaxError: Expected a ')' found '}' in line 2 column 13\n2 | print((1 + 2}\n  |             ^"
+        );
+    }
+
+    #[test]
+    fn break_and_continue_outside_a_loop_are_an_error() {
+        assert!(first_error("break").starts_with("SyntaxError: 'break' outside of a loop"));
+        assert!(first_error("while true { func f() { continue } }")
+            .starts_with("SyntaxError: 'continue' outside of a loop"));
+        assert_eq!(statements("while true { if true { break } }").len(), 1);
+    }
+
+    #[test]
+    fn trees_display_as_an_indented_outline() {
+        let program = parse(
+            "let x = 1 + 2 * 3\nfunc f(a, b = 2) {\n    return a ^ b\n}\nif x > 3 { println(f(x)) } else { -x }",
+        )
+        .unwrap();
+        assert_eq!(
+            program.to_string(),
+            "\
+Scope
+  VariableDeclaration
+    name: Identifier x
+    value: BinaryExpression +
+      IntegerLiteral 1
+      BinaryExpression *
+        IntegerLiteral 2
+        IntegerLiteral 3
+  FunctionDeclaration f(a, b)
+    default b: IntegerLiteral 2
+    Scope
+      ReturnStatement
+        BinaryExpression ^
+          Identifier a
+          Identifier b
+  IfStatement
+    condition: BinaryExpression >
+      Identifier x
+      IntegerLiteral 3
+    then: Scope
+      CallExpression
+        callee: Identifier println
+        argument: CallExpression
+          callee: Identifier f
+          argument: Identifier x
+    else: Scope
+      UnaryExpression -
+        Identifier x"
+        );
+    }
+
+
+    #[test]
+    fn parse_gives_the_program_as_a_scope_of_its_statements() {
+        let Ok(Node::Scope { body }) = parse("let x = 1 + 2 * 3\nprintln(x)") else {
+            panic!("expected a scope");
+        };
+        let [
+            Node::VariableDeclaration { name, value, .. },
+            Node::CallExpression { callee, arguments, .. },
+        ] = &body[..]
+        else {
+            panic!("expected a declaration and a call, got {:?}", body);
+        };
+        assert!(matches!(&**name, Node::Identifier { name, .. } if name == "x"));
+        let Node::BinaryExpression { left, operand: BinaryOperator::Add, right, .. } = &**value else {
+            panic!("expected an addition, got {:?}", value);
+        };
+        assert!(matches!(**left, Node::IntegerLiteral(1)));
+        assert!(matches!(**right, Node::BinaryExpression { operand: BinaryOperator::Mul, .. }));
+        assert!(matches!(&**callee, Node::Identifier { name, .. } if name == "println"));
+        assert!(matches!(&arguments[..], [Node::Identifier { name, .. }] if name == "x"));
+    }
+
+    #[test]
+    fn parse_gives_functions_and_loops_their_parts() {
+        let body = statements("func add(a, b = 1) { return a + b }\nfor i in range(3) { add(i) }");
+        let Node::FunctionDeclaration { name, parameters, body: function_body, .. } = &body[0]
+        else {
+            panic!("expected a function, got {:?}", body[0]);
+        };
+        assert_eq!(name, "add");
+        let names: Vec<&str> = parameters.iter().map(|parameter| parameter.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(parameters[0].default.is_none());
+        assert!(matches!(parameters[1].default, Some(Node::IntegerLiteral(1))));
+        assert!(matches!(
+            &**function_body,
+            Node::Scope { body } if matches!(body[..], [Node::ReturnStatement { value: Some(_), .. }])
+        ));
+
+        let Node::ForLoop { variable, iterable, body: loop_body, else_branch: None, .. } = &body[1]
+        else {
+            panic!("expected a for loop, got {:?}", body[1]);
+        };
+        assert!(matches!(&**variable, Node::Identifier { name, .. } if name == "i"));
+        assert!(matches!(**iterable, Node::CallExpression { .. }));
+        assert!(matches!(&**loop_body, Node::Scope { body } if body.len() == 1));
+    }
+
+    #[test]
+    fn parse_gives_every_syntax_error() {
+        let errors = parse("let = 1\nlet y = )").unwrap_err();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|error| error.to_string().starts_with("SyntaxError")));
+    }
+
+
+    #[test]
+    fn strings_holding_an_operator_are_not_operators() {
+        assert_eq!(
+            first_error("1 \"+\" 2"),
+            "SyntaxError: Expected a newline or ';' before '+' in line 1 column 3"
+        );
+        for operator in ["||", "&&", "==", "<", "|", "&", "<<", "-", "%", "^"] {
+            assert!(first_error(&format!("x \"{}\" 1", operator)).starts_with("SyntaxError: Expected a newline or ';'"));
+        }
+        assert!(first_error("println(3 \"*\" 4)").starts_with("SyntaxError: Expected a ')'"));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::parser::{Node, Parameter, StringPart};
+use std::collections::HashMap;
+
+// Works out how many scopes out from each identifier its name is declared, and which of that
+// scope's variables it is, so that evaluating it can go straight to the variable instead of
+// searching every scope on the way by name. The scopes here follow the environments the interpreter
+// creates, and the variables of each are numbered in the order it declares them. Names declared
+// outside the program, such as natives, earlier REPL lines and whatever an import without an alias
+// brings in, stay unresolved and are searched for as before.
+pub fn resolve(program: &mut Node) {
+    let mut resolver = Resolver { scopes: Vec::new() };
+
+    // The top level runs in the environment it is given, as it does in `evaluate_program`.
+    match program {
+        Node::Scope { body } => resolver.resolve_statements(body, Vec::new()),
+        node => resolver.resolve_statements(std::slice::from_mut(node), Vec::new()),
+    }
+}
+
+struct Scope {
+    // The position of each name among the variables the scope declares.
+    slots: HashMap<String, usize>,
+    // Whether the variables are numbered as the interpreter will store them. The top level runs in
+    // an environment that can already hold variables, from the host or earlier REPL lines, and an
+    // import without an alias declares names that cannot be counted before it runs, so only the
+    // depth of a name is known in those scopes.
+    numbered: bool,
+    // Whether the scope imports a file, whose names are not known until it runs. Like every other
+    // name the block declares, these belong to it from the start, so the scope is marked before any
+    // of its statements are resolved.
+    imported: bool,
+}
+
+struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+impl Resolver {
+    // The depth of the scope that declares `name`, and the name's slot in it when that is known.
+    fn find(&self, name: &str) -> Option<(usize, Option<usize>)> {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(&slot) = scope.slots.get(name) {
+                return Some((depth, scope.numbered.then_some(slot)));
+            }
+            if scope.imported {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Every name a block declares belongs to it from the start, so a function declared earlier in
+    // the block finds a name declared after it rather than one of the same name further out. The
+    // statements of a block run in order and a name cannot be declared twice in one scope, so the
+    // n-th name declared here is the n-th variable the environment stores.
+    fn push_scope(&mut self, names: Vec<String>, statements: &[Node]) {
+        let mut names = names;
+        let mut numbered = !self.scopes.is_empty();
+        let mut imported = false;
+        for statement in statements {
+            match statement {
+                Node::VariableDeclaration { name, .. } | Node::ConstantDeclaration { name, .. } => {
+                    names.extend(declared_names(name))
+                }
+                Node::FunctionDeclaration { name, .. } => names.push(name.clone()),
+                Node::ImportStatement {
+                    alias: Some(alias), ..
+                } => names.push(alias.clone()),
+                Node::ImportStatement { alias: None, .. } => {
+                    numbered = false;
+                    imported = true;
+                }
+                _ => (),
+            }
+        }
+
+        let mut slots = HashMap::new();
+        for name in names {
+            let slot = slots.len();
+            slots.entry(name).or_insert(slot);
+        }
+        self.scopes.push(Scope {
+            slots,
+            numbered,
+            imported,
+        });
+    }
+
+    fn resolve_statements(&mut self, statements: &mut [Node], names: Vec<String>) {
+        self.push_scope(names, statements);
+        for statement in statements {
+            self.resolve_node(statement);
+        }
+        self.scopes.pop();
+    }
+
+    // The loop variable of a `for` and the error of a `catch` have a scope of their own around
+    // the block's.
+    fn resolve_block(&mut self, node: &mut Node, names: Vec<String>) {
+        self.push_scope(names, &[]);
+        self.resolve_node(node);
+        self.scopes.pop();
+    }
+
+    // A call runs the body's statements in the same scope as the parameters, and evaluates the
+    // defaults there as well.
+    fn resolve_function(&mut self, parameters: &mut [Parameter], body: &mut Node) {
+        let names = parameters
+            .iter()
+            .map(|parameter| parameter.name.clone())
+            .collect();
+        let statements = match body {
+            Node::Scope { body } => body.as_mut_slice(),
+            node => std::slice::from_mut(node),
+        };
+
+        self.push_scope(names, statements);
+        for parameter in parameters.iter_mut() {
+            if let Some(default) = &mut parameter.default {
+                self.resolve_node(default);
+            }
+        }
+        for statement in statements {
+            self.resolve_node(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_node(&mut self, node: &mut Node) {
+        match node {
+            Node::StringLiteral(_)
+            | Node::CharLiteral(_)
+            | Node::FloatLiteral(_)
+            | Node::IntegerLiteral(_)
+            | Node::BooleanLiteral(_)
+            | Node::NullLiteral
+            | Node::Break { .. }
+            | Node::Continue { .. } => (),
+            Node::Identifier {
+                name, depth, slot, ..
+            } => {
+                let found = self.find(name);
+                *depth = found.map(|(depth, _)| depth);
+                *slot = found.and_then(|(_, slot)| slot);
+            }
+            Node::InterpolatedString { parts } => {
+                for part in parts {
+                    if let StringPart::Expression(expression) = part {
+                        self.resolve_node(expression);
+                    }
+                }
+            }
+            Node::ArrayLiteral(elements) => {
+                for element in elements {
+                    self.resolve_node(element);
+                }
+            }
+            Node::DictionaryLiteral(entries) => {
+                for (_, value) in entries {
+                    self.resolve_node(value);
+                }
+            }
+            Node::BinaryExpression { left, right, .. } => {
+                self.resolve_node(left);
+                self.resolve_node(right);
+            }
+            Node::UnaryExpression { operand, .. } => self.resolve_node(operand),
+            Node::UpdateExpression { target, .. } => self.resolve_node(target),
+            Node::IndexExpression {
+                collection, index, ..
+            } => {
+                self.resolve_node(collection);
+                self.resolve_node(index);
+            }
+            Node::SliceExpression {
+                collection,
+                start,
+                end,
+                ..
+            } => {
+                self.resolve_node(collection);
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_node(bound);
+                }
+            }
+            Node::TernaryExpression {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.resolve_node(condition);
+                self.resolve_node(then);
+                self.resolve_node(otherwise);
+            }
+            Node::AssignmentExpression { name, value, .. } => {
+                self.resolve_node(name);
+                self.resolve_node(value);
+            }
+            Node::VariableDeclaration { value, .. } | Node::ConstantDeclaration { value, .. } => {
+                self.resolve_node(value)
+            }
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Node::WhileLoop {
+                condition,
+                body: then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_node(condition);
+                self.resolve_node(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_node(else_branch);
+                }
+            }
+            Node::DoWhileLoop {
+                body, condition, ..
+            } => {
+                self.resolve_node(body);
+                self.resolve_node(condition);
+            }
+            Node::ForLoop {
+                variable,
+                iterable,
+                body,
+                else_branch,
+                ..
+            } => {
+                self.resolve_node(iterable);
+                self.resolve_block(body, declared_names(variable));
+                if let Some(else_branch) = else_branch {
+                    self.resolve_node(else_branch);
+                }
+            }
+            Node::FunctionDeclaration {
+                parameters, body, ..
+            }
+            | Node::FunctionExpression {
+                parameters, body, ..
+            } => self.resolve_function(parameters, body),
+            Node::CallExpression {
+                callee, arguments, ..
+            } => {
+                self.resolve_node(callee);
+                for argument in arguments {
+                    self.resolve_node(argument);
+                }
+            }
+            Node::NamedArgument { value, .. } => self.resolve_node(value),
+            Node::ReturnStatement { value, .. } => {
+                if let Some(value) = value {
+                    self.resolve_node(value);
+                }
+            }
+            Node::Match { subject, arms, .. } => {
+                self.resolve_node(subject);
+                for (_, body) in arms {
+                    self.resolve_node(body);
+                }
+            }
+            Node::ThrowStatement { value, .. } => self.resolve_node(value),
+            Node::ImportStatement { .. } => (),
+            Node::MemberAccess { object, .. } => self.resolve_node(object),
+            Node::TryCatch {
+                body,
+                error_name,
+                handler,
+                ..
+            } => {
+                self.resolve_node(body);
+                self.resolve_block(handler, vec![error_name.clone()]);
+            }
+            Node::Scope { body } => self.resolve_statements(body, Vec::new()),
+        }
+    }
+}
+
+fn declared_names(target: &Node) -> Vec<String> {
+    match target {
+        Node::Identifier { name, .. } => vec![name.clone()],
+        Node::ArrayLiteral(targets) => targets.iter().flat_map(declared_names).collect(),
+        _ => Vec::new(),
+    }
+}
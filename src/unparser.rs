@@ -0,0 +1,570 @@
+use crate::parser::{BinaryOperator, Node, Parameter, Pattern, StringPart};
+
+// How tightly each kind of expression binds, following the order in which the parser tries them.
+const ASSIGNMENT: u8 = 0;
+const TERNARY: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const EQUALITY: u8 = 4;
+const COMPARISON: u8 = 5;
+const BITWISE_OR: u8 = 6;
+const BITWISE_AND: u8 = 7;
+const SHIFT: u8 = 8;
+const ADDITIVE: u8 = 9;
+const MULTIPLICATIVE: u8 = 10;
+const POWER: u8 = 11;
+const UNARY: u8 = 12;
+const POSTFIX: u8 = 13;
+const PRIMARY: u8 = 14;
+
+const INDENT: &str = "    ";
+
+// Turns a program back into source code that parses to the same tree, apart from positions.
+// Parentheses are only written where precedence needs them, and compound assignments come back in
+// their long form, so `x += 1` is written as `x = x + 1`.
+pub fn unparse(program: &Node) -> String {
+    match program {
+        Node::Scope { body } => statements(body, 0),
+        node => statement(node, 0),
+    }
+}
+
+// Writes a single node, such as an expression or statement taken out of a program.
+pub fn unparse_node(node: &Node) -> String {
+    expression(node, ASSIGNMENT, 0)
+}
+
+// A `-` at the start of a line continues the expression on the line before, so a statement that
+// starts with one is kept apart from the previous statement with a `;`.
+fn statements(body: &[Node], indent: usize) -> String {
+    let lines = body
+        .iter()
+        .map(|node| statement(node, indent))
+        .collect::<Vec<String>>();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let separator = match lines.get(i + 1) {
+                Some(next) if next.starts_with('-') && !next.starts_with("--") => ";",
+                _ => "",
+            };
+            format!("{}{}{}", INDENT.repeat(indent), line, separator)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn block(body: &[Node], indent: usize) -> String {
+    if body.is_empty() {
+        return "{}".to_string();
+    }
+    format!(
+        "{{\n{}\n{}}}",
+        statements(body, indent + 1),
+        INDENT.repeat(indent)
+    )
+}
+
+// The branches and bodies of statements are always scopes when they come from the parser.
+fn branch(node: &Node, indent: usize) -> String {
+    match node {
+        Node::Scope { body } => block(body, indent),
+        node => block(std::slice::from_ref(node), indent),
+    }
+}
+
+fn statement(node: &Node, indent: usize) -> String {
+    match node {
+        Node::VariableDeclaration {
+            name,
+            type_name,
+            value,
+            ..
+        } => format!(
+            "let {}{} = {}",
+            expression(name, PRIMARY, indent),
+            type_annotation(type_name),
+            expression(value, TERNARY, indent)
+        ),
+        Node::ConstantDeclaration {
+            name,
+            type_name,
+            value,
+            ..
+        } => format!(
+            "const {}{} = {}",
+            expression(name, PRIMARY, indent),
+            type_annotation(type_name),
+            expression(value, TERNARY, indent)
+        ),
+        Node::IfStatement {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            let mut source = format!(
+                "if {} {}",
+                expression(condition, ASSIGNMENT, indent),
+                branch(then_branch, indent)
+            );
+            match else_branch.as_deref() {
+                Some(else_if @ Node::IfStatement { .. }) => {
+                    source.push_str(&format!(" else {}", statement(else_if, indent)))
+                }
+                Some(else_branch) => {
+                    source.push_str(&format!(" else {}", branch(else_branch, indent)))
+                }
+                None => (),
+            }
+            source
+        }
+        Node::WhileLoop {
+            condition,
+            body,
+            else_branch,
+            ..
+        } => format!(
+            "while {} {}{}",
+            expression(condition, ASSIGNMENT, indent),
+            branch(body, indent),
+            loop_else(else_branch, indent)
+        ),
+        Node::DoWhileLoop {
+            body, condition, ..
+        } => format!(
+            "do {} while {}",
+            branch(body, indent),
+            expression(condition, ASSIGNMENT, indent)
+        ),
+        Node::ForLoop {
+            variable,
+            iterable,
+            body,
+            else_branch,
+            ..
+        } => format!(
+            "for {} in {} {}{}",
+            expression(variable, PRIMARY, indent),
+            expression(iterable, ASSIGNMENT, indent),
+            branch(body, indent),
+            loop_else(else_branch, indent)
+        ),
+        Node::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+            ..
+        } => format!(
+            "func {}({}) {}",
+            name,
+            parameter_list(parameters, indent),
+            branch(body, indent)
+        ),
+        Node::ReturnStatement { value: None, .. } => "return".to_string(),
+        Node::ReturnStatement {
+            value: Some(value), ..
+        } => format!("return {}", expression(value, ASSIGNMENT, indent)),
+        Node::Break { .. } => "break".to_string(),
+        Node::Continue { .. } => "continue".to_string(),
+        Node::ThrowStatement { value, .. } => {
+            format!("throw {}", expression(value, ASSIGNMENT, indent))
+        }
+        Node::ImportStatement {
+            path, alias: None, ..
+        } => format!("import \"{}\"", escape(path)),
+        Node::ImportStatement {
+            path,
+            alias: Some(alias),
+            ..
+        } => format!("import \"{}\" as {}", escape(path), alias),
+        Node::TryCatch {
+            body,
+            error_name,
+            handler,
+            ..
+        } => format!(
+            "try {} catch ({}) {}",
+            branch(body, indent),
+            error_name,
+            branch(handler, indent)
+        ),
+        // Written without brackets, the way such assignments are usually written.
+        Node::AssignmentExpression { name, value, .. } => match &**name {
+            Node::ArrayLiteral(targets) if targets.len() > 1 => format!(
+                "{} = {}",
+                targets
+                    .iter()
+                    .map(|target| expression(target, TERNARY, indent))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                expression(value, ASSIGNMENT, indent)
+            ),
+            _ => expression(node, ASSIGNMENT, indent),
+        },
+        node => expression(node, ASSIGNMENT, indent),
+    }
+}
+
+fn type_annotation(type_name: &Option<String>) -> String {
+    match type_name {
+        Some(type_name) => format!(": {}", type_name),
+        None => String::new(),
+    }
+}
+
+fn pattern_source(pattern: &Pattern, indent: usize) -> String {
+    match pattern {
+        Pattern::Literal(literal) => expression(literal, PRIMARY, indent),
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Range(start, end) => format!("{}..{}", start, end),
+        Pattern::Alternatives(alternatives) => alternatives
+            .iter()
+            .map(|alternative| pattern_source(alternative, indent))
+            .collect::<Vec<String>>()
+            .join(" | "),
+    }
+}
+
+fn loop_else(else_branch: &Option<Box<Node>>, indent: usize) -> String {
+    match else_branch {
+        Some(else_branch) => format!(" else {}", branch(else_branch, indent)),
+        None => String::new(),
+    }
+}
+
+fn parameter_list(parameters: &[Parameter], indent: usize) -> String {
+    parameters
+        .iter()
+        .map(|parameter| match &parameter.default {
+            Some(default) => format!(
+                "{} = {}",
+                parameter.name,
+                expression(default, TERNARY, indent)
+            ),
+            None if parameter.rest => format!("...{}", parameter.name),
+            None => parameter.name.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn precedence(node: &Node) -> u8 {
+    match node {
+        Node::AssignmentExpression { .. } => ASSIGNMENT,
+        Node::TernaryExpression { .. } => TERNARY,
+        Node::BinaryExpression { operand, .. } => binary_precedence(*operand),
+        Node::UnaryExpression { .. } | Node::UpdateExpression { prefix: true, .. } => UNARY,
+        Node::UpdateExpression { prefix: false, .. }
+        | Node::IndexExpression { .. }
+        | Node::SliceExpression { .. }
+        | Node::MemberAccess { .. }
+        | Node::CallExpression { .. } => POSTFIX,
+        _ => PRIMARY,
+    }
+}
+
+fn binary_precedence(operator: BinaryOperator) -> u8 {
+    match operator {
+        BinaryOperator::Or => OR,
+        BinaryOperator::And => AND,
+        BinaryOperator::Eq | BinaryOperator::Neq => EQUALITY,
+        BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => {
+            COMPARISON
+        }
+        BinaryOperator::BitOr => BITWISE_OR,
+        BinaryOperator::BitAnd => BITWISE_AND,
+        BinaryOperator::Shl | BinaryOperator::Shr => SHIFT,
+        BinaryOperator::Add | BinaryOperator::Sub => ADDITIVE,
+        BinaryOperator::Mul
+        | BinaryOperator::Div
+        | BinaryOperator::FloorDiv
+        | BinaryOperator::Mod => MULTIPLICATIVE,
+        BinaryOperator::Pow => POWER,
+    }
+}
+
+// `minimum` is the loosest binding the surrounding syntax accepts without parentheses.
+fn expression(node: &Node, minimum: u8, indent: usize) -> String {
+    let source = match node {
+        Node::StringLiteral(s) => format!("\"{}\"", escape(s)),
+        Node::CharLiteral('\'') => "'\\''".to_string(),
+        Node::CharLiteral(c) => format!("'{}'", escape(&c.to_string())),
+        Node::InterpolatedString { parts } => {
+            let parts = parts
+                .iter()
+                .map(|part| match part {
+                    StringPart::Literal(s) => escape(s),
+                    StringPart::Expression(node) => {
+                        format!("${{{}}}", expression(node, ASSIGNMENT, indent))
+                    }
+                })
+                .collect::<String>();
+            format!("\"{}\"", parts)
+        }
+        Node::FloatLiteral(f) => format!("{:?}", f),
+        Node::IntegerLiteral(i) => i.to_string(),
+        Node::BooleanLiteral(b) => b.to_string(),
+        Node::NullLiteral => "null".to_string(),
+        Node::ArrayLiteral(elements) => format!(
+            "[{}]",
+            elements
+                .iter()
+                .map(|element| expression(element, ASSIGNMENT, indent))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Node::DictionaryLiteral(entries) if entries.is_empty() => "{:}".to_string(),
+        Node::DictionaryLiteral(entries) => format!(
+            "{{{}}}",
+            entries
+                .iter()
+                .map(|(key, value)| format!(
+                    "\"{}\": {}",
+                    escape(key),
+                    expression(value, ASSIGNMENT, indent)
+                ))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Node::Identifier { name, .. } => name.clone(),
+        Node::BinaryExpression {
+            left,
+            operand,
+            right,
+            ..
+        } => {
+            let level = binary_precedence(*operand);
+            // `^` groups to the right and binds looser than the unary operators on its left.
+            let (left_level, right_level) = match operand {
+                BinaryOperator::Pow => (UNARY, POWER),
+                _ => (level, level + 1),
+            };
+            format!(
+                "{} {} {}",
+                expression(left, left_level, indent),
+                operand,
+                expression(right, right_level, indent)
+            )
+        }
+        // A space keeps `- -x` from being read as `--x`.
+        Node::UnaryExpression {
+            operator, operand, ..
+        } => {
+            let operand = expression(operand, UNARY, indent);
+            match operand.starts_with(['-', '+']) {
+                true => format!("{} {}", operator, operand),
+                false => format!("{}{}", operator, operand),
+            }
+        }
+        Node::UpdateExpression {
+            target,
+            operator,
+            prefix: true,
+            ..
+        } => {
+            let target = expression(target, UNARY, indent);
+            match target.starts_with(['-', '+']) {
+                true => format!("{} {}", operator, target),
+                false => format!("{}{}", operator, target),
+            }
+        }
+        Node::UpdateExpression {
+            target, operator, ..
+        } => format!("{}{}", expression(target, POSTFIX, indent), operator),
+        Node::IndexExpression {
+            collection, index, ..
+        } => format!(
+            "{}[{}]",
+            expression(collection, POSTFIX, indent),
+            expression(index, ASSIGNMENT, indent)
+        ),
+        Node::SliceExpression {
+            collection,
+            start,
+            end,
+            ..
+        } => {
+            let bound = |bound: &Option<Box<Node>>| match bound {
+                Some(bound) => expression(bound, ASSIGNMENT, indent),
+                None => String::new(),
+            };
+            format!(
+                "{}[{}:{}]",
+                expression(collection, POSTFIX, indent),
+                bound(start),
+                bound(end)
+            )
+        }
+        Node::TernaryExpression {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => format!(
+            "{} ? {} : {}",
+            expression(condition, OR, indent),
+            expression(then, TERNARY, indent),
+            expression(otherwise, TERNARY, indent)
+        ),
+        Node::AssignmentExpression { name, value, .. } => format!(
+            "{} = {}",
+            expression(name, TERNARY, indent),
+            expression(value, ASSIGNMENT, indent)
+        ),
+        Node::CallExpression {
+            callee, arguments, ..
+        } => format!(
+            "{}({})",
+            expression(callee, POSTFIX, indent),
+            arguments
+                .iter()
+                .map(|argument| expression(argument, ASSIGNMENT, indent))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Node::MemberAccess { object, member, .. } => {
+            format!("{}.{}", expression(object, POSTFIX, indent), member)
+        }
+        Node::NamedArgument { name, value, .. } => {
+            format!("{}: {}", name, expression(value, ASSIGNMENT, indent))
+        }
+        Node::FunctionExpression {
+            parameters, body, ..
+        } => format!(
+            "func({}) {}",
+            parameter_list(parameters, indent),
+            branch(body, indent)
+        ),
+        Node::Match { subject, arms, .. } => {
+            let arms = arms
+                .iter()
+                .map(|(pattern, result)| {
+                    format!(
+                        "{}{} => {},\n",
+                        INDENT.repeat(indent + 1),
+                        pattern_source(pattern, indent + 1),
+                        expression(result, ASSIGNMENT, indent + 1)
+                    )
+                })
+                .collect::<String>();
+            format!(
+                "match {} {{\n{}{}}}",
+                expression(subject, ASSIGNMENT, indent),
+                arms,
+                INDENT.repeat(indent)
+            )
+        }
+        Node::Scope { body } => block(body, indent),
+        node => statement(node, indent),
+    };
+
+    if precedence(node) < minimum {
+        format!("({})", source)
+    } else {
+        source
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for character in s.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '$' => escaped.push_str("\\$"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    // Unparsing a tree and parsing the result again gives the same tree, and unparsing that gives
+    // the same source. Trees are compared by how they display, which leaves out positions.
+    fn assert_round_trips(source_code: &str) {
+        let tree = parse(source_code).unwrap_or_else(|errors| panic!("{:?}", errors));
+        let unparsed = unparse(&tree);
+        let reparsed =
+            parse(&unparsed).unwrap_or_else(|errors| panic!("{:?} in\n{}", errors, unparsed));
+        assert_eq!(tree.to_string(), reparsed.to_string(), "{}", unparsed);
+        assert_eq!(unparsed, unparse(&reparsed));
+    }
+
+    // A small linear congruential generator, so the generated expressions are the same every run.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    const OPERATORS: [&str; 19] = [
+        "+", "-", "*", "/", "~/", "%", "^", "==", "!=", "<", ">", "<=", ">=", "&&", "||", "&", "|",
+        "<<", ">>",
+    ];
+
+    fn random_expression(random: &mut Random, depth: usize) -> String {
+        let choice = if depth == 0 { 0 } else { random.below(7) };
+        match choice {
+            0 => ["x", "1", "2.5", "\"s\"", "true", "null", "f(y)"][random.below(7)].to_string(),
+            1 => format!("({})", random_expression(random, depth - 1)),
+            2 => format!(
+                "{} {}",
+                ["- ", "!", "~"][random.below(3)],
+                random_expression(random, depth - 1)
+            ),
+            3 => format!(
+                "{} ? {} : {}",
+                random_expression(random, depth - 1),
+                random_expression(random, depth - 1),
+                random_expression(random, depth - 1)
+            ),
+            _ => format!(
+                "{} {} {}",
+                random_expression(random, depth - 1),
+                OPERATORS[random.below(OPERATORS.len())],
+                random_expression(random, depth - 1)
+            ),
+        }
+    }
+
+    #[test]
+    fn generated_expressions_round_trip() {
+        let mut random = Random(1);
+        for _ in 0..2000 {
+            assert_round_trips(&random_expression(&mut random, 4));
+        }
+    }
+
+    #[test]
+    fn statements_round_trip() {
+        for source_code in [
+            "let x: integer = 1\nconst [a, [b, c]] = [1, [2, 3]]\nx += 2\nx++\n--x",
+            "func f(a, b = 2, ...rest) {\n    return a ^ b ^ 2\n}\nlet g = func (x) { return -x }",
+            "if a { 1 } else if b { 2 } else { 3 }\nwhile x < 3 { x = x + 1 } else { 0 }",
+            "do { x = x - 1 } while x > 0\nfor i in items { continue } else { 0 }",
+            "match n { 0 => \"none\", 1 | 2 => \"few\", 3..9 => \"some\", _ => null }",
+            "try { throw {\"code\": 1} } catch (e) { e.code }\nimport \"lib.jm\" as lib",
+            "let d = {\"a\": [1, 2.0, 'c']}\nd.a[0] = d[\"a\"][1:]\ns[:2]\n\"x = ${x + 1}\\n\"",
+            "f(1, named: 2)(3)\n(a = b)\na, b = [b, a]\n-2 ^ 2\n(-2) ^ 2\n2 ^ -2",
+        ] {
+            assert_round_trips(source_code);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// Runs the `jm` binary on small scripts and checks what it prints. Each test runs in a directory of
+// its own under the system's temporary directory, because running a script writes `ast.json` there.
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+fn directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("jm-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+// Writes `files` into the test's directory and runs `jm` with `arguments` there.
+fn jm(test: &str, files: &[(&str, &str)], arguments: &[&str]) -> Output {
+    let directory = directory(test);
+    for (name, source_code) in files {
+        let path = directory.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source_code).unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_jm"))
+        .args(arguments)
+        .current_dir(&directory)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn print_and_println_write_to_standard_output() {
+    let output = jm(
+        "print",
+        &[(
+            "main.jm",
+            "print(\"a\", 1)\nprint(\"b\")\nprintln()\nprintln(\"c\", [1, 2], null)\nprintln(2.5)",
+        )],
+        &["main.jm"],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "a 1b\nc [1, 2] null\n2.5\n");
+}
+
+#[test]
+fn scripts_run_from_start_to_end() {
+    let output = jm(
+        "script",
+        &[(
+            "main.jm",
+            "func square(x) { return x * x }\nfor i in range(1, 4) { println(square(i)) }",
+        )],
+        &["main.jm"],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1\n4\n9\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn scripts_that_fail_exit_with_an_error() {
+    let output = jm(
+        "failing",
+        &[(
+            "main.jm",
+            "println(\"before\")\nlet x = 1 / 0\nprintln(\"after\")",
+        )],
+        &["main.jm"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "before\n");
+    assert_eq!(
+        stderr(&output),
+        "ZeroDivisionError: Division by zero in line 2 column 11\n2 | let x = 1 / 0\n  |           ^\n"
+    );
+
+    let output = jm("missing", &[], &["missing.jm"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).starts_with("Cannot read 'missing.jm': "));
+}
+
+#[test]
+fn imports_run_files_relative_to_the_importer() {
+    let output = jm(
+        "import",
+        &[
+            (
+                "main.jm",
+                "import \"lib/geometry.jm\"\nprintln(area(3), SIDES)",
+            ),
+            (
+                "lib/geometry.jm",
+                "import \"shapes.jm\"\nfunc area(x) { square(x) }",
+            ),
+            ("lib/shapes.jm", "func square(x) { x * x }\nconst SIDES = 4"),
+        ],
+        &["main.jm"],
+    );
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "9 4\n");
+}
+
+#[test]
+fn circular_imports_are_an_error() {
+    let output = jm(
+        "circular",
+        &[("a.jm", "import \"b.jm\""), ("b.jm", "import \"a.jm\"")],
+        &["a.jm"],
+    );
+    assert!(!output.status.success());
+    assert!(stderr(&output).starts_with("ImportError: Circular import of 'a.jm'"));
+}
+
+#[test]
+fn imports_with_an_alias_keep_their_names_in_a_module() {
+    let files = [
+        ("shapes.jm", "func square(x) { x * x }\nconst SIDES = 4"),
+        (
+            "main.jm",
+            "func square(x) { -1 }\nimport \"shapes.jm\" as shapes\nprintln(shapes.square(5), shapes.SIDES, square(5))",
+        ),
+    ];
+    let output = jm("alias", &files, &["main.jm"]);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "25 4 -1\n");
+
+    let output = jm(
+        "missing-member",
+        &[
+            files[0],
+            ("main.jm", "import \"shapes.jm\" as shapes\nshapes.cube(2)"),
+        ],
+        &["main.jm"],
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).starts_with("NameError: Module 'shapes' has no member 'cube' in line 2")
+    );
+}
+
+#[test]
+fn trace_prints_each_step_and_its_value() {
+    let output = jm(
+        "trace",
+        &[("main.jm", "let x = 1 + 2 * 3\nx - 1")],
+        &["--trace", "main.jm"],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "\
+let x = 1 + 2 * 3
+  1 + 2 * 3
+    1
+    => 1
+    2 * 3
+      2
+      => 2
+      3
+      => 3
+    => 6
+  => 7
+=> 7
+x - 1
+  x
+  => 7
+  1
+  => 1
+=> 6
+"
+    );
+
+    let output = jm(
+        "no-trace",
+        &[("main.jm", "let x = 1 + 2 * 3\nx - 1")],
+        &["main.jm"],
+    );
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn functions_declared_before_an_import_find_its_names() {
+    let output = jm(
+        "import-after-function",
+        &[
+            ("lib.jm", "let v = \"from lib\""),
+            (
+                "main.jm",
+                "let v = \"outer\"\nif true {\n    func get() { return v }\n    import \"lib.jm\"\n    println(get())\n}",
+            ),
+        ],
+        &["main.jm"],
+    );
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "from lib\n");
+}